@@ -0,0 +1,514 @@
+//! Threshold based alerting on the current statistics.
+//!
+//! Rules are read from a file with one rule per line:
+//!
+//! ```text
+//! # [name:] metric operator threshold [for duration] => action
+//! memory_pressure: pressure.memory_some_avg10 > 20 for 30s => log
+//! slow_disk: blockdevice.*.await > 50ms for 10s => webhook http://127.0.0.1:8080/alert
+//! swapping: vmstat.pswpin/s > 100 => command logger "procstat swapping"
+//! ```
+//!
+//! A metric is `category.name` or `category.subcategory.name` as used for the keys of the
+//! statistics map, where subcategory can be `*` to evaluate the rule for every subcategory.
+//! The value is the last value of the statistic, or the per second value if the name ends
+//! with `/s`. Next to the statistics, the derived metrics in [`derived_value`] can be used.
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Local};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use tokio::time::{timeout, Duration as TokioDuration};
+
+use crate::http::{http_post, parse_http_url};
+use crate::processor::Statistic;
+
+/// The time a webhook can take, after which the action fails.
+const WEBHOOK_TIMEOUT: TokioDuration = TokioDuration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Operator {
+    fn parse(operator: &str) -> Option<Self> {
+        match operator {
+            ">" => Some(Operator::Greater),
+            ">=" => Some(Operator::GreaterOrEqual),
+            "<" => Some(Operator::Less),
+            "<=" => Some(Operator::LessOrEqual),
+            "==" => Some(Operator::Equal),
+            "!=" => Some(Operator::NotEqual),
+            _ => None,
+        }
+    }
+    fn compare(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Operator::Greater => value > threshold,
+            Operator::GreaterOrEqual => value >= threshold,
+            Operator::Less => value < threshold,
+            Operator::LessOrEqual => value <= threshold,
+            Operator::Equal => value == threshold,
+            Operator::NotEqual => value != threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Log,
+    Command(String),
+    Webhook { host: String, path: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    name: String,
+    category: String,
+    subcategory: String,
+    statistic: String,
+    per_second: bool,
+    operator: Operator,
+    threshold: f64,
+    duration: Duration,
+    action: Action,
+}
+
+/// The state of a rule for a single subcategory.
+#[derive(Debug, Default)]
+struct RuleState {
+    pending_since: Option<DateTime<Local>>,
+    firing: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Alert {
+    rule: String,
+    state: &'static str,
+    metric: String,
+    value: f64,
+    threshold: f64,
+    timestamp: DateTime<Local>,
+}
+
+#[derive(Debug, Default)]
+pub struct Alerting {
+    rules: Vec<Rule>,
+    states: HashMap<(usize, String), RuleState>,
+}
+
+impl Alerting {
+    pub fn from_file(filename: &str) -> Result<Self> {
+        let rules = read_to_string(filename)
+            .with_context(|| format!("Unable to read rules file {}", filename))?;
+        Self::parse(&rules).with_context(|| format!("Rules file {}", filename))
+    }
+    pub fn parse(rules: &str) -> Result<Self> {
+        let rules = rules
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim().starts_with('#'))
+            .map(|(number, line)| {
+                parse_rule(line.trim()).with_context(|| format!("line {}: {}", number + 1, line))
+            })
+            .collect::<Result<Vec<Rule>>>()?;
+        Ok(Alerting {
+            rules,
+            states: HashMap::new(),
+        })
+    }
+    /// Evaluate all rules against the statistics, and perform the action of the rules that
+    /// changed from or to firing.
+    pub async fn evaluate(
+        &mut self,
+        statistics: &HashMap<(String, String, String), Statistic>,
+        timestamp: DateTime<Local>,
+    ) {
+        let mut alerts = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            for subcategory in subcategories(rule, statistics) {
+                let Some(value) = rule_value(rule, &subcategory, statistics) else {
+                    continue;
+                };
+                let state = self.states.entry((index, subcategory.clone())).or_default();
+                let state_change = if rule.operator.compare(value, rule.threshold) {
+                    let pending_since = *state.pending_since.get_or_insert(timestamp);
+                    if !state.firing && timestamp - pending_since >= rule.duration {
+                        state.firing = true;
+                        Some("firing")
+                    } else {
+                        None
+                    }
+                } else {
+                    state.pending_since = None;
+                    if state.firing {
+                        state.firing = false;
+                        Some("resolved")
+                    } else {
+                        None
+                    }
+                };
+                if let Some(state) = state_change {
+                    alerts.push((
+                        rule.action.clone(),
+                        Alert {
+                            rule: rule.name.clone(),
+                            state,
+                            metric: metric_name(rule, &subcategory),
+                            value,
+                            threshold: rule.threshold,
+                            timestamp,
+                        },
+                    ));
+                }
+            }
+        }
+        for (action, alert) in alerts {
+            // the actions are performed in the background, so a slow command or webhook
+            // does not delay fetching the statistics.
+            tokio::spawn(async move {
+                if let Err(error) = perform_action(&action, &alert).await {
+                    eprintln!("Alert action for rule {} failed: {:?}", alert.rule, error);
+                }
+            });
+        }
+    }
+}
+
+fn parse_rule(line: &str) -> Result<Rule> {
+    let Some((expression, action)) = line.split_once("=>") else {
+        bail!("missing => action");
+    };
+    let (name, expression) = match expression.split_once(':') {
+        Some((name, expression)) => (name.trim().to_string(), expression.trim()),
+        None => (expression.trim().to_string(), expression.trim()),
+    };
+    let words = expression.split_whitespace().collect::<Vec<&str>>();
+    let (metric, operator, threshold) = match words[..] {
+        [metric, operator, threshold] | [metric, operator, threshold, "for", _] => {
+            (metric, operator, threshold)
+        }
+        _ => bail!("expected: metric operator threshold [for duration]"),
+    };
+    let duration = match words[..] {
+        [_, _, _, "for", duration] => parse_duration(duration)?,
+        _ => Duration::zero(),
+    };
+    let mut parts = metric.split('.').collect::<Vec<&str>>();
+    if parts.len() < 2 {
+        bail!(
+            "metric {} should be category.name or category.subcategory.name",
+            metric
+        );
+    }
+    let category = parts.remove(0).to_string();
+    let statistic = parts.pop().unwrap();
+    let subcategory = parts.join(".");
+    let (statistic, per_second) = match statistic.strip_suffix("/s") {
+        Some(statistic) => (statistic.to_string(), true),
+        None => (statistic.to_string(), false),
+    };
+    Ok(Rule {
+        name,
+        category,
+        subcategory,
+        statistic,
+        per_second,
+        operator: Operator::parse(operator)
+            .with_context(|| format!("invalid operator {}", operator))?,
+        threshold: parse_threshold(threshold)?,
+        duration,
+        action: parse_action(action.trim())?,
+    })
+}
+
+/// Thresholds are numbers, optionally with a % sign, or a time which is converted to
+/// milliseconds, which is the unit of the latency statistics.
+fn parse_threshold(threshold: &str) -> Result<f64> {
+    let (number, multiplier) = if let Some(number) = threshold.strip_suffix("ms") {
+        (number, 1_f64)
+    } else if let Some(number) = threshold.strip_suffix("us") {
+        (number, 0.001_f64)
+    } else if let Some(number) = threshold.strip_suffix('s') {
+        (number, 1000_f64)
+    } else if let Some(number) = threshold.strip_suffix('%') {
+        (number, 1_f64)
+    } else {
+        (threshold, 1_f64)
+    };
+    Ok(number
+        .parse::<f64>()
+        .with_context(|| format!("invalid threshold {}", threshold))?
+        * multiplier)
+}
+
+fn parse_duration(duration: &str) -> Result<Duration> {
+    let (number, seconds) = if let Some(number) = duration.strip_suffix('s') {
+        (number, 1)
+    } else if let Some(number) = duration.strip_suffix('m') {
+        (number, 60)
+    } else if let Some(number) = duration.strip_suffix('h') {
+        (number, 3600)
+    } else {
+        bail!("duration {} needs a unit: s, m or h", duration);
+    };
+    Ok(Duration::seconds(
+        number
+            .parse::<i64>()
+            .with_context(|| format!("invalid duration {}", duration))?
+            * seconds,
+    ))
+}
+
+fn parse_action(action: &str) -> Result<Action> {
+    let (kind, argument) = action.split_once(' ').unwrap_or((action, ""));
+    match (kind, argument.trim()) {
+        ("log", "") => Ok(Action::Log),
+        ("command", "") => bail!("command action needs a command"),
+        ("command", command) => Ok(Action::Command(command.to_string())),
+        ("webhook", url) => {
+            let (host, path) = parse_http_url(url).context("invalid webhook url")?;
+            Ok(Action::Webhook { host, path })
+        }
+        _ => bail!("invalid action {}, use log, command or webhook", action),
+    }
+}
+
+fn metric_name(rule: &Rule, subcategory: &str) -> String {
+    let statistic = if rule.per_second {
+        format!("{}/s", rule.statistic)
+    } else {
+        rule.statistic.clone()
+    };
+    if subcategory.is_empty() {
+        format!("{}.{}", rule.category, statistic)
+    } else {
+        format!("{}.{}.{}", rule.category, subcategory, statistic)
+    }
+}
+
+/// The subcategories the rule applies to: all subcategories of the category for a wildcard.
+fn subcategories(
+    rule: &Rule,
+    statistics: &HashMap<(String, String, String), Statistic>,
+) -> Vec<String> {
+    if rule.subcategory != "*" {
+        return vec![rule.subcategory.clone()];
+    }
+    let mut subcategories = statistics
+        .keys()
+        .filter(|(category, _, _)| category == &rule.category)
+        .map(|(_, subcategory, _)| subcategory.clone())
+        .collect::<Vec<String>>();
+    subcategories.sort();
+    subcategories.dedup();
+    subcategories
+}
+
+/// The value for the rule, or None if it doesn't exist or has no valid value yet.
+fn rule_value(
+    rule: &Rule,
+    subcategory: &str,
+    statistics: &HashMap<(String, String, String), Statistic>,
+) -> Option<f64> {
+    match statistics.get(&(
+        rule.category.clone(),
+        subcategory.to_string(),
+        rule.statistic.clone(),
+    )) {
+        // the first time a statistic is found, there is no per second value yet.
+        Some(statistic) if rule.per_second && !statistic.updated_value => None,
+        Some(statistic) if rule.per_second => Some(statistic.per_second_value),
+        Some(statistic) => Some(statistic.last_value),
+        None => derived_value(&rule.category, subcategory, &rule.statistic, statistics),
+    }
+}
+
+/// Metrics that are calculated from the statistics in the same way as the CLI output:
+/// - blockdevice: await, r_await, w_await (ms), tps, aqu-sz.
+/// - stat: %usr, %nice, %sys, %iowait, %steal, %irq, %soft, %guest, %gnice, %idle.
+fn derived_value(
+    category: &str,
+    subcategory: &str,
+    name: &str,
+    statistics: &HashMap<(String, String, String), Statistic>,
+) -> Option<f64> {
+    let per_second = |statistic: &str| {
+        statistics
+            .get(&(
+                category.to_string(),
+                subcategory.to_string(),
+                statistic.to_string(),
+            ))
+            .filter(|statistic| statistic.updated_value)
+            .map(|statistic| statistic.per_second_value)
+    };
+    let ratio = |numerator: f64, denominator: f64| {
+        if denominator == 0_f64 {
+            0_f64
+        } else {
+            numerator / denominator
+        }
+    };
+    match (category, name) {
+        ("blockdevice", "await") => Some(ratio(
+            per_second("stat_reads_time_spent_ms")? + per_second("stat_writes_time_spent_ms")?,
+            per_second("stat_reads_completed_success")?
+                + per_second("stat_writes_completed_success")?,
+        )),
+        ("blockdevice", "r_await") => Some(ratio(
+            per_second("stat_reads_time_spent_ms")?,
+            per_second("stat_reads_completed_success")?,
+        )),
+        ("blockdevice", "w_await") => Some(ratio(
+            per_second("stat_writes_time_spent_ms")?,
+            per_second("stat_writes_completed_success")?,
+        )),
+        ("blockdevice", "tps") => Some(
+            per_second("stat_reads_completed_success")?
+                + per_second("stat_writes_completed_success")?,
+        ),
+        ("blockdevice", "aqu-sz") => {
+            Some(per_second("stat_ios_weighted_time_spent_ms")? / 1000_f64)
+        }
+        ("stat", percentage) if percentage.starts_with('%') => {
+            let statistic = match percentage {
+                "%usr" => "user",
+                "%nice" => "nice",
+                "%sys" => "system",
+                "%iowait" => "iowait",
+                "%steal" => "steal",
+                "%irq" => "irq",
+                "%soft" => "softirq",
+                "%guest" => "guest",
+                "%gnice" => "guest_nice",
+                "%idle" => "idle",
+                _ => return None,
+            };
+            let total = [
+                "user",
+                "nice",
+                "system",
+                "iowait",
+                "steal",
+                "irq",
+                "softirq",
+                "guest",
+                "guest_nice",
+                "idle",
+            ]
+            .iter()
+            .map(|statistic| per_second(statistic))
+            .sum::<Option<f64>>()?;
+            Some(ratio(per_second(statistic)?, total) * 100_f64)
+        }
+        _ => None,
+    }
+}
+
+async fn perform_action(action: &Action, alert: &Alert) -> Result<()> {
+    match action {
+        Action::Log => {
+            eprintln!(
+                "{} alert {}: {} {} = {:.2} (threshold {})",
+                alert.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                alert.state,
+                alert.rule,
+                alert.metric,
+                alert.value,
+                alert.threshold
+            );
+        }
+        Action::Command(command) => {
+            let status = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .env("PROCSTAT_ALERT_RULE", &alert.rule)
+                .env("PROCSTAT_ALERT_STATE", alert.state)
+                .env("PROCSTAT_ALERT_METRIC", &alert.metric)
+                .env("PROCSTAT_ALERT_VALUE", alert.value.to_string())
+                .env("PROCSTAT_ALERT_THRESHOLD", alert.threshold.to_string())
+                .status()
+                .await
+                .with_context(|| format!("Unable to run {}", command))?;
+            if !status.success() {
+                bail!("{} exited with {}", command, status);
+            }
+        }
+        Action::Webhook { host, path } => {
+            timeout(
+                WEBHOOK_TIMEOUT,
+                http_post(
+                    host,
+                    path,
+                    "application/json",
+                    "",
+                    serde_json::to_string(alert)?,
+                ),
+            )
+            .await
+            .context("timeout")
+            .and_then(|result| result)
+            .with_context(|| format!("webhook {}{}", host, path))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rule_fires_and_resolves() {
+        let mut alerting =
+            Alerting::parse("slow_disk: blockdevice.*.await > 50ms for 2s => log\n").unwrap();
+        let start: DateTime<Local> = DateTime::parse_from_rfc3339("2023-12-13T15:20:24+00:00")
+            .unwrap()
+            .into();
+        let mut statistics = HashMap::new();
+        for (second, time_spent_ms) in [
+            (0, 0_f64),
+            (1, 100_f64),
+            (2, 100_f64),
+            (3, 100_f64),
+            (4, 10_f64),
+        ] {
+            for (name, value) in [
+                ("stat_reads_time_spent_ms", time_spent_ms),
+                ("stat_writes_time_spent_ms", 0_f64),
+                ("stat_reads_completed_success", 1_f64),
+                ("stat_writes_completed_success", 0_f64),
+            ] {
+                statistics.insert(
+                    (
+                        "blockdevice".to_string(),
+                        "sda".to_string(),
+                        name.to_string(),
+                    ),
+                    Statistic {
+                        last_timestamp: start + Duration::seconds(second),
+                        last_value: 0_f64,
+                        delta_value: value,
+                        per_second_value: value,
+                        updated_value: second > 0,
+                    },
+                );
+            }
+            alerting
+                .evaluate(&statistics, start + Duration::seconds(second))
+                .await;
+            let firing = alerting
+                .states
+                .get(&(0, "sda".to_string()))
+                .is_some_and(|state| state.firing);
+            assert_eq!(firing, second == 3, "second {}", second);
+        }
+    }
+}
@@ -1,5 +1,7 @@
-use crate::processor::read_proc_data_and_process;
+use crate::alerting::Alerting;
+use crate::exporter::Exporters;
 use crate::processor::Statistic;
+use crate::processor::{probe_collectors, read_proc_data_and_process};
 use crate::OutputOptions;
 use crate::ARGS;
 use anyhow::{Context, Result};
@@ -18,120 +20,50 @@ use crate::processor::stat::{print_all_cpu, print_per_cpu};
 use crate::processor::vmstat::print_vmstat;
 
 pub async fn app() -> Result<()> {
-    let mut interval = time::interval(Duration::from_secs(ARGS.interval));
+    probe_collectors().await;
+    if !ARGS.deamon {
+        ARGS.output.check_collectors()?;
+    }
+
+    let mut interval = time::interval(Duration::from_secs_f64(ARGS.interval));
     interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
     let mut current_statistics: HashMap<(String, String, String), Statistic> = HashMap::new();
     let mut output_counter = 0_u64;
-    loop {
+    let mut alerting = match &ARGS.rules {
+        Some(rules) => Some(Alerting::from_file(rules)?),
+        None => None,
+    };
+    let mut exporters = match &ARGS.export {
+        Some(urls) => Some(Exporters::start(urls)?),
+        None => None,
+    };
+    for fetch in 0_u64.. {
         interval.tick().await;
 
-        read_proc_data_and_process(&mut current_statistics)
+        let collectors = read_proc_data_and_process(&mut current_statistics, fetch)
             .await
             .with_context(|| "Processor: read proc data and process")?;
 
-        if !ARGS.deamon {
-            let print_header = output_counter % ARGS.header_print == 0;
-            match ARGS.output {
-                OutputOptions::SarU => print_all_cpu(&current_statistics, "sar-u", print_header)
-                    .await
-                    .with_context(|| "print_all_cpu sar-u")?,
-                OutputOptions::SarB => print_vmstat(&current_statistics, "sar-B", print_header)
-                    .await
-                    .with_context(|| "print_vmstat sar-B")?,
-                OutputOptions::Sarb => print_diskstats(&current_statistics, "sar-b", print_header)
-                    .await
-                    .with_context(|| "print_diskstats sar-b")?,
-                OutputOptions::SarUAll => {
-                    print_all_cpu(&current_statistics, "sar-u-ALL", print_header)
-                        .await
-                        .with_context(|| "print_all_cpu sar-u-ALL")?
-                }
-                OutputOptions::CpuAll => {
-                    print_all_cpu(&current_statistics, "cpu-all", print_header)
-                        .await
-                        .with_context(|| "print_all_cpu cpu-all")?
-                }
-                OutputOptions::Schedstat => print_per_cpu(&current_statistics, "schedstat")
-                    .await
-                    .with_context(|| "print_per_cpu schedstat")?,
-                OutputOptions::MpstatPAll => print_per_cpu(&current_statistics, "mpstat-P-ALL")
-                    .await
-                    .with_context(|| "print_per_cpu mpstat-P-ALL")?,
-                OutputOptions::PerCpuAll => print_per_cpu(&current_statistics, "per-cpu-all")
-                    .await
-                    .with_context(|| "print_per_cpu per-cpu-all")?,
-                OutputOptions::SarD => print_diskstats(&current_statistics, "sar-d", print_header)
-                    .await
-                    .with_context(|| "print_diskstats sar-d")?,
-                OutputOptions::Iostat => {
-                    print_diskstats(&current_statistics, "iostat", print_header)
-                        .await
-                        .with_context(|| "print_diskstats iostat")?
-                }
-                OutputOptions::IostatX => {
-                    print_diskstats(&current_statistics, "iostat-x", print_header)
-                        .await
-                        .with_context(|| "print_diskstats iostat-x")?
-                }
-                OutputOptions::Ioq => print_diskstats(&current_statistics, "ioq", print_header)
-                    .await
-                    .with_context(|| "print_diskstats ioq")?,
-                OutputOptions::Ios => print_diskstats(&current_statistics, "ios", print_header)
-                    .await
-                    .with_context(|| "print_diskstats ios")?,
-                OutputOptions::SarH => print_meminfo(&current_statistics, "sar-H", print_header)
-                    .await
-                    .with_context(|| "print_meminfo sar-H")?,
-                OutputOptions::SarR => print_meminfo(&current_statistics, "sar-r", print_header)
-                    .await
-                    .with_context(|| "print_meminfo sar-r")?,
-                OutputOptions::SarRAll => {
-                    print_meminfo(&current_statistics, "sar-r-ALL", print_header)
-                        .await
-                        .with_context(|| "print_meminfo sar-r-ALL")?
-                }
-                OutputOptions::SarNDev => print_net_dev(&current_statistics, "sar-n-DEV")
-                    .await
-                    .with_context(|| "print_net_dev sar-n-DEV")?,
-                OutputOptions::SarNEdev => print_net_dev(&current_statistics, "sar-n-EDEV")
-                    .await
-                    .with_context(|| "print_net_dev sar-n-EDEV")?,
-                OutputOptions::SarQCpu => print_psi(&current_statistics, "sar-q-CPU", print_header)
-                    .await
-                    .with_context(|| "print_psi sar-q-CPU")?,
-                OutputOptions::SarQLoad => {
-                    print_loadavg(&current_statistics, "sar-q-LOAD", print_header)
-                        .await
-                        .with_context(|| "print sar-q-LOAD")?
-                }
-                OutputOptions::SarQIo => print_psi(&current_statistics, "sar-q-IO", print_header)
-                    .await
-                    .with_context(|| "print_psi sar-q-IO")?,
-                OutputOptions::SarQMem => print_psi(&current_statistics, "sar-q-MEM", print_header)
-                    .await
-                    .with_context(|| "print_psi sar-q-MEM")?,
-                OutputOptions::SarQ => {
-                    print_loadavg(&current_statistics, "sar-q-LOAD", print_header)
-                        .await
-                        .with_context(|| "print_psi sar-q-LOAD")?
-                }
-                OutputOptions::SarS => print_meminfo(&current_statistics, "sar-S", print_header)
-                    .await
-                    .with_context(|| "print_meminfo sar-S")?,
-                OutputOptions::SarW => print_vmstat(&current_statistics, "sar-W", print_header)
-                    .await
-                    .with_context(|| "print_vmstat sar-W")?,
-                OutputOptions::Sarw => print_all_cpu(&current_statistics, "sar-w", print_header)
-                    .await
-                    .with_context(|| "print_all_cpu sar-w")?,
-                OutputOptions::Vmstat => print_vmstat(&current_statistics, "vmstat", print_header)
-                    .await
-                    .with_context(|| "print_vmstat vmstat")?,
-                OutputOptions::Free => print_vmstat(&current_statistics, "free", print_header)
-                    .await
-                    .with_context(|| "print_vmstat free")?,
-            }
+        if let Some(alerting) = alerting.as_mut() {
+            alerting.evaluate(&current_statistics, Local::now()).await;
+        }
+
+        if let Some(exporters) = exporters.as_mut() {
+            exporters.export(&current_statistics);
+        }
+
+        // with collectors that have their own interval, the output is printed when all its
+        // collectors are read.
+        if !ARGS.deamon
+            && ARGS
+                .output
+                .collectors()
+                .iter()
+                .all(|collector| collectors.contains(collector))
+        {
+            let print_header = output_counter.is_multiple_of(ARGS.header_print);
+            print_statistics(&current_statistics, ARGS.output, print_header).await?;
             output_counter += 1;
 
             if let Some(until) = ARGS.until {
@@ -151,3 +83,98 @@ pub async fn app() -> Result<()> {
 
     Ok(())
 }
+
+/// Print the statistics in the format of the output.
+pub(crate) async fn print_statistics(
+    statistics: &HashMap<(String, String, String), Statistic>,
+    output: OutputOptions,
+    print_header: bool,
+) -> Result<()> {
+    match output {
+        OutputOptions::SarU => print_all_cpu(statistics, "sar-u", print_header)
+            .await
+            .with_context(|| "print_all_cpu sar-u")?,
+        OutputOptions::SarB => print_vmstat(statistics, "sar-B", print_header)
+            .await
+            .with_context(|| "print_vmstat sar-B")?,
+        OutputOptions::Sarb => print_diskstats(statistics, "sar-b", print_header)
+            .await
+            .with_context(|| "print_diskstats sar-b")?,
+        OutputOptions::SarUAll => print_all_cpu(statistics, "sar-u-ALL", print_header)
+            .await
+            .with_context(|| "print_all_cpu sar-u-ALL")?,
+        OutputOptions::CpuAll => print_all_cpu(statistics, "cpu-all", print_header)
+            .await
+            .with_context(|| "print_all_cpu cpu-all")?,
+        OutputOptions::Schedstat => print_per_cpu(statistics, "schedstat")
+            .await
+            .with_context(|| "print_per_cpu schedstat")?,
+        OutputOptions::MpstatPAll => print_per_cpu(statistics, "mpstat-P-ALL")
+            .await
+            .with_context(|| "print_per_cpu mpstat-P-ALL")?,
+        OutputOptions::PerCpuAll => print_per_cpu(statistics, "per-cpu-all")
+            .await
+            .with_context(|| "print_per_cpu per-cpu-all")?,
+        OutputOptions::SarD => print_diskstats(statistics, "sar-d", print_header)
+            .await
+            .with_context(|| "print_diskstats sar-d")?,
+        OutputOptions::Iostat => print_diskstats(statistics, "iostat", print_header)
+            .await
+            .with_context(|| "print_diskstats iostat")?,
+        OutputOptions::IostatX => print_diskstats(statistics, "iostat-x", print_header)
+            .await
+            .with_context(|| "print_diskstats iostat-x")?,
+        OutputOptions::Ioq => print_diskstats(statistics, "ioq", print_header)
+            .await
+            .with_context(|| "print_diskstats ioq")?,
+        OutputOptions::Ios => print_diskstats(statistics, "ios", print_header)
+            .await
+            .with_context(|| "print_diskstats ios")?,
+        OutputOptions::SarH => print_meminfo(statistics, "sar-H", print_header)
+            .await
+            .with_context(|| "print_meminfo sar-H")?,
+        OutputOptions::SarR => print_meminfo(statistics, "sar-r", print_header)
+            .await
+            .with_context(|| "print_meminfo sar-r")?,
+        OutputOptions::SarRAll => print_meminfo(statistics, "sar-r-ALL", print_header)
+            .await
+            .with_context(|| "print_meminfo sar-r-ALL")?,
+        OutputOptions::SarNDev => print_net_dev(statistics, "sar-n-DEV")
+            .await
+            .with_context(|| "print_net_dev sar-n-DEV")?,
+        OutputOptions::SarNEdev => print_net_dev(statistics, "sar-n-EDEV")
+            .await
+            .with_context(|| "print_net_dev sar-n-EDEV")?,
+        OutputOptions::SarQCpu => print_psi(statistics, "sar-q-CPU", print_header)
+            .await
+            .with_context(|| "print_psi sar-q-CPU")?,
+        OutputOptions::SarQLoad => print_loadavg(statistics, "sar-q-LOAD", print_header)
+            .await
+            .with_context(|| "print sar-q-LOAD")?,
+        OutputOptions::SarQIo => print_psi(statistics, "sar-q-IO", print_header)
+            .await
+            .with_context(|| "print_psi sar-q-IO")?,
+        OutputOptions::SarQMem => print_psi(statistics, "sar-q-MEM", print_header)
+            .await
+            .with_context(|| "print_psi sar-q-MEM")?,
+        OutputOptions::SarQ => print_loadavg(statistics, "sar-q-LOAD", print_header)
+            .await
+            .with_context(|| "print_psi sar-q-LOAD")?,
+        OutputOptions::SarS => print_meminfo(statistics, "sar-S", print_header)
+            .await
+            .with_context(|| "print_meminfo sar-S")?,
+        OutputOptions::SarW => print_vmstat(statistics, "sar-W", print_header)
+            .await
+            .with_context(|| "print_vmstat sar-W")?,
+        OutputOptions::Sarw => print_all_cpu(statistics, "sar-w", print_header)
+            .await
+            .with_context(|| "print_all_cpu sar-w")?,
+        OutputOptions::Vmstat => print_vmstat(statistics, "vmstat", print_header)
+            .await
+            .with_context(|| "print_vmstat vmstat")?,
+        OutputOptions::Free => print_vmstat(statistics, "free", print_header)
+            .await
+            .with_context(|| "print_vmstat free")?,
+    }
+    Ok(())
+}
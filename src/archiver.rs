@@ -1,21 +1,86 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Duration, DurationRound, Local};
-use log::debug;
-use std::env::current_dir;
-use std::fs::write;
-use std::path::Path;
+use clap::ValueEnum;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::fs::{create_dir_all, read, read_dir, remove_file, rename, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use tokio::time::{self, Duration as TokioDuration, MissedTickBehavior};
 
+use crate::metadata::ArchiveMetadata;
 use crate::processor::blockdevice::BlockDeviceInfo;
 use crate::processor::loadavg::LoadavgInfo;
 use crate::processor::meminfo::MemInfo;
 use crate::processor::net_dev::NetworkDeviceInfo;
 use crate::processor::pressure::PressureInfo;
-use crate::processor::stat::CpuStat;
+use crate::processor::schedstat::SchedStatInfo;
+use crate::processor::stat::{CpuStat, PerCpuStat};
 use crate::processor::vmstat::VmStatInfo;
 use crate::processor::xfs::XfsInfo;
+use crate::processor::Data;
 use crate::processor::HistoricalDataTransit;
-use crate::{ARGS, DATA};
+use crate::{columnar, ConvertOpts, ARGS, DATA};
+
+/// The format of the archives. The reader detects the format of an archive by its content, so
+/// archives of all formats can be read together.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    #[default]
+    Json,
+    Columnar,
+}
+
+impl ArchiveFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Json => "json",
+            ArchiveFormat::Columnar => "columnar",
+        }
+    }
+    fn encode(&self, transition: &HistoricalDataTransit) -> Result<Vec<u8>> {
+        match self {
+            ArchiveFormat::Json => Ok(serde_json::to_vec(transition)?),
+            ArchiveFormat::Columnar => columnar::encode(transition),
+        }
+    }
+}
+
+/// The compression of the archives. The reader detects the compression of an archive by its
+/// content, so archives of all compressions can be read together.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl ArchiveCompression {
+    fn extension(&self) -> &'static str {
+        match self {
+            ArchiveCompression::None => "",
+            ArchiveCompression::Gzip => ".gz",
+            ArchiveCompression::Zstd => ".zst",
+        }
+    }
+    fn compress(&self, contents: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(match self {
+            ArchiveCompression::None => contents,
+            ArchiveCompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&contents)?;
+                encoder.finish()?
+            }
+            ArchiveCompression::Zstd => zstd::encode_all(contents.as_slice(), 0)?,
+        })
+    }
+}
 
 pub async fn archiver() -> Result<()> {
     // regardless of the archiver_interval set, the archiver will tick once per minute.
@@ -39,6 +104,12 @@ pub async fn archiver() -> Result<()> {
         Local::now(),
         high_time
     );
+    // an archive that was being written when procstat stopped is left as a temporary file.
+    let archive_directory = Path::new(&ARGS.archive_dir);
+    if archive_directory.is_dir() {
+        remove_temporary_archives(archive_directory)
+            .with_context(|| "Error removing temporary archives.")?;
+    }
 
     loop {
         interval.tick().await;
@@ -64,7 +135,6 @@ pub async fn archiver() -> Result<()> {
     }
 }
 pub fn archive(high_time: DateTime<Local>, interval_completed: bool) -> Result<()> {
-    let mut transition = HistoricalDataTransit::default();
     // this function gets the "end" time of the data to archive,
     // so subtracting the interval will result in the begin time for the archive.
     //let low_time = high_time.duration_trunc(Duration::minutes(ARGS.archiver_interval))?
@@ -81,6 +151,47 @@ pub fn archive(high_time: DateTime<Local>, interval_completed: bool) -> Result<(
         low_time, high_time, ARGS.archiver_interval
     );
 
+    let transition = historical_data(low_time, high_time);
+
+    let archive_directory = Path::new(&ARGS.archive_dir);
+    create_dir_all(archive_directory).with_context(|| {
+        format!(
+            "Error creating archive directory {}.",
+            archive_directory.to_string_lossy()
+        )
+    })?;
+    let filename = archive_directory.join(format!(
+        "procstat_{}-{}-{}T{}-{}",
+        high_time.format("%Y"),
+        high_time.format("%m"),
+        high_time.format("%d"),
+        high_time.format("%H"),
+        high_time.format("%M"),
+    ));
+    let filename = write_archive(
+        &filename,
+        &transition,
+        ARGS.archive_format,
+        ARGS.archive_compression,
+    )?;
+
+    prune_archives(
+        archive_directory,
+        &filename,
+        ARGS.archive_max_age,
+        ARGS.archive_max_size,
+    )
+    .with_context(|| "Error removing archives for the retention.")?;
+
+    Ok(())
+}
+
+/// The historical data after low_time up to and including high_time, with the metadata.
+pub fn historical_data(
+    low_time: DateTime<Local>,
+    high_time: DateTime<Local>,
+) -> HistoricalDataTransit {
+    let mut transition = HistoricalDataTransit::default();
     transition.cpu = DATA
         .cpu
         .read()
@@ -89,6 +200,22 @@ pub fn archive(high_time: DateTime<Local>, interval_completed: bool) -> Result<(
         .filter(|cpustat| cpustat.timestamp > low_time && cpustat.timestamp <= high_time)
         .cloned()
         .collect::<Vec<CpuStat>>();
+    transition.per_cpu = DATA
+        .per_cpu
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|per_cpu| per_cpu.timestamp > low_time && per_cpu.timestamp <= high_time)
+        .cloned()
+        .collect::<Vec<PerCpuStat>>();
+    transition.schedstat = DATA
+        .schedstat
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|schedstat| schedstat.timestamp > low_time && schedstat.timestamp <= high_time)
+        .cloned()
+        .collect::<Vec<SchedStatInfo>>();
     transition.memory = DATA
         .memory
         .read()
@@ -149,78 +276,423 @@ pub fn archive(high_time: DateTime<Local>, interval_completed: bool) -> Result<(
         .filter(|xfs| xfs.timestamp > low_time && xfs.timestamp <= high_time)
         .cloned()
         .collect::<Vec<XfsInfo>>();
+    // the history of read archives has the metadata of the archives.
+    transition.metadata = DATA.metadata.read().unwrap().clone().or_else(|| {
+        Some(ArchiveMetadata::current(
+            transition.blockdevices.iter().map(|row| &row.device_name),
+            transition.networkdevices.iter().map(|row| &row.device_name),
+        ))
+    });
 
-    let current_directory = current_dir()?;
-    let filename = current_directory.join(format!(
-        "procstat_{}-{}-{}T{}-{}.json",
-        high_time.format("%Y"),
-        high_time.format("%m"),
-        high_time.format("%d"),
-        high_time.format("%H"),
-        high_time.format("%M")
+    transition
+}
+
+/// Write the historical data to an archive with the extension of the format and the compression
+/// added to the filename, and return the filename of the archive.
+pub fn write_archive(
+    filename: &Path,
+    transition: &HistoricalDataTransit,
+    format: ArchiveFormat,
+    compression: ArchiveCompression,
+) -> Result<PathBuf> {
+    let filename = PathBuf::from(format!(
+        "{}.{}{}",
+        filename.to_string_lossy(),
+        format.extension(),
+        compression.extension()
     ));
     debug!("filename: {:?}", &filename.to_str());
+    // the archive is written to a temporary file first, and renamed when it is completely
+    // written, so that a crash during the write never leaves a truncated archive.
     // the most likely place to fail is the write.
-    write(filename.clone(), serde_json::to_string(&transition)?).with_context(|| {
-        format!(
-            "Error writing {} to {}.",
-            filename.to_string_lossy(),
-            current_directory.to_string_lossy()
-        )
-    })?;
+    // the directory is synced after the rename, so that the rename survives a crash too.
+    let temporary_filename = PathBuf::from(format!("{}.tmp", filename.to_string_lossy()));
+    let contents = compression.compress(format.encode(transition)?)?;
+    let directory = match filename.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    File::create(&temporary_filename)
+        .and_then(|mut file| {
+            file.write_all(&contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| rename(&temporary_filename, &filename))
+        .and_then(|_| File::open(directory)?.sync_all())
+        .with_context(|| format!("Error writing {}.", filename.to_string_lossy()))?;
+    Ok(filename)
+}
 
+/// Remove the temporary files of archives that were not completely written, because procstat
+/// stopped during the write.
+fn remove_temporary_archives(directory: &Path) -> Result<()> {
+    for entry in read_dir(directory)?.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.strip_suffix(".tmp").is_some_and(is_archive_name) {
+            remove_file(entry.path())?;
+            info!(
+                "Removed temporary archive {}",
+                entry.path().to_string_lossy()
+            );
+        }
+    }
     Ok(())
 }
 
-pub async fn reader() {
-    for file in ARGS.read.clone().unwrap() {
-        if Path::new(&file).exists() {
-            let transition: HistoricalDataTransit =
-                serde_json::from_str(&std::fs::read_to_string(file.clone()).unwrap())
-                    .unwrap_or_else(|e| panic!("{}", e));
-            transition
-                .cpu
-                .iter()
-                .for_each(|row| DATA.cpu.write().unwrap().push_back(row.clone()));
-            transition
-                .memory
-                .iter()
-                .for_each(|row| DATA.memory.write().unwrap().push_back(row.clone()));
-            transition
-                .blockdevices
-                .iter()
-                .for_each(|row| DATA.blockdevices.write().unwrap().push_back(row.clone()));
-            transition
-                .networkdevices
-                .iter()
-                .for_each(|row| DATA.networkdevices.write().unwrap().push_back(row.clone()));
-            transition
-                .loadavg
-                .iter()
-                .for_each(|row| DATA.loadavg.write().unwrap().push_back(row.clone()));
-            transition
-                .pressure
-                .iter()
-                .for_each(|row| DATA.pressure.write().unwrap().push_back(row.clone()));
-            transition
-                .vmstat
+/// Whether the name is the name of an archive, of any format and compression.
+fn is_archive_name(name: &str) -> bool {
+    name.starts_with("procstat_")
+        && [ArchiveFormat::Json, ArchiveFormat::Columnar]
+            .iter()
+            .flat_map(|format| {
+                [
+                    ArchiveCompression::None,
+                    ArchiveCompression::Gzip,
+                    ArchiveCompression::Zstd,
+                ]
                 .iter()
-                .for_each(|row| DATA.vmstat.write().unwrap().push_back(row.clone()));
-            transition
-                .xfs
-                .iter()
-                .for_each(|row| DATA.xfs.write().unwrap().push_back(row.clone()));
-            println!("✔ {}", &file);
-        } else {
-            println!("✘ {}", file);
+                .map(move |compression| {
+                    format!(".{}{}", format.extension(), compression.extension())
+                })
+            })
+            .any(|extension| name.ends_with(&extension))
+}
+
+/// The archives in the directory with their modification time and size, oldest first.
+fn archives_in_directory(directory: &Path) -> Result<Vec<(PathBuf, SystemTime, u64)>> {
+    let mut archives = read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_archive_name(&entry.file_name().to_string_lossy()))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((entry.path(), metadata.modified().ok()?, metadata.len()))
+        })
+        .collect::<Vec<(PathBuf, SystemTime, u64)>>();
+    archives.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+    Ok(archives)
+}
+
+/// Remove the archives that are older than the maximum age, and the oldest archives while the
+/// archives together are larger than the maximum size. The archive that is just written is
+/// never removed.
+fn prune_archives(
+    directory: &Path,
+    current_archive: &Path,
+    max_age_hours: Option<u64>,
+    max_size_megabytes: Option<u64>,
+) -> Result<()> {
+    if max_age_hours.is_none() && max_size_megabytes.is_none() {
+        return Ok(());
+    }
+    let oldest_time =
+        max_age_hours.map(|hours| SystemTime::now() - std::time::Duration::from_secs(hours * 3600));
+    let mut total_size = archives_in_directory(directory)?
+        .iter()
+        .map(|(_, _, size)| size)
+        .sum::<u64>();
+    for (archive, modified, size) in archives_in_directory(directory)? {
+        if archive == current_archive {
+            continue;
+        }
+        let too_old = oldest_time.is_some_and(|oldest_time| modified < oldest_time);
+        let too_large =
+            max_size_megabytes.is_some_and(|megabytes| total_size > megabytes * 1024 * 1024);
+        if too_old || too_large {
+            remove_file(&archive)?;
+            info!("Removed archive {}", archive.to_string_lossy());
+            total_size -= size;
+        }
+    }
+    Ok(())
+}
+
+/// Read an archive of any format, which can be gzip or zstd compressed.
+pub fn read_archive(file: &str) -> Result<HistoricalDataTransit> {
+    let contents = read(file)?;
+    let mut decompressed = Vec::new();
+    let contents = if contents.starts_with(&[0x1f, 0x8b]) {
+        GzDecoder::new(contents.as_slice()).read_to_end(&mut decompressed)?;
+        decompressed
+    } else if contents.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        zstd::Decoder::new(contents.as_slice())?.read_to_end(&mut decompressed)?;
+        decompressed
+    } else {
+        contents
+    };
+    if contents.starts_with(columnar::COLUMNAR_MAGIC) {
+        columnar::decode(&contents)
+    } else {
+        Ok(serde_json::from_slice(&contents)?)
+    }
+}
+
+/// Convert archives to the format and compression of the options. The converted archive gets the
+/// name of the archive with the extension of the format and compression.
+pub fn convert(convert_opts: &ConvertOpts) -> Result<()> {
+    for file in &convert_opts.archives {
+        let transition = read_archive(file).with_context(|| format!("Error reading {}.", file))?;
+        let path = Path::new(file);
+        let mut name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        for extension in [".gz", ".zst", ".json", ".columnar"] {
+            if let Some(stripped) = name.strip_suffix(extension) {
+                name = stripped.to_string();
+            }
+        }
+        let directory = match &convert_opts.directory {
+            Some(directory) => PathBuf::from(directory),
+            None => path.parent().map(Path::to_path_buf).unwrap_or_default(),
         };
+        create_dir_all(&directory)?;
+        let target = directory.join(format!(
+            "{}.{}{}",
+            name,
+            convert_opts.format.extension(),
+            convert_opts.compression.extension()
+        ));
+        if target.exists() && target.canonicalize()? == path.canonicalize()? {
+            bail!(
+                "{} already is in the {:?} format with {:?} compression.",
+                file,
+                convert_opts.format,
+                convert_opts.compression
+            );
+        }
+        let target = write_archive(
+            &directory.join(name),
+            &transition,
+            convert_opts.format,
+            convert_opts.compression,
+        )?;
+        println!("✔ {} -> {}", file, target.to_string_lossy());
+    }
+    Ok(())
+}
+
+pub async fn reader() {
+    load_archives_by_host(&ARGS.read.clone().unwrap());
+    let hostnames = DATA.hostnames();
+    if hostnames.len() > 1 {
+        println!(
+            "The archives are of {} hosts: {}.",
+            hostnames.len(),
+            hostnames.join(", ")
+        );
     }
-    println!("All files loaded.");
 
     // this sets up an endless loop that ticks with the set interval.
-    let mut interval = time::interval(std::time::Duration::from_secs(ARGS.interval));
+    let mut interval = time::interval(std::time::Duration::from_secs_f64(ARGS.interval));
     interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
     loop {
         interval.tick().await;
     }
 }
+
+/// Load archives into the historical data. The statistics of different hosts would be mixed in a
+/// single history, so the archives must be of a single host, unless mixed hosts are allowed.
+pub fn load_archives(files: &[String], allow_mixed_hosts: bool) -> Result<()> {
+    let mut hostnames = Vec::new();
+    for file in files {
+        if Path::new(file).exists() {
+            let transition = read_archive(file).with_context(|| file.clone())?;
+            if let Some(metadata) = &transition.metadata {
+                check_hostname(&mut hostnames, &metadata.hostname, allow_mixed_hosts)?;
+            }
+            add_to_history(transition, &DATA);
+            println!("✔ {}", file);
+        } else {
+            println!("✘ {}", file);
+        }
+    }
+    println!("All files loaded.");
+    Ok(())
+}
+
+/// Add the hostname of an archive to the hostnames of the archives that are read, and fail when
+/// it is of another host, unless mixed hosts are allowed, which is warned about once.
+fn check_hostname(
+    hostnames: &mut Vec<String>,
+    hostname: &str,
+    allow_mixed_hosts: bool,
+) -> Result<()> {
+    if hostnames.iter().any(|known| known == hostname) {
+        return Ok(());
+    }
+    hostnames.push(hostname.to_string());
+    if hostnames.len() == 2 {
+        if !allow_mixed_hosts {
+            bail!(
+                "The archives are of different hosts: {}. Use --allow-mixed-hosts to read them together.",
+                hostnames.join(", ")
+            );
+        }
+        eprintln!(
+            "Warning: the archives are of different hosts: {}; the metadata is of the last archive.",
+            hostnames.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Load archives into the history of the host of their metadata, so that the archives of
+/// several hosts are kept side by side instead of being mixed.
+pub fn load_archives_by_host(files: &[String]) {
+    for file in files {
+        if Path::new(file).exists() {
+            let transition = read_archive(file).unwrap_or_else(|e| panic!("{}: {}", file, e));
+            let hostname = transition
+                .metadata
+                .as_ref()
+                .map(|metadata| metadata.hostname.clone())
+                .unwrap_or_default();
+            add_to_history(transition, DATA.host(&hostname));
+            println!("✔ {}", file);
+        } else {
+            println!("✘ {}", file);
+        }
+    }
+    for hostname in DATA.hostnames() {
+        DATA.host(&hostname).roll_up();
+    }
+    println!("All files loaded.");
+}
+
+pub(crate) fn add_to_history(transition: HistoricalDataTransit, data: &Data) {
+    transition
+        .cpu
+        .iter()
+        .for_each(|row| data.cpu.write().unwrap().push_back(row.clone()));
+    transition
+        .per_cpu
+        .iter()
+        .for_each(|row| data.per_cpu.write().unwrap().push_back(row.clone()));
+    transition
+        .schedstat
+        .iter()
+        .for_each(|row| data.schedstat.write().unwrap().push_back(row.clone()));
+    transition
+        .memory
+        .iter()
+        .for_each(|row| data.memory.write().unwrap().push_back(row.clone()));
+    transition
+        .blockdevices
+        .iter()
+        .for_each(|row| data.blockdevices.write().unwrap().push_back(row.clone()));
+    transition
+        .networkdevices
+        .iter()
+        .for_each(|row| data.networkdevices.write().unwrap().push_back(row.clone()));
+    transition
+        .loadavg
+        .iter()
+        .for_each(|row| data.loadavg.write().unwrap().push_back(row.clone()));
+    transition
+        .pressure
+        .iter()
+        .for_each(|row| data.pressure.write().unwrap().push_back(row.clone()));
+    transition
+        .vmstat
+        .iter()
+        .for_each(|row| data.vmstat.write().unwrap().push_back(row.clone()));
+    transition
+        .xfs
+        .iter()
+        .for_each(|row| data.xfs.write().unwrap().push_back(row.clone()));
+    if transition.metadata.is_some() {
+        *data.metadata.write().unwrap() = transition.metadata;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    #[test]
+    fn archives_are_read_and_pruned_by_size() {
+        let directory = std::env::temp_dir().join(format!("procstat_test_{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+        let transition = HistoricalDataTransit {
+            cpu: vec![CpuStat {
+                timestamp: Local::now(),
+                user: 1.5,
+                ..Default::default()
+            }],
+            per_cpu: ["cpu0", "cpu1"]
+                .map(|cpu_name| PerCpuStat {
+                    timestamp: Local::now(),
+                    cpu_name: cpu_name.to_string(),
+                    system: 0.25,
+                    ..Default::default()
+                })
+                .into(),
+            metadata: Some(ArchiveMetadata {
+                hostname: "db01".to_string(),
+                interval: 0.5,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut files = Vec::new();
+        for format in [ArchiveFormat::Json, ArchiveFormat::Columnar] {
+            for compression in [
+                ArchiveCompression::None,
+                ArchiveCompression::Gzip,
+                ArchiveCompression::Zstd,
+            ] {
+                let file = write_archive(
+                    &directory.join(format!("procstat_2024-10-20T12-{}", files.len())),
+                    &transition,
+                    format,
+                    compression,
+                )
+                .unwrap();
+                assert!(is_archive_name(
+                    &file.file_name().unwrap().to_string_lossy()
+                ));
+                let read = read_archive(file.to_str().unwrap()).unwrap();
+                assert_eq!(
+                    read.metadata
+                        .as_ref()
+                        .map(|metadata| metadata.hostname.as_str()),
+                    Some("db01")
+                );
+                assert_eq!(
+                    serde_json::to_value(read).unwrap(),
+                    serde_json::to_value(&transition).unwrap()
+                );
+                files.push(file);
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+        // a file of 1 megabyte makes the archives too large, so all archives but the current
+        // one are removed.
+        let current = directory.join("procstat_2024-10-20T12-30.json");
+        write(&current, vec![b' '; 1024 * 1024]).unwrap();
+        prune_archives(&directory, &current, None, Some(1)).unwrap();
+        assert!(files.iter().all(|file| !file.exists()));
+        assert!(current.exists());
+        // the temporary file of an archive that was not completely written is removed.
+        let temporary = directory.join("procstat_2024-10-20T12-40.json.zst.tmp");
+        write(&temporary, b"{").unwrap();
+        remove_temporary_archives(&directory).unwrap();
+        assert!(!temporary.exists());
+        assert!(current.exists());
+        remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn archives_of_different_hosts_are_refused_unless_allowed() {
+        let mut hostnames = Vec::new();
+        check_hostname(&mut hostnames, "db01", false).unwrap();
+        check_hostname(&mut hostnames, "db01", false).unwrap();
+        assert!(check_hostname(&mut hostnames, "db02", false).is_err());
+
+        let mut hostnames = Vec::new();
+        check_hostname(&mut hostnames, "db01", true).unwrap();
+        check_hostname(&mut hostnames, "db02", true).unwrap();
+        assert_eq!(hostnames, vec!["db01", "db02"]);
+    }
+}
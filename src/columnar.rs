@@ -0,0 +1,351 @@
+//! The columnar archive format.
+//!
+//! A json archive repeats every field name for every row. The columnar format stores every
+//! history (cpu, memory, blockdevices, ...) as a table, with the values of every field in a
+//! column, with a header with the name and the kind of the columns. Numbers are stored as is,
+//! timestamps delta encoded and strings as a dictionary, which compresses well.
+//!
+//! The rows are rebuilt by field name, so archives written before a field was added to a row
+//! can be read: the field gets its default value.
+//!
+//! The values of the rows are numbers, and json has no number that is not finite: such a value
+//! is null in json, and is stored as NaN.
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::processor::HistoricalDataTransit;
+
+/// The bytes that a columnar archive starts with.
+pub const COLUMNAR_MAGIC: &[u8] = b"PROCSTAT-COLUMNAR";
+/// The version of the columnar format; an archive of a newer version is not read.
+pub const COLUMNAR_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ColumnarArchive {
+    version: u32,
+    tables: Vec<Table>,
+    /// the values that are not a history, such as the metadata, as json.
+    #[serde(default)]
+    values: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Table {
+    name: String,
+    rows: usize,
+    columns: Vec<Column>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Column {
+    name: String,
+    values: ColumnValues,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum ColumnValues {
+    /// nanoseconds since the epoch, as the difference with the previous row.
+    Timestamp(Vec<i64>),
+    Number(Vec<f64>),
+    /// the distinct strings, and the index in the dictionary for every row.
+    Text {
+        dictionary: Vec<String>,
+        indexes: Vec<u32>,
+    },
+    /// any other value, such as a list of device names.
+    Json(Vec<Value>),
+}
+
+/// A value of the decoded archive. The archive is decoded via messagepack instead of json,
+/// because a number that is not finite can't be a json value.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+enum DecodedValue {
+    Number(f64),
+    Rows(Vec<BTreeMap<String, DecodedValue>>),
+    Json(Value),
+}
+
+fn timestamp_nanoseconds(value: &Value) -> Option<i64> {
+    DateTime::parse_from_rfc3339(value.as_str()?)
+        .ok()?
+        .timestamp_nanos_opt()
+}
+
+/// The column of the values of a field. The kind of the column is taken from the values: if
+/// not all values are of the same kind, they are stored as json.
+fn column(name: &str, values: Vec<Value>) -> Column {
+    let values = if name == "timestamp" && values.iter().all(|v| timestamp_nanoseconds(v).is_some())
+    {
+        let mut previous = 0_i64;
+        ColumnValues::Timestamp(
+            values
+                .iter()
+                .map(|value| {
+                    let nanoseconds = timestamp_nanoseconds(value).unwrap();
+                    let delta = nanoseconds - previous;
+                    previous = nanoseconds;
+                    delta
+                })
+                .collect(),
+        )
+    } else if values
+        .iter()
+        .all(|value| value.is_number() || value.is_null())
+    {
+        ColumnValues::Number(
+            values
+                .iter()
+                .map(|value| value.as_f64().unwrap_or(f64::NAN))
+                .collect(),
+        )
+    } else if values.iter().all(|value| value.is_string()) {
+        let mut dictionary: Vec<String> = Vec::new();
+        let mut positions: HashMap<String, u32> = HashMap::new();
+        let indexes = values
+            .iter()
+            .map(|value| {
+                let text = value.as_str().unwrap_or_default();
+                *positions.entry(text.to_string()).or_insert_with(|| {
+                    dictionary.push(text.to_string());
+                    dictionary.len() as u32 - 1
+                })
+            })
+            .collect();
+        ColumnValues::Text {
+            dictionary,
+            indexes,
+        }
+    } else {
+        ColumnValues::Json(values)
+    };
+    Column {
+        name: name.to_string(),
+        values,
+    }
+}
+
+impl Column {
+    fn values(self) -> Result<Vec<DecodedValue>> {
+        Ok(match self.values {
+            ColumnValues::Timestamp(deltas) => {
+                let mut nanoseconds = 0_i64;
+                deltas
+                    .into_iter()
+                    .map(|delta| {
+                        nanoseconds += delta;
+                        DecodedValue::Json(Value::String(
+                            Local.timestamp_nanos(nanoseconds).to_rfc3339(),
+                        ))
+                    })
+                    .collect()
+            }
+            ColumnValues::Number(numbers) => {
+                numbers.into_iter().map(DecodedValue::Number).collect()
+            }
+            ColumnValues::Text {
+                dictionary,
+                indexes,
+            } => indexes
+                .into_iter()
+                .map(|index| {
+                    dictionary
+                        .get(index as usize)
+                        .map(|text| DecodedValue::Json(Value::String(text.to_string())))
+                        .with_context(|| format!("invalid index {} in column {}", index, self.name))
+                })
+                .collect::<Result<Vec<DecodedValue>>>()?,
+            // a null in a row is a number that is not finite.
+            ColumnValues::Json(values) => values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Null => DecodedValue::Number(f64::NAN),
+                    value => DecodedValue::Json(value),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Encode the historical data as a columnar archive.
+pub fn encode(transition: &HistoricalDataTransit) -> Result<Vec<u8>> {
+    let Value::Object(histories) = serde_json::to_value(transition)? else {
+        bail!("historical data is not an object");
+    };
+    let mut tables = Vec::new();
+    let mut values = Map::new();
+    for (name, rows) in histories {
+        let rows = match rows {
+            Value::Array(rows) => rows,
+            Value::Null => continue,
+            value => {
+                values.insert(name, value);
+                continue;
+            }
+        };
+        // the field names in the order of the first row, followed by the fields that only
+        // later rows have.
+        let mut field_names: Vec<String> = Vec::new();
+        for row in &rows {
+            if let Value::Object(fields) = row {
+                for field_name in fields.keys() {
+                    if !field_names.contains(field_name) {
+                        field_names.push(field_name.to_string());
+                    }
+                }
+            }
+        }
+        let columns = field_names
+            .iter()
+            .map(|field_name| {
+                column(
+                    field_name,
+                    rows.iter()
+                        .map(|row| row.get(field_name).cloned().unwrap_or(Value::Null))
+                        .collect(),
+                )
+            })
+            .collect();
+        tables.push(Table {
+            name,
+            rows: rows.len(),
+            columns,
+        });
+    }
+    let mut archive = COLUMNAR_MAGIC.to_vec();
+    archive.append(&mut rmp_serde::to_vec_named(&ColumnarArchive {
+        version: COLUMNAR_VERSION,
+        tables,
+        values,
+    })?);
+    Ok(archive)
+}
+
+/// Decode a columnar archive into the historical data.
+pub fn decode(archive: &[u8]) -> Result<HistoricalDataTransit> {
+    let Some(archive) = archive.strip_prefix(COLUMNAR_MAGIC) else {
+        bail!("not a columnar archive");
+    };
+    let archive: ColumnarArchive = rmp_serde::from_slice(archive)?;
+    if archive.version > COLUMNAR_VERSION {
+        bail!(
+            "columnar archive version {} is newer than the supported version {}",
+            archive.version,
+            COLUMNAR_VERSION
+        );
+    }
+    let mut histories = archive
+        .values
+        .into_iter()
+        .map(|(name, value)| (name, DecodedValue::Json(value)))
+        .collect::<BTreeMap<String, DecodedValue>>();
+    for table in archive.tables {
+        let mut rows = vec![BTreeMap::new(); table.rows];
+        for column in table.columns {
+            let name = column.name.clone();
+            let values = column.values()?;
+            if values.len() != table.rows {
+                bail!(
+                    "column {} of {} has {} values instead of {}",
+                    name,
+                    table.name,
+                    values.len(),
+                    table.rows
+                );
+            }
+            for (row, value) in rows.iter_mut().zip(values) {
+                row.insert(name.clone(), value);
+            }
+        }
+        histories.insert(table.name, DecodedValue::Rows(rows));
+    }
+    Ok(rmp_serde::from_slice(&rmp_serde::to_vec_named(
+        &histories,
+    )?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::ArchiveMetadata;
+    use crate::processor::blockdevice::BlockDeviceInfo;
+    use crate::processor::stat::CpuStat;
+
+    #[test]
+    fn columnar_archive_round_trip() {
+        let timestamp = Local::now();
+        let transition = HistoricalDataTransit {
+            cpu: vec![
+                CpuStat {
+                    timestamp,
+                    user: 1.5,
+                    ..Default::default()
+                },
+                CpuStat {
+                    timestamp: timestamp + chrono::Duration::seconds(1),
+                    user: 2.5,
+                    ..Default::default()
+                },
+            ],
+            blockdevices: vec![BlockDeviceInfo {
+                timestamp,
+                device_name: "vg0-data".to_string(),
+                slaves: vec!["sda".to_string()],
+                ..Default::default()
+            }],
+            metadata: Some(ArchiveMetadata {
+                hostname: "db01".to_string(),
+                cpu_count: 2,
+                blockdevices: vec!["sda".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let decoded = decode(&encode(&transition).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&transition).unwrap()
+        );
+    }
+
+    #[test]
+    fn a_missing_column_gets_the_default_and_a_null_is_not_a_number() {
+        // the cpu rows of an archive with only the timestamp and the user time.
+        let timestamp = Local::now();
+        let transition = HistoricalDataTransit {
+            cpu: vec![
+                CpuStat {
+                    timestamp,
+                    user: 1.5,
+                    system: 1_f64,
+                    ..Default::default()
+                },
+                CpuStat {
+                    timestamp: timestamp + chrono::Duration::seconds(1),
+                    user: f64::NAN,
+                    system: 1_f64,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let encoded = encode(&transition).unwrap();
+        let mut columnar: ColumnarArchive =
+            rmp_serde::from_slice(&encoded[COLUMNAR_MAGIC.len()..]).unwrap();
+        for table in columnar.tables.iter_mut() {
+            table
+                .columns
+                .retain(|column| column.name == "timestamp" || column.name == "user");
+        }
+        let mut archive = COLUMNAR_MAGIC.to_vec();
+        archive.append(&mut rmp_serde::to_vec_named(&columnar).unwrap());
+        let decoded = decode(&archive).unwrap();
+        assert_eq!(decoded.cpu.len(), 2);
+        assert_eq!(decoded.cpu[0].user, 1.5);
+        assert!(decoded.cpu[1].user.is_nan());
+        assert_eq!(decoded.cpu[0].system, 0_f64);
+    }
+}
@@ -0,0 +1,405 @@
+//! The configuration file: the settings of the options in toml, such as:
+//!
+//! ```toml
+//! interval = 0.5
+//! history = 3600
+//!
+//! [webserver]
+//! enabled = true
+//! bind = "0.0.0.0"
+//! port = 1111
+//!
+//! [archiver]
+//! enabled = true
+//! directory = "/var/lib/procstat"
+//! interval = 10
+//!
+//! [collectors.disk]
+//! filter = "^dm-"
+//! interval = 10
+//!
+//! [collectors.network]
+//! filter = "^(lo|veth)"
+//!
+//! [collectors.xfs]
+//! enabled = false
+//!
+//! [export]
+//! urls = ["otlp-http://otel-collector:4318"]
+//! ```
+//!
+//! The options that are set on the command line override the settings of the file.
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use std::fs::read_to_string;
+
+use crate::archiver::{ArchiveCompression, ArchiveFormat};
+use crate::processor::Collector;
+use crate::Opts;
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// seconds.
+    pub interval: Option<f64>,
+    /// seconds.
+    pub history: Option<usize>,
+    pub daemon: Option<bool>,
+    pub webserver: WebserverConfig,
+    pub archiver: ArchiverConfig,
+    pub collectors: CollectorsConfig,
+    pub alerting: AlertingConfig,
+    pub export: ExportConfig,
+    pub federation: FederationConfig,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebserverConfig {
+    pub enabled: Option<bool>,
+    pub bind: Option<String>,
+    pub port: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiverConfig {
+    pub enabled: Option<bool>,
+    pub directory: Option<String>,
+    /// minutes.
+    pub interval: Option<i64>,
+    pub format: Option<ArchiveFormat>,
+    pub compression: Option<ArchiveCompression>,
+    /// hours.
+    pub max_age: Option<u64>,
+    /// megabytes.
+    pub max_size: Option<u64>,
+}
+
+/// The settings of every collector. There is no cgroup collector yet, so its depth setting is
+/// deferred until there is one.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectorsConfig {
+    pub cpu: CollectorConfig,
+    pub schedstat: CollectorConfig,
+    pub memory: CollectorConfig,
+    pub disk: DiskConfig,
+    pub network: NetworkConfig,
+    pub loadavg: CollectorConfig,
+    pub pressure: CollectorConfig,
+    pub vmstat: CollectorConfig,
+    pub xfs: CollectorConfig,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectorConfig {
+    pub enabled: Option<bool>,
+    /// seconds.
+    pub interval: Option<f64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiskConfig {
+    pub enabled: Option<bool>,
+    /// seconds.
+    pub interval: Option<f64>,
+    /// regex of the kernel names or dm names of the devices to skip.
+    pub filter: Option<String>,
+    pub partitions: Option<bool>,
+    pub layer_totals: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub enabled: Option<bool>,
+    /// seconds.
+    pub interval: Option<f64>,
+    /// regex of the names of the interfaces to skip.
+    pub filter: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertingConfig {
+    pub rules: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    pub urls: Option<Vec<String>>,
+    pub every: Option<u64>,
+    /// megabytes.
+    pub queue: Option<usize>,
+    pub prefix: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FederationConfig {
+    pub urls: Option<Vec<String>>,
+}
+
+impl CollectorsConfig {
+    /// The enabled and interval settings of every collector.
+    fn settings(&self) -> [(Collector, Option<bool>, Option<f64>); 9] {
+        [
+            (Collector::Cpu, self.cpu.enabled, self.cpu.interval),
+            (
+                Collector::Schedstat,
+                self.schedstat.enabled,
+                self.schedstat.interval,
+            ),
+            (Collector::Memory, self.memory.enabled, self.memory.interval),
+            (Collector::Disk, self.disk.enabled, self.disk.interval),
+            (
+                Collector::Network,
+                self.network.enabled,
+                self.network.interval,
+            ),
+            (
+                Collector::Loadavg,
+                self.loadavg.enabled,
+                self.loadavg.interval,
+            ),
+            (
+                Collector::Pressure,
+                self.pressure.enabled,
+                self.pressure.interval,
+            ),
+            (Collector::Vmstat, self.vmstat.enabled, self.vmstat.interval),
+            (Collector::Xfs, self.xfs.enabled, self.xfs.interval),
+        ]
+    }
+}
+
+impl Config {
+    pub fn from_file(filename: &str) -> Result<Config> {
+        let config = read_to_string(filename)
+            .with_context(|| format!("Unable to read config file {}", filename))?;
+        Config::parse(&config).with_context(|| format!("Config file {}", filename))
+    }
+    pub fn parse(config: &str) -> Result<Config> {
+        Ok(toml::from_str(config)?)
+    }
+    /// Set the options to the settings of the file, unless the option is set on the command
+    /// line.
+    pub fn apply(self, opts: &mut Opts, matches: &ArgMatches) {
+        let from_command_line =
+            |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        let collectors = self.collectors.settings();
+        macro_rules! set {
+            ($($setting:expr => $option:ident),* $(,)?) => {
+                $(
+                if let Some(value) = $setting {
+                    if !from_command_line(stringify!($option)) {
+                        opts.$option = value.into();
+                    }
+                }
+                )*
+            };
+        }
+        set!(
+            self.interval => interval,
+            self.history => history,
+            self.daemon => deamon,
+            self.webserver.enabled => webserver,
+            self.webserver.bind => webserver_bind,
+            self.webserver.port => webserver_port,
+            self.archiver.enabled => archiver,
+            self.archiver.directory => archive_dir,
+            self.archiver.interval => archiver_interval,
+            self.archiver.format => archive_format,
+            self.archiver.compression => archive_compression,
+            self.archiver.max_age => archive_max_age,
+            self.archiver.max_size => archive_max_size,
+            self.collectors.disk.filter => disk_filter,
+            self.collectors.disk.partitions => partitions,
+            self.collectors.disk.layer_totals => layer_totals,
+            self.collectors.network.filter => network_filter,
+            self.alerting.rules => rules,
+            self.export.urls => export,
+            self.export.every => export_every,
+            self.export.queue => export_queue,
+            self.export.prefix => export_prefix,
+            self.export.token => export_token,
+            self.federation.urls => federate,
+        );
+        if !from_command_line("collectors") {
+            for (collector, enabled, _) in collectors {
+                match enabled {
+                    Some(true) if !opts.collectors.contains(&collector) => {
+                        opts.collectors.push(collector)
+                    }
+                    Some(false) => opts.collectors.retain(|c| *c != collector),
+                    _ => {}
+                }
+            }
+        }
+        if !from_command_line("collector_interval") {
+            for (collector, _, interval) in collectors {
+                if let Some(interval) = interval {
+                    opts.collector_interval.retain(|(c, _)| *c != collector);
+                    opts.collector_interval.push((collector, interval));
+                }
+            }
+        }
+    }
+    /// The effective settings of the options, in the format of the file. The export token is
+    /// masked, so the settings can be shown.
+    pub fn from_opts(opts: &Opts) -> Config {
+        let interval = |collector: Collector| {
+            opts.collector_interval
+                .iter()
+                .rev()
+                .find(|(c, _)| *c == collector)
+                .map(|(_, interval)| *interval)
+        };
+        let collector = |collector: Collector| CollectorConfig {
+            enabled: Some(opts.collectors.contains(&collector)),
+            interval: interval(collector),
+        };
+        Config {
+            interval: Some(opts.interval),
+            history: Some(opts.history),
+            daemon: Some(opts.deamon),
+            webserver: WebserverConfig {
+                enabled: Some(opts.webserver),
+                bind: Some(opts.webserver_bind.clone()),
+                port: Some(opts.webserver_port),
+            },
+            archiver: ArchiverConfig {
+                enabled: Some(opts.archiver),
+                directory: Some(opts.archive_dir.clone()),
+                interval: Some(opts.archiver_interval),
+                format: Some(opts.archive_format),
+                compression: Some(opts.archive_compression),
+                max_age: opts.archive_max_age,
+                max_size: opts.archive_max_size,
+            },
+            collectors: CollectorsConfig {
+                cpu: collector(Collector::Cpu),
+                schedstat: collector(Collector::Schedstat),
+                memory: collector(Collector::Memory),
+                disk: DiskConfig {
+                    enabled: Some(opts.collectors.contains(&Collector::Disk)),
+                    interval: interval(Collector::Disk),
+                    filter: Some(opts.disk_filter.clone()),
+                    partitions: Some(opts.partitions),
+                    layer_totals: Some(opts.layer_totals),
+                },
+                network: NetworkConfig {
+                    enabled: Some(opts.collectors.contains(&Collector::Network)),
+                    interval: interval(Collector::Network),
+                    filter: Some(opts.network_filter.clone()),
+                },
+                loadavg: collector(Collector::Loadavg),
+                pressure: collector(Collector::Pressure),
+                vmstat: collector(Collector::Vmstat),
+                xfs: collector(Collector::Xfs),
+            },
+            alerting: AlertingConfig {
+                rules: opts.rules.clone(),
+            },
+            export: ExportConfig {
+                urls: opts.export.clone(),
+                every: Some(opts.export_every),
+                queue: Some(opts.export_queue),
+                prefix: Some(opts.export_prefix.clone()),
+                token: opts.export_token.as_ref().map(|_| "***".to_string()),
+            },
+            federation: FederationConfig {
+                urls: opts.federate.clone(),
+            },
+        }
+    }
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    #[test]
+    fn command_line_overrides_the_config_file() {
+        let config = Config::parse(
+            r#"
+            interval = 5
+            [webserver]
+            enabled = true
+            port = 2222
+            [archiver]
+            compression = "zstd"
+            [collectors.network]
+            filter = "^(lo|veth)"
+            [collectors.xfs]
+            enabled = false
+            [export]
+            urls = ["statsd://localhost"]
+            token = "secret"
+            "#,
+        )
+        .unwrap();
+        let matches = Opts::command().get_matches_from(["procstat", "--webserver-port", "3333"]);
+        let mut opts = Opts::from_arg_matches(&matches).unwrap();
+        config.apply(&mut opts, &matches);
+        assert_eq!(opts.interval, 5_f64);
+        assert!(opts.webserver);
+        assert_eq!(opts.webserver_port, 3333);
+        assert_eq!(opts.archive_compression, ArchiveCompression::Zstd);
+        assert_eq!(opts.network_filter, "^(lo|veth)");
+        assert!(!opts.collectors.contains(&Collector::Xfs));
+        assert!(opts.collectors.contains(&Collector::Cpu));
+        assert_eq!(opts.export, Some(vec!["statsd://localhost".to_string()]));
+        assert_eq!(opts.export_token.as_deref(), Some("secret"));
+
+        // the effective settings can be read as a config file again.
+        let effective = Config::from_opts(&opts);
+        assert_eq!(
+            Config::parse(&effective.to_toml().unwrap()).unwrap(),
+            effective
+        );
+        assert_eq!(effective.export.token.as_deref(), Some("***"));
+        assert!(Config::parse("intervall = 5").is_err());
+    }
+
+    #[test]
+    fn command_line_collectors_and_intervals_override_the_config_file() {
+        let config = || {
+            Config::parse(
+                r#"
+            [collectors.xfs]
+            enabled = true
+            interval = 60
+            [collectors.memory]
+            enabled = false
+            "#,
+            )
+            .unwrap()
+        };
+        let matches = Opts::command().get_matches_from(["procstat", "--collectors", "cpu,mem"]);
+        let mut opts = Opts::from_arg_matches(&matches).unwrap();
+        config().apply(&mut opts, &matches);
+        assert_eq!(opts.collectors, vec![Collector::Cpu, Collector::Memory]);
+        assert_eq!(opts.collector_interval, vec![(Collector::Xfs, 60_f64)]);
+
+        let matches =
+            Opts::command().get_matches_from(["procstat", "--collector-interval", "disk=0.5"]);
+        let mut opts = Opts::from_arg_matches(&matches).unwrap();
+        config().apply(&mut opts, &matches);
+        assert_eq!(opts.collector_interval, vec![(Collector::Disk, 0.5)]);
+        assert!(Opts::command()
+            .try_get_matches_from(["procstat", "--collector-interval", "disk=0.001"])
+            .is_err());
+    }
+}
@@ -0,0 +1,642 @@
+//! Exporters push the statistics to a time series database after every fetch, or after every
+//! number of fetches. The destination is set by the scheme of the url:
+//!
+//! ```text
+//! influx-http://host[:port]/path   InfluxDB line protocol posted over http (default port 8086)
+//! influx-udp://host[:port]         InfluxDB line protocol over udp (default port 8089)
+//! graphite://host[:port]           Graphite plaintext over tcp (default port 2003)
+//! statsd://host[:port]             StatsD gauges over udp (default port 8125)
+//! otlp-http://host[:port][/path]   OpenTelemetry metrics over http as json (default port 4318,
+//!                                  path /v1/metrics)
+//! ```
+//!
+//! The statistics are exported with their category, subcategory and name: as tags for the line
+//! protocol, and as the path of the metric for Graphite and StatsD. For OTLP, see [`crate::otlp`].
+//! The samples that can't be sent are kept in a bounded queue and sent with the next flush, so an
+//! outage of the destination loses no statistics, unless the queue is full, in which case the
+//! oldest are dropped.
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+
+use crate::http::{http_post, HttpStatusError};
+use crate::metadata::ArchiveMetadata;
+use crate::otlp::resource_metrics;
+use crate::processor::Statistic;
+use crate::ARGS;
+
+/// The time to wait for a flush to the destination.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+/// The number of lines sent in a single http request or tcp connection.
+const BATCH_LINES: usize = 5000;
+/// The number of fetches sent in a single OTLP request.
+const BATCH_OTLP: usize = 10;
+/// The maximum size of a udp datagram, to stay below the common mtu.
+const DATAGRAM_SIZE: usize = 1400;
+/// The number of fetches that can wait for an exporter that is busy flushing.
+const CHANNEL_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocol {
+    InfluxHttp,
+    InfluxUdp,
+    Graphite,
+    Statsd,
+    OtlpHttp,
+}
+
+impl Protocol {
+    fn parse(scheme: &str) -> Option<(Self, u16)> {
+        match scheme {
+            "influx-http" => Some((Protocol::InfluxHttp, 8086)),
+            "influx-udp" => Some((Protocol::InfluxUdp, 8089)),
+            "graphite" => Some((Protocol::Graphite, 2003)),
+            "statsd" => Some((Protocol::Statsd, 8125)),
+            "otlp-http" => Some((Protocol::OtlpHttp, 4318)),
+            _ => None,
+        }
+    }
+}
+
+/// A statistic as exported: the last value, and the per second value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub category: String,
+    pub subcategory: String,
+    pub name: String,
+    pub value: f64,
+    pub per_second: f64,
+    pub timestamp: DateTime<Local>,
+}
+
+/// The samples of the statistics that are updated after the time of the previous export, sorted
+/// by their key. The statistics of a device that is removed are not updated anymore, and
+/// therefore are not exported again.
+pub fn samples(
+    statistics: &HashMap<(String, String, String), Statistic>,
+    after: Option<DateTime<Local>>,
+) -> Vec<Sample> {
+    let mut samples = statistics
+        .iter()
+        .filter(|(_, statistic)| statistic.updated_value)
+        .filter(|(_, statistic)| after.is_none_or(|after| statistic.last_timestamp > after))
+        .map(|((category, subcategory, name), statistic)| Sample {
+            category: category.to_string(),
+            subcategory: subcategory.to_string(),
+            name: name.to_string(),
+            value: statistic.last_value,
+            per_second: statistic.per_second_value,
+            timestamp: statistic.last_timestamp,
+        })
+        .collect::<Vec<Sample>>();
+    samples.sort_by(|a, b| {
+        (&a.category, &a.subcategory, &a.name).cmp(&(&b.category, &b.subcategory, &b.name))
+    });
+    samples
+}
+
+/// Escape the commas, spaces and equal signs of a tag or measurement of the line protocol.
+fn influx_escape(text: &str) -> String {
+    text.replace(',', "\\,")
+        .replace(' ', "\\ ")
+        .replace('=', "\\=")
+}
+
+/// A part of a Graphite or StatsD path, with the characters that have a meaning replaced.
+fn path_component(text: &str) -> String {
+    text.chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() || character == '-' || character == '_' {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+pub struct Exporter {
+    url: String,
+    protocol: Protocol,
+    host: String,
+    path: String,
+    prefix: String,
+    hostname: String,
+    /// the headers of the http requests, such as the authorization token.
+    headers: String,
+    /// the start time of the cumulative sums of OTLP.
+    start_time: DateTime<Local>,
+    /// the lines in the format of the destination; for OTLP, the metrics of a fetch.
+    queue: VecDeque<String>,
+    /// bytes.
+    queue_size: usize,
+    queued_bytes: usize,
+    dropped: u64,
+    failing: bool,
+}
+
+impl Exporter {
+    pub fn new(url: &str, prefix: &str, hostname: &str, queue_size: usize) -> Result<Exporter> {
+        let Some((scheme, address)) = url.split_once("://") else {
+            bail!("export url {} needs a scheme", url);
+        };
+        let Some((protocol, default_port)) = Protocol::parse(scheme) else {
+            bail!(
+                "export url {}: invalid scheme {}, use influx-http, influx-udp, graphite, statsd or otlp-http",
+                url,
+                scheme
+            );
+        };
+        let (host, path) = match address.find('/') {
+            Some(position) => (&address[..position], &address[position..]),
+            None => (address, "/"),
+        };
+        if host.is_empty() {
+            bail!("export url {} needs a host", url);
+        }
+        let host = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{}:{}", host, default_port)
+        };
+        let path = if protocol == Protocol::OtlpHttp && path == "/" {
+            "/v1/metrics"
+        } else {
+            path
+        };
+        Ok(Exporter {
+            url: url.to_string(),
+            protocol,
+            host,
+            path: path.to_string(),
+            prefix: prefix.to_string(),
+            hostname: hostname.to_string(),
+            headers: String::new(),
+            start_time: Local::now(),
+            queue: VecDeque::new(),
+            queue_size,
+            queued_bytes: 0,
+            dropped: 0,
+            failing: false,
+        })
+    }
+    pub fn with_token(mut self, token: Option<&str>) -> Exporter {
+        if let Some(token) = token {
+            self.headers = format!("Authorization: Token {}\r\n", token);
+        }
+        self
+    }
+    pub fn with_start_time(mut self, start_time: Option<DateTime<Local>>) -> Exporter {
+        if let Some(start_time) = start_time {
+            self.start_time = start_time;
+        }
+        self
+    }
+    /// The lines of the samples in the format of the destination.
+    fn lines(&self, samples: &[Sample]) -> Vec<String> {
+        if self.protocol == Protocol::OtlpHttp {
+            return vec![serde_json::to_string(&resource_metrics(
+                samples,
+                &self.prefix,
+                &self.hostname,
+                self.start_time,
+            ))
+            .unwrap()];
+        }
+        let mut lines = Vec::new();
+        for sample in samples {
+            match self.protocol {
+                Protocol::InfluxHttp | Protocol::InfluxUdp => {
+                    let mut line = format!(
+                        "{},host={},category={}",
+                        influx_escape(&self.prefix),
+                        influx_escape(&self.hostname),
+                        influx_escape(&sample.category)
+                    );
+                    // the line protocol does not allow empty tag values.
+                    if !sample.subcategory.is_empty() {
+                        let _ = write!(line, ",subcategory={}", influx_escape(&sample.subcategory));
+                    }
+                    // the line protocol has no value for not a number or infinite.
+                    let fields = [("value", sample.value), ("per_second", sample.per_second)]
+                        .iter()
+                        .filter(|(_, value)| value.is_finite())
+                        .map(|(field, value)| format!("{}={}", field, value))
+                        .collect::<Vec<String>>();
+                    if fields.is_empty() {
+                        continue;
+                    }
+                    let _ = write!(
+                        line,
+                        ",name={} {} {}",
+                        influx_escape(&sample.name),
+                        fields.join(","),
+                        sample.timestamp.timestamp_nanos_opt().unwrap_or_default()
+                    );
+                    lines.push(line);
+                }
+                Protocol::OtlpHttp => {}
+                Protocol::Graphite | Protocol::Statsd => {
+                    let path = [
+                        &self.prefix,
+                        &self.hostname,
+                        &sample.category,
+                        &sample.subcategory,
+                        &sample.name,
+                    ]
+                    .iter()
+                    .filter(|component| !component.is_empty())
+                    .map(|component| path_component(component))
+                    .collect::<Vec<String>>()
+                    .join(".");
+                    for (path, value) in [
+                        (path.clone(), sample.value),
+                        (format!("{}_per_second", path), sample.per_second),
+                    ]
+                    .into_iter()
+                    .filter(|(_, value)| value.is_finite())
+                    {
+                        lines.push(if self.protocol == Protocol::Graphite {
+                            format!("{} {} {}", path, value, sample.timestamp.timestamp())
+                        } else {
+                            format!("{}:{}|g", path, value)
+                        });
+                    }
+                }
+            }
+        }
+        lines
+    }
+    /// Add the samples to the queue, and drop the oldest lines if the queue is full.
+    pub fn add(&mut self, samples: &[Sample]) {
+        for line in self.lines(samples) {
+            self.queued_bytes += line.len();
+            self.queue.push_back(line);
+        }
+        while self.queued_bytes > self.queue_size {
+            let Some(line) = self.queue.pop_front() else {
+                break;
+            };
+            self.queued_bytes -= line.len();
+            self.dropped += 1;
+        }
+    }
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+    /// Send the queued lines in batches. The lines of a batch are removed from the queue when
+    /// they are sent, so a failure keeps the lines that are not sent for the next flush. A batch
+    /// that the destination rejects would be rejected again, so it is dropped.
+    pub async fn flush(&mut self) -> Result<()> {
+        while !self.queue.is_empty() {
+            let batch = self
+                .queue
+                .iter()
+                .take(if self.protocol == Protocol::OtlpHttp {
+                    BATCH_OTLP
+                } else {
+                    BATCH_LINES
+                })
+                .cloned()
+                .collect::<Vec<String>>();
+            let mut sent = 0;
+            let result = self.send(&batch, &mut sent).await;
+            let done = match result {
+                Ok(()) => batch.len(),
+                Err(error)
+                    if error
+                        .downcast_ref::<HttpStatusError>()
+                        .is_some_and(|error| error.is_permanent()) =>
+                {
+                    eprintln!(
+                        "Export {}: dropped {} lines that are rejected: {:#}",
+                        self.url,
+                        batch.len(),
+                        error
+                    );
+                    self.dropped += batch.len() as u64;
+                    batch.len()
+                }
+                Err(error) => {
+                    self.remove_sent(sent);
+                    return Err(error);
+                }
+            };
+            self.remove_sent(done);
+        }
+        Ok(())
+    }
+    fn remove_sent(&mut self, lines: usize) {
+        for line in self.queue.drain(..lines) {
+            self.queued_bytes -= line.len();
+        }
+    }
+    /// Send the lines; sent is the number of lines that are sent, which is less than the number
+    /// of lines if some of the udp datagrams are sent before a failure.
+    async fn send(&self, lines: &[String], sent: &mut usize) -> Result<()> {
+        match self.protocol {
+            Protocol::InfluxHttp => {
+                let mut body = lines.join("\n");
+                body.push('\n');
+                http_post(
+                    &self.host,
+                    &self.path,
+                    "text/plain; charset=utf-8",
+                    &self.headers,
+                    body,
+                )
+                .await?;
+            }
+            Protocol::OtlpHttp => {
+                http_post(
+                    &self.host,
+                    &self.path,
+                    "application/json",
+                    &self.headers,
+                    format!(r#"{{"resourceMetrics":[{}]}}"#, lines.join(",")),
+                )
+                .await?;
+            }
+            Protocol::Graphite => {
+                let mut stream = TcpStream::connect(&self.host)
+                    .await
+                    .with_context(|| format!("Unable to connect to {}", self.host))?;
+                let mut body = lines.join("\n");
+                body.push('\n');
+                stream.write_all(body.as_bytes()).await?;
+                stream.shutdown().await?;
+            }
+            Protocol::InfluxUdp | Protocol::Statsd => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket
+                    .connect(&self.host)
+                    .await
+                    .with_context(|| format!("Unable to connect to {}", self.host))?;
+                // the lines are packed in datagrams, a line is never split over two datagrams.
+                let mut datagram = String::new();
+                let mut datagram_lines = 0;
+                for line in lines {
+                    if !datagram.is_empty() && datagram.len() + line.len() + 1 > DATAGRAM_SIZE {
+                        socket.send(datagram.as_bytes()).await?;
+                        *sent += datagram_lines;
+                        datagram.clear();
+                        datagram_lines = 0;
+                    }
+                    datagram.push_str(line);
+                    datagram.push('\n');
+                    datagram_lines += 1;
+                }
+                if !datagram.is_empty() {
+                    socket.send(datagram.as_bytes()).await?;
+                }
+            }
+        }
+        *sent = lines.len();
+        Ok(())
+    }
+    /// Add the samples of every fetch, and flush every number of fetches, until the sender is
+    /// dropped.
+    async fn run(mut self, mut receiver: mpsc::Receiver<Arc<Vec<Sample>>>, every: u64) {
+        let mut fetches = 0_u64;
+        while let Some(samples) = receiver.recv().await {
+            self.add(&samples);
+            fetches += 1;
+            if !fetches.is_multiple_of(every) {
+                continue;
+            }
+            match timeout(FLUSH_TIMEOUT, self.flush())
+                .await
+                .context("timeout")
+                .and_then(|result| result)
+            {
+                Ok(()) => {
+                    if self.failing {
+                        eprintln!("Export {}: sending again.", self.url);
+                        self.failing = false;
+                    }
+                }
+                // a destination that can't be reached is reported once, not every flush.
+                Err(error) if !self.failing => {
+                    eprintln!(
+                        "Export {} failed, queueing the statistics: {:#}",
+                        self.url, error
+                    );
+                    self.failing = true;
+                }
+                Err(_) => {}
+            }
+            if self.dropped > 0 {
+                eprintln!(
+                    "Export {}: dropped {} lines in total.",
+                    self.url, self.dropped
+                );
+                self.dropped = 0;
+            }
+        }
+    }
+}
+
+/// The exporters of the urls, which each run in the background, so a slow destination does not
+/// delay fetching the statistics.
+pub struct Exporters {
+    senders: Vec<(String, mpsc::Sender<Arc<Vec<Sample>>>)>,
+    last_timestamp: Option<DateTime<Local>>,
+}
+
+impl Exporters {
+    pub fn start(urls: &[String]) -> Result<Exporters> {
+        let metadata = ArchiveMetadata::current(std::iter::empty(), std::iter::empty());
+        let mut senders = Vec::new();
+        for url in urls {
+            let exporter = Exporter::new(
+                url,
+                &ARGS.export_prefix,
+                &metadata.hostname,
+                ARGS.export_queue * 1024 * 1024,
+            )?
+            .with_token(ARGS.export_token.as_deref())
+            // the counters are the totals since the boot of the host.
+            .with_start_time(metadata.boot_time);
+            let (sender, receiver) = mpsc::channel(CHANNEL_SIZE);
+            tokio::spawn(exporter.run(receiver, ARGS.export_every.max(1)));
+            senders.push((url.to_string(), sender));
+        }
+        Ok(Exporters {
+            senders,
+            last_timestamp: None,
+        })
+    }
+    /// Export the statistics that are updated since the previous export.
+    pub fn export(&mut self, statistics: &HashMap<(String, String, String), Statistic>) {
+        let samples = Arc::new(samples(statistics, self.last_timestamp));
+        if let Some(timestamp) = samples.iter().map(|sample| sample.timestamp).max() {
+            self.last_timestamp = Some(timestamp);
+        }
+        if samples.is_empty() {
+            return;
+        }
+        for (url, sender) in &self.senders {
+            if sender.try_send(samples.clone()).is_err() {
+                eprintln!("Export {}: the exporter is busy, skipped a fetch.", url);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    const QUEUE_SIZE: usize = 1024 * 1024;
+
+    fn test_samples() -> Vec<Sample> {
+        let timestamp: DateTime<Local> = DateTime::parse_from_rfc3339("2023-12-13T15:20:24+00:00")
+            .unwrap()
+            .into();
+        vec![
+            Sample {
+                category: "blockdevice".to_string(),
+                subcategory: "sda".to_string(),
+                name: "stat_reads_completed_success".to_string(),
+                value: 1000_f64,
+                per_second: 25_f64,
+                timestamp,
+            },
+            Sample {
+                category: "loadavg".to_string(),
+                subcategory: "".to_string(),
+                name: "load_1".to_string(),
+                value: 1.5_f64,
+                per_second: 0_f64,
+                timestamp,
+            },
+        ]
+    }
+
+    #[test]
+    fn samples_are_serialized_to_the_protocol() {
+        let influx =
+            Exporter::new("influx-udp://localhost", "procstat", "db 01", QUEUE_SIZE).unwrap();
+        assert_eq!(influx.host, "localhost:8089");
+        assert_eq!(
+            influx.lines(&test_samples()),
+            vec![
+                "procstat,host=db\\ 01,category=blockdevice,subcategory=sda,name=stat_reads_completed_success value=1000,per_second=25 1702480824000000000",
+                "procstat,host=db\\ 01,category=loadavg,name=load_1 value=1.5,per_second=0 1702480824000000000",
+            ]
+        );
+        let graphite =
+            Exporter::new("graphite://localhost", "procstat", "db.01", QUEUE_SIZE).unwrap();
+        assert_eq!(
+            graphite.lines(&test_samples())[2..],
+            [
+                "procstat.db_01.loadavg.load_1 1.5 1702480824",
+                "procstat.db_01.loadavg.load_1_per_second 0 1702480824",
+            ]
+        );
+        let statsd =
+            Exporter::new("statsd://localhost:9125", "procstat", "db01", QUEUE_SIZE).unwrap();
+        assert_eq!(
+            statsd.lines(&test_samples())[..1],
+            ["procstat.db01.blockdevice.sda.stat_reads_completed_success:1000|g"]
+        );
+        assert!(Exporter::new("carbon://localhost", "procstat", "db01", QUEUE_SIZE).is_err());
+    }
+
+    #[test]
+    fn values_that_are_not_finite_are_skipped() {
+        let mut samples = test_samples();
+        samples[0].per_second = f64::NAN;
+        samples[1].value = f64::INFINITY;
+        samples[1].per_second = f64::NAN;
+        let influx =
+            Exporter::new("influx-udp://localhost", "procstat", "db01", QUEUE_SIZE).unwrap();
+        assert_eq!(
+            influx.lines(&samples),
+            vec!["procstat,host=db01,category=blockdevice,subcategory=sda,name=stat_reads_completed_success value=1000 1702480824000000000"]
+        );
+        let graphite =
+            Exporter::new("graphite://localhost", "procstat", "db01", QUEUE_SIZE).unwrap();
+        assert_eq!(
+            graphite.lines(&samples),
+            vec!["procstat.db01.blockdevice.sda.stat_reads_completed_success 1000 1702480824"]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_rejected_batch_is_dropped() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("influx-http://{}/write", listener.local_addr().unwrap());
+        let mut exporter = Exporter::new(&url, "procstat", "db01", QUEUE_SIZE).unwrap();
+        exporter.add(&test_samples());
+        let (flushed, _) = tokio::join!(exporter.flush(), async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0_u8; 4096];
+            let _ = stream.read(&mut buffer).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+        flushed.unwrap();
+        assert_eq!(exporter.queued(), 0);
+        assert_eq!(exporter.dropped, 2);
+    }
+
+    #[tokio::test]
+    async fn statsd_is_sent_to_a_listener() {
+        let listener = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("statsd://{}", listener.local_addr().unwrap());
+        let mut exporter = Exporter::new(&url, "procstat", "db01", QUEUE_SIZE).unwrap();
+        exporter.add(&test_samples());
+        exporter.flush().await.unwrap();
+        let mut buffer = [0_u8; DATAGRAM_SIZE];
+        let length = listener.recv(&mut buffer).await.unwrap();
+        let datagram = String::from_utf8_lossy(&buffer[..length]).to_string();
+        assert_eq!(datagram.lines().count(), 4);
+        assert!(datagram.contains("procstat.db01.loadavg.load_1:1.5|g\n"));
+        assert_eq!(exporter.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn graphite_is_queued_during_an_outage() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let url = format!("graphite://{}", address);
+        let size = Exporter::new(&url, "procstat", "db01", QUEUE_SIZE)
+            .unwrap()
+            .lines(&test_samples())
+            .iter()
+            .map(|line| line.len())
+            .sum::<usize>();
+        let mut exporter = Exporter::new(&url, "procstat", "db01", size + size / 2).unwrap();
+        exporter.add(&test_samples());
+        assert!(exporter.flush().await.is_err());
+        assert_eq!(exporter.queued(), 4);
+        // the queue is bounded: the oldest lines are dropped.
+        exporter.add(&test_samples());
+        let queued = exporter.queued();
+        assert!(queued > 4 && queued < 8);
+        assert_eq!(exporter.dropped, 8 - queued as u64);
+
+        let listener = TcpListener::bind(address).await.unwrap();
+        let (flushed, received) = tokio::join!(exporter.flush(), async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = String::new();
+            stream.read_to_string(&mut received).await.unwrap();
+            received
+        });
+        flushed.unwrap();
+        assert_eq!(received.lines().count(), queued);
+        assert_eq!(exporter.queued(), 0);
+    }
+}
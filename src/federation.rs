@@ -0,0 +1,270 @@
+//! Federation: an aggregator reads the recent history of other procstat instances from their
+//! webserver, and keeps it in the history of their host, so the webserver of the aggregator can
+//! show the hosts side by side.
+use anyhow::{bail, Context, Result};
+use axum::extract::Query;
+use axum::Json;
+use chrono::{DateTime, Local, TimeDelta, TimeZone};
+use futures::future::join_all;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{self, timeout, Duration, MissedTickBehavior};
+
+use crate::archiver::{add_to_history, historical_data};
+use crate::http::parse_http_url;
+use crate::processor::{Data, HistoricalDataTransit, HistoryRow, LAST_FETCH};
+use crate::{ARGS, DATA};
+
+/// The time to wait for the history of an instance.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A procstat instance that is federated.
+struct Instance {
+    url: String,
+    host: String,
+    path: String,
+    /// the time of the last row that is read, to only read newer rows.
+    last_timestamp: Option<DateTime<Local>>,
+    /// the host name to keep the history under, from the metadata of the instance.
+    hostname: Option<String>,
+    failing: bool,
+}
+
+impl Instance {
+    fn new(url: &str) -> Result<Instance> {
+        let (host, path) =
+            parse_http_url(url).with_context(|| format!("invalid federate url {}", url))?;
+        Ok(Instance {
+            url: url.to_string(),
+            host,
+            path,
+            last_timestamp: None,
+            hostname: None,
+            failing: false,
+        })
+    }
+    /// Read the history of the instance after the last timestamp.
+    async fn fetch_history(&self) -> Result<FederatedHistory> {
+        let mut stream = TcpStream::connect(&self.host)
+            .await
+            .with_context(|| format!("Unable to connect to {}", self.host))?;
+        // http/1.0, so the response is not chunked and ends when the connection is closed.
+        stream
+            .write_all(
+                format!(
+                    "GET {}/history{} HTTP/1.0\r\nHost: {}\r\n\r\n",
+                    self.path.trim_end_matches('/'),
+                    self.last_timestamp
+                        .and_then(|timestamp| timestamp.timestamp_nanos_opt())
+                        .map(|nanoseconds| format!("?since={}", nanoseconds))
+                        .unwrap_or_default(),
+                    self.host
+                )
+                .as_bytes(),
+            )
+            .await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        let Some(header_end) = response.windows(4).position(|window| window == b"\r\n\r\n") else {
+            bail!("invalid response");
+        };
+        let header = String::from_utf8_lossy(&response[..header_end]);
+        let status_line = header.lines().next().unwrap_or_default();
+        if status_line.split_whitespace().nth(1) != Some("200") {
+            bail!("history returned: {}", status_line);
+        }
+        Ok(serde_json::from_slice(&response[header_end + 4..])?)
+    }
+    fn add_history(&mut self, federated: FederatedHistory) {
+        let transition = federated.history;
+        if let Some(metadata) = &transition.metadata {
+            self.hostname = Some(metadata.hostname.clone());
+        }
+        // the history is read up to the high time of the instance; instances of older versions
+        // don't return it, so the time of their last row is used.
+        let last_timestamp = last_timestamp(&transition);
+        if let Some(high_time) = federated.high_time.or(last_timestamp) {
+            self.last_timestamp = Some(high_time);
+        }
+        // an instance without new rows, such as an instance that is starting, is not added yet.
+        if last_timestamp.is_none() {
+            return;
+        }
+        // an instance without metadata is shown with the host and port of its url.
+        let data = DATA.host(self.hostname.as_deref().unwrap_or(&self.host));
+        debug!(
+            "{}: new rows up to {:?}, next since: {:?}",
+            self.url, last_timestamp, self.last_timestamp
+        );
+        add_to_history(transition, data);
+        trim_history(data);
+        data.roll_up();
+    }
+}
+
+/// The time of the last row of all histories.
+fn last_timestamp(transition: &HistoricalDataTransit) -> Option<DateTime<Local>> {
+    macro_rules! last_timestamp {
+        ($($history:ident),*) => {
+            [$(transition.$history.iter().map(|row| row.timestamp()).max()),*]
+        };
+    }
+    last_timestamp!(
+        cpu,
+        per_cpu,
+        schedstat,
+        memory,
+        blockdevices,
+        networkdevices,
+        loadavg,
+        pressure,
+        vmstat,
+        xfs
+    )
+    .into_iter()
+    .flatten()
+    .max()
+}
+
+/// Remove the rows of a history that are older than the history time before its last row.
+fn trim_history(data: &Data) {
+    macro_rules! trim {
+        ($($history:ident),*) => {
+            $(
+            let mut history = data.$history.write().unwrap();
+            if let Some(last) = history.back().map(|row| row.timestamp()) {
+                let oldest = last - TimeDelta::seconds(ARGS.history as i64);
+                while history.front().is_some_and(|row| row.timestamp() < oldest) {
+                    history.pop_front();
+                }
+            }
+            drop(history);
+            )*
+        };
+    }
+    trim!(
+        cpu,
+        per_cpu,
+        schedstat,
+        memory,
+        blockdevices,
+        networkdevices,
+        loadavg,
+        pressure,
+        vmstat,
+        xfs
+    );
+}
+
+/// Read the history of the federated instances every interval, until procstat is stopped.
+pub async fn federator() -> Result<()> {
+    let mut instances = ARGS
+        .federate
+        .clone()
+        .unwrap_or_default()
+        .iter()
+        .map(|url| Instance::new(url))
+        .collect::<Result<Vec<Instance>>>()?;
+
+    // the instances are read at most every second, also with a sub-second interval.
+    let mut interval = time::interval(Duration::from_secs_f64(ARGS.interval.max(1_f64)));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        // the instances are read together, so a slow instance does not delay the others.
+        let histories = join_all(
+            instances
+                .iter()
+                .map(|instance| timeout(FETCH_TIMEOUT, instance.fetch_history())),
+        )
+        .await;
+        for (instance, history) in instances.iter_mut().zip(histories) {
+            match history.context("timeout").and_then(|history| history) {
+                Ok(transition) => {
+                    if instance.failing {
+                        eprintln!("Federate {}: reading the history again.", instance.url);
+                        instance.failing = false;
+                    }
+                    instance.add_history(transition);
+                }
+                // an instance that can't be read is reported once, not every interval.
+                Err(error) if !instance.failing => {
+                    eprintln!("Federate {} failed: {:#}", instance.url, error);
+                    instance.failing = true;
+                }
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    /// nanoseconds since the epoch, the precision of the timestamps, so the last row that is
+    /// read is not returned again.
+    pub since: Option<i64>,
+}
+
+/// The history of an instance for the aggregator of a federation, with the time up to which it
+/// is returned, which is the since time of the next read.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FederatedHistory {
+    #[serde(flatten)]
+    pub history: HistoricalDataTransit,
+    /// not returned by instances of older versions.
+    #[serde(default)]
+    pub high_time: Option<DateTime<Local>>,
+}
+
+/// The history after the since time, for the aggregator of a federation.
+pub async fn handler_history(Query(history_query): Query<HistoryQuery>) -> Json<FederatedHistory> {
+    let low_time = history_query
+        .since
+        .map(|since| Local.timestamp_nanos(since))
+        .unwrap_or_default();
+    // the statistics of a fetch are added to the histories one after the other, and the collectors
+    // can have their own interval, so the history is returned up to the last complete fetch.
+    let high_time = LAST_FETCH
+        .read()
+        .unwrap()
+        .map_or(low_time, |last_fetch| last_fetch.max(low_time));
+    Json(FederatedHistory {
+        history: historical_data(low_time, high_time),
+        high_time: Some(high_time),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::meminfo::MemInfo;
+
+    #[test]
+    fn history_without_cpu_rows_is_read_up_to_its_high_time() {
+        let timestamp = Local.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let transition = HistoricalDataTransit {
+            memory: vec![MemInfo {
+                timestamp,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(last_timestamp(&transition), Some(timestamp));
+        assert_eq!(last_timestamp(&HistoricalDataTransit::default()), None);
+
+        // an instance of an older version returns the history without the high time.
+        let federated: FederatedHistory =
+            serde_json::from_value(serde_json::to_value(&transition).unwrap()).unwrap();
+        assert_eq!(federated.high_time, None);
+        assert_eq!(federated.history.memory.len(), 1);
+        let federated = FederatedHistory {
+            history: transition,
+            high_time: Some(timestamp),
+        };
+        let federated: FederatedHistory =
+            serde_json::from_value(serde_json::to_value(&federated).unwrap()).unwrap();
+        assert_eq!(federated.high_time, Some(timestamp));
+    }
+}
@@ -0,0 +1,141 @@
+//! The http requests of the webhooks and the exporters: a request over a new connection, with
+//! timeouts, so a server that doesn't respond can't keep a task waiting.
+use anyhow::{bail, Context, Result};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+
+/// The time to wait for the connection to the server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// The time to wait for the request to be sent and the response to be read.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The server responded with a status that is not a 2xx status.
+#[derive(Debug, Error)]
+#[error("returned: {status_line}")]
+pub struct HttpStatusError {
+    pub status: u16,
+    pub status_line: String,
+}
+
+impl HttpStatusError {
+    /// The request itself is rejected, so sending it again fails again: a 4xx status, except for
+    /// a timeout (408) and too many requests (429).
+    pub fn is_permanent(&self) -> bool {
+        (400..500).contains(&self.status) && self.status != 408 && self.status != 429
+    }
+}
+
+/// Split a http url into the host with the port to connect to, and the path.
+pub(crate) fn parse_http_url(url: &str) -> Result<(String, String)> {
+    let Some(url) = url.strip_prefix("http://") else {
+        bail!("url {} should start with http://", url);
+    };
+    let (host, path) = match url.find('/') {
+        Some(position) => (&url[..position], &url[position..]),
+        None => (url, "/"),
+    };
+    if host.is_empty() {
+        bail!("url needs a host");
+    }
+    let host = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    Ok((host, path.to_string()))
+}
+
+/// Post the body to the path at the host, and fail if the response is not a 2xx status. The
+/// headers are added as is, and should end with "\r\n".
+pub(crate) async fn http_post(
+    host: &str,
+    path: &str,
+    content_type: &str,
+    headers: &str,
+    body: String,
+) -> Result<()> {
+    post(host, path, content_type, headers, body, RESPONSE_TIMEOUT).await
+}
+
+async fn post(
+    host: &str,
+    path: &str,
+    content_type: &str,
+    headers: &str,
+    body: String,
+    response_timeout: Duration,
+) -> Result<()> {
+    let mut stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(host))
+        .await
+        .context("timeout")
+        .and_then(|stream| Ok(stream?))
+        .with_context(|| format!("Unable to connect to {}", host))?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        path,
+        host,
+        content_type,
+        body.len(),
+        headers,
+        body
+    );
+    let response = timeout(response_timeout, async {
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok::<String, std::io::Error>(response)
+    })
+    .await
+    .with_context(|| format!("No response from {} within {:?}", host, response_timeout))??;
+    let status_line = response.lines().next().unwrap_or_default();
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .unwrap_or_default();
+    if !(200..300).contains(&status) {
+        return Err(HttpStatusError {
+            status,
+            status_line: status_line.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn post_to_a_server_that_does_not_respond_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        // the connection is accepted, but the request is never answered.
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            drop(stream);
+        });
+        let result = post(
+            &host,
+            "/alert",
+            "application/json",
+            "",
+            "{}".to_string(),
+            Duration::from_millis(100),
+        )
+        .await;
+        assert!(format!("{:#}", result.unwrap_err()).contains("No response"));
+        server.abort();
+
+        assert_eq!(
+            parse_http_url("http://localhost/alert").unwrap(),
+            ("localhost:80".to_string(), "/alert".to_string())
+        );
+        assert!(parse_http_url("https://localhost").is_err());
+    }
+}
@@ -1,10 +1,26 @@
-use clap::{Parser, ValueEnum};
+use archiver::{ArchiveCompression, ArchiveFormat};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use config::Config;
 use once_cell::sync::Lazy;
-use processor::Data;
+use processor::rollup::ROLLUP_LEVELS;
+use processor::{Collector, Data, Histories};
+use std::sync::atomic::{AtomicBool, Ordering};
+use webserver::PlotFormat;
 
+pub mod alerting;
 pub mod app;
 pub mod archiver;
+pub mod columnar;
+pub mod config;
+pub mod exporter;
+pub mod federation;
+pub mod http;
+pub mod metadata;
+pub mod otlp;
 pub mod processor;
+pub mod replay;
+pub mod report;
 pub mod webserver;
 
 static LABEL_AREA_SIZE_LEFT: i32 = 100;
@@ -64,12 +80,99 @@ enum OutputOptions {
     Free,
 }
 
+impl OutputOptions {
+    /// The collectors of which the output prints the statistics.
+    fn collectors(&self) -> &'static [Collector] {
+        match self {
+            OutputOptions::SarU
+            | OutputOptions::SarUAll
+            | OutputOptions::MpstatPAll
+            | OutputOptions::Sarw => &[Collector::Cpu],
+            OutputOptions::CpuAll | OutputOptions::PerCpuAll => {
+                &[Collector::Cpu, Collector::Schedstat]
+            }
+            OutputOptions::Schedstat => &[Collector::Schedstat],
+            OutputOptions::SarD
+            | OutputOptions::Sarb
+            | OutputOptions::Iostat
+            | OutputOptions::IostatX
+            | OutputOptions::Ioq
+            | OutputOptions::Ios => &[Collector::Disk],
+            OutputOptions::SarH
+            | OutputOptions::SarR
+            | OutputOptions::SarRAll
+            | OutputOptions::SarS => &[Collector::Memory],
+            OutputOptions::SarNDev | OutputOptions::SarNEdev => &[Collector::Network],
+            OutputOptions::SarQLoad | OutputOptions::SarQ => &[Collector::Loadavg],
+            OutputOptions::SarQCpu | OutputOptions::SarQIo | OutputOptions::SarQMem => {
+                &[Collector::Pressure]
+            }
+            OutputOptions::SarB | OutputOptions::SarW => &[Collector::Vmstat],
+            OutputOptions::Free => &[Collector::Vmstat, Collector::Memory],
+            OutputOptions::Vmstat => &[Collector::Vmstat, Collector::Memory, Collector::Cpu],
+        }
+    }
+    /// Returns an error if a collector of the output doesn't run.
+    pub(crate) fn check_collectors(&self) -> anyhow::Result<()> {
+        let missing: Vec<_> = self
+            .collectors()
+            .iter()
+            .filter(|collector| !collector.enabled())
+            .map(|collector| collector.to_string())
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "The output {} needs the statistics of the collector(s) {}, which are not collected.",
+                self.to_possible_value().unwrap().get_name(),
+                missing.join(", ")
+            );
+        }
+        Ok(())
+    }
+    /// Returns an error if the data has no rows of a collector of the output, such as for a
+    /// replay of archives of a host on which the collector didn't run.
+    pub(crate) fn check_rows(&self, data: &Data) -> anyhow::Result<()> {
+        let mut missing: Vec<_> = self
+            .collectors()
+            .iter()
+            .filter(|collector| !data.has_rows(**collector))
+            .map(|collector| collector.to_string())
+            .collect();
+        // archives of older versions have no per CPU rows.
+        if matches!(self, OutputOptions::MpstatPAll | OutputOptions::PerCpuAll)
+            && data.per_cpu.read().unwrap().is_empty()
+        {
+            missing.push("per CPU".to_string());
+        }
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "The archives contain no statistics of {} for the output {}.",
+                missing.join(", "),
+                self.to_possible_value().unwrap().get_name()
+            );
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Parser, Clone)]
 #[clap(version, about, long_about = None)]
 pub struct Opts {
-    /// Interval
-    #[arg(short = 'i', long, value_name = "time (s)", default_value = "1")]
-    pub interval: u64,
+    /// Configuration file; the options on the command line override its settings
+    #[arg(long, value_name = "config file")]
+    pub config: Option<String>,
+    /// Print the effective settings in the format of the configuration file, and exit
+    #[arg(long)]
+    pub print_config: bool,
+    /// Interval, such as 0.1 for 100ms
+    #[arg(
+        short = 'i',
+        long,
+        value_name = "time (s)",
+        default_value = "1",
+        value_parser = parse_interval
+    )]
+    pub interval: f64,
     /// run Until
     #[arg(short = 'u', long, value_name = "run until cycle nr")]
     pub until: Option<u64>,
@@ -84,17 +187,15 @@ pub struct Opts {
         default_value = "30"
     )]
     pub header_print: u64,
-    /// History size
-    #[arg(
-        short = 's',
-        long,
-        value_name = "nr statistics",
-        default_value = "10800"
-    )]
+    /// History time: the rows of this many seconds are kept, the older rows are kept as roll-ups
+    #[arg(short = 's', long, value_name = "time (s)", default_value = "3600")]
     pub history: usize,
     /// Read history (only read archives, no active fetching)
     #[arg(short = 'r', long, value_name = "read archives", num_args(1..))]
     pub read: Option<Vec<String>>,
+    /// Federate: read the history of the procstat instances at these urls (only federating, no active fetching)
+    #[arg(long, value_name = "procstat urls", num_args(1..))]
+    pub federate: Option<Vec<String>>,
     /// Enable webserver
     #[arg(short = 'w', long, value_name = "enable webserver")]
     pub webserver: bool,
@@ -106,6 +207,9 @@ pub struct Opts {
         default_value = "1111"
     )]
     pub webserver_port: u64,
+    /// Webserver bind address
+    #[arg(long, value_name = "address", default_value = "0.0.0.0")]
+    pub webserver_bind: String,
     /// Enable archiver
     #[arg(short = 'A', long, value_name = "enable archiving")]
     pub archiver: bool,
@@ -120,6 +224,21 @@ pub struct Opts {
         default_value = "10"
     )]
     pub archiver_interval: i64,
+    /// Archive directory
+    #[arg(long, value_name = "archive directory", default_value = ".")]
+    pub archive_dir: String,
+    /// Archive format
+    #[arg(long, value_name = "format", value_enum, default_value_t = ArchiveFormat::Json)]
+    pub archive_format: ArchiveFormat,
+    /// Archive compression
+    #[arg(long, value_name = "compression", value_enum, default_value_t = ArchiveCompression::None)]
+    pub archive_compression: ArchiveCompression,
+    /// Remove archives older than this number of hours
+    #[arg(long, value_name = "hours")]
+    pub archive_max_age: Option<u64>,
+    /// Remove the oldest archives when the archives together are larger than this number of megabytes
+    #[arg(long, value_name = "megabytes")]
+    pub archive_max_size: Option<u64>,
     /// graph buffer width
     #[arg(
         short = 'W',
@@ -136,14 +255,329 @@ pub struct Opts {
         default_value = "1200"
     )]
     pub graph_height: u32,
-    /// disk filter
+    /// disk filter: regex of the kernel names (dm-N) or dm names (vg0-data) of devices to skip
+    #[arg(long, value_name = "disk filter", default_value = "")]
+    pub disk_filter: String,
+    /// network filter: regex of the names of the network interfaces to skip
+    #[arg(long, value_name = "network filter", default_value = "^lo")]
+    pub network_filter: String,
+    /// Collect the partitions of the disks too
+    #[arg(long, value_name = "collect partitions")]
+    pub partitions: bool,
+    /// Add totals for the device-mapper (TOTAL-dm) and md (TOTAL-md) layers
+    #[arg(long, value_name = "layer totals")]
+    pub layer_totals: bool,
+    /// Alerting rules file
+    #[arg(long, value_name = "rules file")]
+    pub rules: Option<String>,
+    /// Collectors to run, comma separated
     #[arg(
         long,
-        value_name = "disk filter",
-        default_value = "^dm-"
+        value_name = "collectors",
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = Collector::all()
     )]
-    pub disk_filter: String,
+    pub collectors: Vec<Collector>,
+    /// Interval of a collector, such as xfs=60; the collectors without one use the interval
+    #[arg(
+        long,
+        value_name = "collector=time (s)",
+        value_delimiter = ',',
+        value_parser = parse_collector_interval
+    )]
+    pub collector_interval: Vec<(Collector, f64)>,
+    /// Export the statistics to these urls: influx-http://, influx-udp://, graphite://, statsd:// or otlp-http://
+    #[arg(long, value_name = "export urls", num_args(1..))]
+    pub export: Option<Vec<String>>,
+    /// Export every number of fetches
+    #[arg(long, value_name = "fetches", default_value = "1")]
+    pub export_every: u64,
+    /// Maximum size of the queue per export url while the destination can't be reached
+    #[arg(long, value_name = "megabytes", default_value = "64")]
+    pub export_queue: usize,
+    /// Measurement name (InfluxDB), first path component (Graphite, StatsD) or metric name prefix (OTLP) of the exported statistics
+    #[arg(long, value_name = "prefix", default_value = "procstat")]
+    pub export_prefix: String,
+    /// InfluxDB token for influx-http export urls
+    #[arg(long, value_name = "token")]
+    pub export_token: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum Command {
+    /// Render all plots of archives to image files with a static html index
+    Report(ReportOpts),
+    /// Print the statistics of archives as CLI output, like sar -f
+    Replay(ReplayOpts),
+    /// Convert archives to another archive format or compression
+    Convert(ConvertOpts),
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct ConvertOpts {
+    /// Archives to convert
+    #[arg(value_name = "archives", num_args(1..), required = true)]
+    pub archives: Vec<String>,
+    /// Archive format to convert to
+    #[arg(long, value_name = "format", value_enum, default_value_t = ArchiveFormat::Columnar)]
+    pub format: ArchiveFormat,
+    /// Archive compression to convert to
+    #[arg(long, value_name = "compression", value_enum, default_value_t = ArchiveCompression::None)]
+    pub compression: ArchiveCompression,
+    /// Directory to write the converted archives to (default: the directory of the archive)
+    #[arg(short = 'd', long, value_name = "directory")]
+    pub directory: Option<String>,
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct ReplayOpts {
+    /// Archives to read
+    #[arg(value_name = "archives", num_args(1..), required = true)]
+    pub archives: Vec<String>,
+    /// Output
+    #[arg(short = 'o', long, value_name = "option", value_enum, default_value_t = OutputOptions::SarU)]
+    output: OutputOptions,
+    /// Interval to aggregate the statistics to (default: the interval of the archives)
+    #[arg(short = 'i', long, value_name = "time (s)")]
+    pub interval: Option<u64>,
+    /// Start time of the replay (default: start of the archives)
+    #[arg(long, value_name = "time", value_parser = parse_time)]
+    pub start: Option<DateTime<Local>>,
+    /// End time of the replay (default: end of the archives)
+    #[arg(long, value_name = "time", value_parser = parse_time)]
+    pub end: Option<DateTime<Local>>,
+    /// Allow reading archives of different hosts together
+    #[arg(long, value_name = "allow mixed hosts")]
+    pub allow_mixed_hosts: bool,
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct ReportOpts {
+    /// Archives to read
+    #[arg(value_name = "archives", num_args(1..), required = true)]
+    pub archives: Vec<String>,
+    /// Report directory
+    #[arg(
+        short = 'd',
+        long,
+        value_name = "report directory",
+        default_value = "procstat_report"
+    )]
+    pub directory: String,
+    /// Start time of the report (default: start of the archives)
+    #[arg(long, value_name = "time", value_parser = parse_time)]
+    pub start: Option<DateTime<Local>>,
+    /// End time of the report (default: end of the archives)
+    #[arg(long, value_name = "time", value_parser = parse_time)]
+    pub end: Option<DateTime<Local>>,
+    /// Image format
+    #[arg(long, value_name = "format", value_enum, default_value_t = PlotFormat::Png)]
+    pub format: PlotFormat,
+    /// Image scale
+    #[arg(long, value_name = "scale", default_value = "1")]
+    pub scale: f64,
+    /// Candidate archives to compare with the archives as baseline
+    #[arg(long, value_name = "candidate archives", num_args(1..))]
+    pub compare: Option<Vec<String>>,
+    /// Start time of the candidate to compare with the start to end time as baseline
+    #[arg(long, value_name = "time", value_parser = parse_time)]
+    pub candidate_start: Option<DateTime<Local>>,
+    /// End time of the candidate (default: end of the (candidate) archives)
+    #[arg(long, value_name = "time", value_parser = parse_time)]
+    pub candidate_end: Option<DateTime<Local>>,
+    /// Allow reading archives of different hosts together
+    #[arg(long, value_name = "allow mixed hosts")]
+    pub allow_mixed_hosts: bool,
+}
+
+/// Parse a time as rfc3339, or as local time in the format "YYYY-MM-DD HH:MM:SS" or
+/// "YYYY-MM-DDTHH:MM:SS".
+pub fn parse_time(time: &str) -> Result<DateTime<Local>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Ok(time.with_timezone(&Local));
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(time, format).ok())
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+        .ok_or(format!(
+            "{} is not a valid time, use YYYY-MM-DD HH:MM:SS or rfc3339",
+            time
+        ))
+}
+
+/// The shortest interval: the proc and sys files are read in a few milliseconds.
+static MIN_INTERVAL: f64 = 0.01;
+
+/// Check that an interval is a number of seconds of at least the shortest interval.
+fn check_interval(interval: f64) -> Result<f64, String> {
+    if interval.is_finite() && interval >= MIN_INTERVAL {
+        Ok(interval)
+    } else {
+        Err(format!(
+            "{} is not a valid interval, the shortest interval is {}s",
+            interval, MIN_INTERVAL
+        ))
+    }
+}
+
+/// Parse an interval in seconds, which can be a fraction of a second.
+pub fn parse_interval(interval: &str) -> Result<f64, String> {
+    interval
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("{} is not a valid interval", interval))
+        .and_then(check_interval)
+}
+
+/// Parse the interval of a collector as collector=time (s), such as "xfs=60".
+pub fn parse_collector_interval(setting: &str) -> Result<(Collector, f64), String> {
+    let (collector, interval) = setting
+        .split_once('=')
+        .ok_or(format!("{} is not a collector=time (s) setting", setting))?;
+    let collector = Collector::from_str(collector.trim(), true)?;
+    Ok((collector, parse_interval(interval)?))
+}
+
+/// The CLI output shows the timestamps with milliseconds if the interval is under one second,
+/// or if the archives that are replayed are.
+static SUBSECOND_TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+
+/// Show the timestamps with milliseconds if the interval of replayed archives is under a second.
+pub fn set_subsecond_timestamps(interval: f64) {
+    SUBSECOND_TIMESTAMPS.store(interval < 1_f64, Ordering::Relaxed);
+}
+
+/// The timestamps are shown with milliseconds.
+fn subsecond_timestamps() -> bool {
+    SUBSECOND_TIMESTAMPS.load(Ordering::Relaxed) || ARGS.interval < 1_f64
+}
+
+/// The format of the timestamps of the CLI output.
+pub fn timestamp_format() -> &'static str {
+    timestamp_format_of(subsecond_timestamps())
 }
-static DATA: Lazy<Data> = Lazy::new(|| Data::new(Opts::parse().history));
 
-pub static ARGS: Lazy<Opts> = Lazy::new(Opts::parse);
+/// The format of the timestamps, with or without milliseconds.
+fn timestamp_format_of(subsecond: bool) -> &'static str {
+    if subsecond {
+        "%H:%M:%S%.3f"
+    } else {
+        "%H:%M:%S"
+    }
+}
+
+/// The header of the timestamp column of the CLI output, as wide as the timestamps.
+pub fn timestamp_header(header: &str) -> String {
+    if subsecond_timestamps() {
+        format!("{:12}", header)
+    } else {
+        header.to_string()
+    }
+}
+
+impl Opts {
+    /// The options of the command line, with the settings of the configuration file for the
+    /// options that are not set on the command line.
+    pub fn load() -> anyhow::Result<Opts> {
+        let matches = Opts::command().get_matches();
+        let mut opts = Opts::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+        if let Some(config) = &opts.config {
+            Config::from_file(config)?.apply(&mut opts, &matches);
+            check_interval(opts.interval).map_err(anyhow::Error::msg)?;
+            for (_, interval) in &opts.collector_interval {
+                check_interval(*interval).map_err(anyhow::Error::msg)?;
+            }
+        }
+        // the archiver writes the rows of its interval from the history.
+        if opts.archiver && (opts.history as i64) < opts.archiver_interval * 60 {
+            anyhow::bail!(
+                "The history of {} seconds is shorter than the archiver interval of {} minutes.",
+                opts.history,
+                opts.archiver_interval
+            );
+        }
+        Ok(opts)
+    }
+}
+
+static DATA: Lazy<Histories> = Lazy::new(|| Histories::new(&ROLLUP_LEVELS));
+
+pub static ARGS: Lazy<Opts> = Lazy::new(|| {
+    Opts::load().unwrap_or_else(|error| {
+        eprintln!("Error: {:#}", error);
+        std::process::exit(1);
+    })
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collector_intervals_are_parsed() {
+        assert_eq!(
+            parse_collector_interval("xfs=60"),
+            Ok((Collector::Xfs, 60_f64))
+        );
+        assert_eq!(
+            parse_collector_interval(" mem = 0.5 "),
+            Ok((Collector::Memory, 0.5))
+        );
+        assert!(parse_collector_interval("nfs=60").is_err());
+        assert!(parse_collector_interval("xfs").is_err());
+        assert!(parse_collector_interval("xfs=fast").is_err());
+        assert!(parse_collector_interval("xfs=0").is_err());
+    }
+
+    #[test]
+    fn sub_second_intervals_are_parsed_down_to_the_shortest_interval() {
+        assert_eq!(parse_interval("0.5"), Ok(0.5));
+        assert_eq!(parse_interval(" 2 "), Ok(2_f64));
+        assert_eq!(parse_interval("0.01"), Ok(MIN_INTERVAL));
+        assert!(parse_interval("0.001").is_err());
+        assert!(parse_interval("0").is_err());
+        assert!(parse_interval("-1").is_err());
+        assert!(parse_interval("NaN").is_err());
+        assert!(parse_interval("inf").is_err());
+        assert!(parse_interval("1s").is_err());
+    }
+
+    #[test]
+    fn sub_second_timestamps_have_milliseconds() {
+        use chrono::TimeZone;
+
+        let timestamp = Local.with_ymd_and_hms(2024, 10, 20, 12, 0, 1).unwrap()
+            + chrono::TimeDelta::milliseconds(250);
+        assert_eq!(
+            timestamp.format(timestamp_format_of(true)).to_string(),
+            "12:00:01.250"
+        );
+        assert_eq!(
+            timestamp.format(timestamp_format_of(false)).to_string(),
+            "12:00:01"
+        );
+    }
+
+    #[test]
+    fn outputs_need_the_rows_of_their_collectors() {
+        use processor::meminfo::MemInfo;
+        use processor::vmstat::VmStatInfo;
+
+        let data = Data::new(&[]);
+        data.vmstat
+            .write()
+            .unwrap()
+            .push_back(VmStatInfo::default());
+        assert!(OutputOptions::SarB.check_rows(&data).is_ok());
+        assert!(OutputOptions::SarW.check_rows(&data).is_ok());
+        let error = OutputOptions::Free.check_rows(&data).unwrap_err();
+        assert!(error.to_string().contains("memory"));
+        data.memory.write().unwrap().push_back(MemInfo::default());
+        assert!(OutputOptions::Free.check_rows(&data).is_ok());
+        assert!(OutputOptions::Iostat.check_rows(&data).is_err());
+    }
+}
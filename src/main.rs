@@ -12,9 +12,13 @@ use tokio::time::Instant;
 static ALLOC: dhat::Alloc = dhat::Alloc;
 
 use procstat::app::app;
-use procstat::archiver::{archive, archiver, reader};
+use procstat::archiver::{archive, archiver, convert, reader};
+use procstat::config::Config;
+use procstat::federation::federator;
+use procstat::replay::replay;
+use procstat::report::report;
 use procstat::webserver::webserver;
-use procstat::ARGS;
+use procstat::{Command, ARGS};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -25,6 +29,31 @@ async fn main() -> Result<()> {
     info!("Start procstat");
     let timer = Instant::now();
 
+    if ARGS.print_config {
+        print!("{}", Config::from_opts(&ARGS).to_toml()?);
+        return Ok(());
+    }
+
+    // the report subcommand only reads archives and writes the report.
+    if let Some(Command::Report(report_opts)) = &ARGS.command {
+        report(report_opts)?;
+        info!("End procstat, total time: {:?}", timer.elapsed());
+        return Ok(());
+    }
+    // the replay subcommand only reads archives and prints them.
+    if let Some(Command::Replay(replay_opts)) = &ARGS.command {
+        replay(replay_opts).await?;
+        info!("End procstat, total time: {:?}", timer.elapsed());
+        return Ok(());
+    }
+
+    // the convert subcommand only converts archives.
+    if let Some(Command::Convert(convert_opts)) = &ARGS.command {
+        convert(convert_opts)?;
+        info!("End procstat, total time: {:?}", timer.elapsed());
+        return Ok(());
+    }
+
     // spawn the ctrlc thead
     ctrlc::set_handler(move || {
         println!("SIGINT received, terminating.");
@@ -49,8 +78,11 @@ async fn main() -> Result<()> {
     .unwrap();
 
     // spawn the webserver
-    if ARGS.webserver || ARGS.read.is_some() {
-        println!("Webserver is started at port: {}", ARGS.webserver_port);
+    if ARGS.webserver || ARGS.read.is_some() || ARGS.federate.is_some() {
+        println!(
+            "Webserver is started at: {}:{}",
+            ARGS.webserver_bind, ARGS.webserver_port
+        );
         tokio::spawn(async move {
             webserver().await;
         });
@@ -68,6 +100,12 @@ async fn main() -> Result<()> {
         });
     };
 
+    // federator function.
+    // execution loops in the federator if called.
+    if ARGS.federate.is_some() {
+        federator().await?;
+    }
+
     // reader function.
     // execution loops in the reader if called.
     if ARGS.read.is_some() {
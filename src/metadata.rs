@@ -0,0 +1,149 @@
+//! The metadata of an archive: the host that produced the statistics, its kernel and topology,
+//! and the procstat settings that were used, so that an archive can be identified on its own.
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::read_to_string;
+
+use crate::ARGS;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ArchiveMetadata {
+    pub hostname: String,
+    /// the kernel name, release and version, and the machine, as shown by uname.
+    pub kernel_name: String,
+    pub kernel_release: String,
+    pub kernel_version: String,
+    pub machine: String,
+    pub boot_time: Option<DateTime<Local>>,
+    pub cpu_model: String,
+    pub cpu_count: usize,
+    /// bytes.
+    pub memtotal: f64,
+    pub blockdevices: Vec<String>,
+    pub networkdevices: Vec<String>,
+    /// seconds; an integer in archives of older versions.
+    pub interval: f64,
+    /// minutes.
+    pub archiver_interval: i64,
+    pub disk_filter: String,
+    pub procstat_version: String,
+}
+
+/// The first line of a file, or an empty string if the file can't be read.
+fn first_line(file: &str) -> String {
+    read_to_string(file)
+        .unwrap_or_default()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// The value after the first line of the file that starts with the key, split by the separator.
+fn value_of_key(contents: &str, key: &str, separator: char) -> Option<String> {
+    contents
+        .lines()
+        .find(|line| line.starts_with(key))
+        .and_then(|line| line.split_once(separator))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+impl ArchiveMetadata {
+    /// The metadata of the current host with the device names of the statistics. The files that
+    /// can't be read leave their fields empty: the metadata never makes collection fail.
+    pub fn current<'a>(
+        blockdevices: impl Iterator<Item = &'a String>,
+        networkdevices: impl Iterator<Item = &'a String>,
+    ) -> ArchiveMetadata {
+        let cpuinfo = read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let stat = read_to_string("/proc/stat").unwrap_or_default();
+        let meminfo = read_to_string("/proc/meminfo").unwrap_or_default();
+        ArchiveMetadata {
+            hostname: first_line("/proc/sys/kernel/hostname"),
+            kernel_name: first_line("/proc/sys/kernel/ostype"),
+            kernel_release: first_line("/proc/sys/kernel/osrelease"),
+            kernel_version: first_line("/proc/sys/kernel/version"),
+            machine: Some(first_line("/proc/sys/kernel/arch"))
+                .filter(|machine| !machine.is_empty())
+                .unwrap_or_else(|| std::env::consts::ARCH.to_string()),
+            boot_time: value_of_key(&stat, "btime ", ' ')
+                .and_then(|seconds| seconds.parse::<i64>().ok())
+                .and_then(|seconds| Local.timestamp_opt(seconds, 0).single()),
+            cpu_model: value_of_key(&cpuinfo, "model name", ':').unwrap_or_default(),
+            cpu_count: cpuinfo
+                .lines()
+                .filter(|line| line.starts_with("processor"))
+                .count(),
+            memtotal: value_of_key(&meminfo, "MemTotal:", ':')
+                .and_then(|kilobytes| kilobytes.trim_end_matches(" kB").parse::<f64>().ok())
+                .map(|kilobytes| kilobytes * 1024_f64)
+                .unwrap_or_default(),
+            blockdevices: blockdevices
+                .filter(|device| !device.starts_with("TOTAL"))
+                .cloned()
+                .collect::<BTreeSet<String>>()
+                .into_iter()
+                .collect(),
+            networkdevices: networkdevices
+                .filter(|device| !device.starts_with("TOTAL"))
+                .cloned()
+                .collect::<BTreeSet<String>>()
+                .into_iter()
+                .collect(),
+            interval: ARGS.interval,
+            archiver_interval: ARGS.archiver_interval,
+            disk_filter: ARGS.disk_filter.clone(),
+            procstat_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// A one line description of the host, such as for a page header.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} | {} {} {} | booted {} | {} x {} | {:.1} GiB | interval {}s | disk filter '{}' | procstat {}",
+            self.hostname,
+            self.kernel_name,
+            self.kernel_release,
+            self.machine,
+            self.boot_time
+                .map(|boot_time| boot_time.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+            self.cpu_count,
+            self.cpu_model,
+            self.memtotal / (1024_f64 * 1024_f64 * 1024_f64),
+            self.interval,
+            self.disk_filter,
+            self.procstat_version,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_of_older_archives_is_read_with_defaults() {
+        // the metadata of archives written before the sub-second intervals has an integer
+        // interval, and the fields a version doesn't write are read as their defaults. Archives
+        // written before the metadata was added have none, and read as None.
+        let metadata: ArchiveMetadata =
+            serde_json::from_str(r#"{"hostname": "db01", "interval": 5}"#).unwrap();
+        assert_eq!(metadata.hostname, "db01");
+        assert_eq!(metadata.interval, 5_f64);
+        assert!(metadata.kernel_release.is_empty());
+        assert!(metadata.boot_time.is_none());
+        assert!(metadata.summary().starts_with("db01 | "));
+
+        let cpuinfo = "processor\t: 0\nmodel name\t: Example CPU @ 2.00GHz\nprocessor\t: 1\n";
+        assert_eq!(
+            value_of_key(cpuinfo, "model name", ':').as_deref(),
+            Some("Example CPU @ 2.00GHz")
+        );
+        assert_eq!(value_of_key(cpuinfo, "btime ", ' '), None);
+        assert!(first_line("/nonexistent/procstat/hostname").is_empty());
+    }
+}
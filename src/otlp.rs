@@ -0,0 +1,576 @@
+//! OpenTelemetry metrics in the json encoding of OTLP/HTTP.
+//!
+//! The statistics that have a metric in the system semantic conventions are exported with the
+//! name, unit and attributes of the convention, such as `system.cpu.time` with `cpu.mode` and
+//! `cpu.logical_number`, `system.disk.io` with `system.device` and `disk.io.direction`, and
+//! `system.network.io` with `network.interface.name` and `network.io.direction`. The other
+//! statistics are exported as `<prefix>.<category>.<name>`. Counters are cumulative monotonic
+//! sums that start at the boot time, the other statistics are gauges.
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::exporter::Sample;
+
+/// The size of a sector of the statistics of the block devices.
+const SECTOR_SIZE: f64 = 512_f64;
+/// The aggregation temporality of the sums: the value is the total since the start time.
+const AGGREGATION_TEMPORALITY_CUMULATIVE: u8 = 2;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+enum AnyValue {
+    #[serde(rename = "stringValue")]
+    String(String),
+    /// int64 values are strings in the json encoding.
+    #[serde(rename = "intValue")]
+    Int(String),
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+struct KeyValue {
+    key: String,
+    value: AnyValue,
+}
+
+impl KeyValue {
+    fn string(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: AnyValue::String(value.to_string()),
+        }
+    }
+    fn int(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: AnyValue::Int(value.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NumberDataPoint {
+    attributes: Vec<KeyValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_time_unix_nano: Option<String>,
+    time_unix_nano: String,
+    as_double: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Sum {
+    data_points: Vec<NumberDataPoint>,
+    aggregation_temporality: u8,
+    is_monotonic: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Gauge {
+    data_points: Vec<NumberDataPoint>,
+}
+
+#[derive(Debug, Serialize)]
+struct Metric {
+    name: String,
+    unit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sum: Option<Sum>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gauge: Option<Gauge>,
+}
+
+#[derive(Debug, Serialize)]
+struct Scope {
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScopeMetrics {
+    scope: Scope,
+    metrics: Vec<Metric>,
+}
+
+#[derive(Debug, Serialize)]
+struct Resource {
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceMetrics {
+    resource: Resource,
+    scope_metrics: Vec<ScopeMetrics>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Sum,
+    Gauge,
+}
+
+/// A data point with the metric it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+struct Point {
+    name: String,
+    unit: &'static str,
+    kind: Kind,
+    attributes: Vec<KeyValue>,
+    value: f64,
+    timestamp: DateTime<Local>,
+}
+
+/// Whether a statistic is a counter, which only increases, or a gauge.
+fn is_counter(category: &str, name: &str) -> bool {
+    match category {
+        "stat" => !matches!(name, "processes_running" | "processes_blocked"),
+        "schedstat" | "net_dev" | "xfs" => true,
+        "blockdevice" => name.starts_with("stat_") && name != "stat_ios_in_progress",
+        "pressure" => name.ends_with("_total"),
+        "vmstat" => !name.starts_with("nr_"),
+        _ => false,
+    }
+}
+
+/// The point of the semantic conventions for the statistic, if there is one.
+fn semantic_point(sample: &Sample) -> Option<Point> {
+    let point = |name: &str, unit, kind, attributes, value| Point {
+        name: name.to_string(),
+        unit,
+        kind,
+        attributes,
+        value,
+        timestamp: sample.timestamp,
+    };
+    let device = KeyValue::string("system.device", &sample.subcategory);
+    let disk_direction = |name: &str| {
+        KeyValue::string(
+            "disk.io.direction",
+            if name.starts_with("stat_reads") {
+                "read"
+            } else {
+                "write"
+            },
+        )
+    };
+    match (sample.category.as_str(), sample.name.as_str()) {
+        // the times of all cpus are not exported as cpu time, as it would be counted twice.
+        (
+            "stat",
+            mode @ ("user" | "nice" | "system" | "idle" | "iowait" | "irq" | "softirq" | "steal"),
+        ) => {
+            let cpu_number = sample.subcategory.strip_prefix("cpu")?;
+            Some(point(
+                "system.cpu.time",
+                "s",
+                Kind::Sum,
+                vec![
+                    KeyValue::string("cpu.mode", if mode == "irq" { "interrupt" } else { mode }),
+                    KeyValue::int("cpu.logical_number", cpu_number),
+                ],
+                sample.value / 1000_f64,
+            ))
+        }
+        ("stat", "processes") => Some(point(
+            "system.processes.created",
+            "{process}",
+            Kind::Sum,
+            vec![],
+            sample.value,
+        )),
+        ("stat", status @ ("processes_running" | "processes_blocked")) => Some(point(
+            "system.processes.count",
+            "{process}",
+            Kind::Gauge,
+            vec![KeyValue::string(
+                "process.status",
+                status.trim_start_matches("processes_"),
+            )],
+            sample.value,
+        )),
+        ("blockdevice", name @ ("stat_reads_sectors" | "stat_writes_sectors")) => Some(point(
+            "system.disk.io",
+            "By",
+            Kind::Sum,
+            vec![device, disk_direction(name)],
+            sample.value * SECTOR_SIZE,
+        )),
+        (
+            "blockdevice",
+            name @ ("stat_reads_completed_success" | "stat_writes_completed_success"),
+        ) => Some(point(
+            "system.disk.operations",
+            "{operation}",
+            Kind::Sum,
+            vec![device, disk_direction(name)],
+            sample.value,
+        )),
+        ("blockdevice", name @ ("stat_reads_time_spent_ms" | "stat_writes_time_spent_ms")) => {
+            Some(point(
+                "system.disk.operation_time",
+                "s",
+                Kind::Sum,
+                vec![device, disk_direction(name)],
+                sample.value / 1000_f64,
+            ))
+        }
+        ("blockdevice", name @ ("stat_reads_merged" | "stat_writes_merged")) => Some(point(
+            "system.disk.merged",
+            "{operation}",
+            Kind::Sum,
+            vec![device, disk_direction(name)],
+            sample.value,
+        )),
+        ("blockdevice", "stat_ios_time_spent_ms") => Some(point(
+            "system.disk.io_time",
+            "s",
+            Kind::Sum,
+            vec![device],
+            sample.value / 1000_f64,
+        )),
+        ("net_dev", name) => {
+            let (direction, statistic) = name.split_once('_')?;
+            let (metric, unit) = match statistic {
+                "bytes" => ("system.network.io", "By"),
+                "packets" => ("system.network.packets", "{packet}"),
+                "errors" => ("system.network.errors", "{error}"),
+                "drop" => ("system.network.dropped", "{packet}"),
+                _ => return None,
+            };
+            Some(point(
+                metric,
+                unit,
+                Kind::Sum,
+                vec![
+                    KeyValue::string("network.interface.name", &sample.subcategory),
+                    KeyValue::string("network.io.direction", direction),
+                ],
+                sample.value,
+            ))
+        }
+        ("meminfo", "memtotal") => Some(point(
+            "system.memory.limit",
+            "By",
+            Kind::Gauge,
+            vec![],
+            sample.value,
+        )),
+        ("meminfo", state @ ("memfree" | "buffers" | "cached")) => Some(point(
+            "system.memory.usage",
+            "By",
+            Kind::Gauge,
+            vec![KeyValue::string(
+                "system.memory.state",
+                state.trim_start_matches("mem"),
+            )],
+            sample.value,
+        )),
+        ("loadavg", load @ ("load_1" | "load_5" | "load_15")) => Some(point(
+            &format!(
+                "system.cpu.load_average.{}m",
+                load.trim_start_matches("load_")
+            ),
+            "{thread}",
+            Kind::Gauge,
+            vec![],
+            sample.value,
+        )),
+        ("vmstat", name @ ("pswpin" | "pswpout" | "pgpgin" | "pgpgout")) => Some(point(
+            "system.paging.operations",
+            "{operation}",
+            Kind::Sum,
+            vec![
+                KeyValue::string(
+                    "system.paging.direction",
+                    if name.ends_with("in") { "in" } else { "out" },
+                ),
+                KeyValue::string(
+                    "system.paging.type",
+                    if name.starts_with("psw") {
+                        "major"
+                    } else {
+                        "minor"
+                    },
+                ),
+            ],
+            sample.value,
+        )),
+        ("vmstat", "pgmajfault") => Some(point(
+            "system.paging.faults",
+            "{fault}",
+            Kind::Sum,
+            vec![KeyValue::string("system.paging.type", "major")],
+            sample.value,
+        )),
+        _ => None,
+    }
+}
+
+/// The point of a statistic without a semantic convention, with the subcategory as attribute.
+fn generic_point(sample: &Sample, prefix: &str) -> Point {
+    let attributes = match (sample.category.as_str(), sample.subcategory.as_str()) {
+        (_, "") | ("stat" | "schedstat", "all") => vec![],
+        ("blockdevice", device) => vec![KeyValue::string("system.device", device)],
+        ("net_dev", interface) => vec![KeyValue::string("network.interface.name", interface)],
+        ("stat" | "schedstat", cpu) => match cpu.strip_prefix("cpu") {
+            Some(cpu_number) => vec![KeyValue::int("cpu.logical_number", cpu_number)],
+            None => vec![KeyValue::string("procstat.subcategory", cpu)],
+        },
+        (_, subcategory) => vec![KeyValue::string("procstat.subcategory", subcategory)],
+    };
+    Point {
+        name: format!("{}.{}.{}", prefix, sample.category, sample.name),
+        unit: "",
+        kind: if is_counter(&sample.category, &sample.name) {
+            Kind::Sum
+        } else {
+            Kind::Gauge
+        },
+        attributes,
+        value: sample.value,
+        timestamp: sample.timestamp,
+    }
+}
+
+/// The used memory of the semantic conventions, which is not a statistic of its own.
+fn used_memory_point(samples: &[Sample]) -> Option<Point> {
+    let value = |name: &str| {
+        samples
+            .iter()
+            .find(|sample| sample.category == "meminfo" && sample.name == name)
+    };
+    let memtotal = value("memtotal")?;
+    Some(Point {
+        name: "system.memory.usage".to_string(),
+        unit: "By",
+        kind: Kind::Gauge,
+        attributes: vec![KeyValue::string("system.memory.state", "used")],
+        value: memtotal.value
+            - value("memfree")?.value
+            - value("buffers")?.value
+            - value("cached")?.value,
+        timestamp: memtotal.timestamp,
+    })
+}
+
+fn nanoseconds(timestamp: DateTime<Local>) -> String {
+    timestamp
+        .timestamp_nanos_opt()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The metrics of the samples of a fetch, for the host. The sums start at the start time.
+pub fn resource_metrics(
+    samples: &[Sample],
+    prefix: &str,
+    hostname: &str,
+    start_time: DateTime<Local>,
+) -> ResourceMetrics {
+    let points = samples
+        .iter()
+        .map(|sample| semantic_point(sample).unwrap_or_else(|| generic_point(sample, prefix)))
+        .chain(used_memory_point(samples))
+        // a value that is not a number, or infinite, is not valid json.
+        .filter(|point| point.value.is_finite());
+    // the points are grouped by metric, as a metric can occur only once.
+    let mut metrics: BTreeMap<String, (&'static str, Kind, Vec<NumberDataPoint>)> = BTreeMap::new();
+    for point in points {
+        let (_, _, data_points) =
+            metrics
+                .entry(point.name)
+                .or_insert((point.unit, point.kind, Vec::new()));
+        data_points.push(NumberDataPoint {
+            attributes: point.attributes,
+            start_time_unix_nano: (point.kind == Kind::Sum).then(|| nanoseconds(start_time)),
+            time_unix_nano: nanoseconds(point.timestamp),
+            as_double: point.value,
+        });
+    }
+    ResourceMetrics {
+        resource: Resource {
+            attributes: vec![
+                KeyValue::string("host.name", hostname),
+                KeyValue::string("os.type", "linux"),
+                KeyValue::string("service.name", "procstat"),
+                KeyValue::string("service.version", env!("CARGO_PKG_VERSION")),
+            ],
+        },
+        scope_metrics: vec![ScopeMetrics {
+            scope: Scope {
+                name: "procstat".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            metrics: metrics
+                .into_iter()
+                .map(|(name, (unit, kind, data_points))| {
+                    let (sum, gauge) = match kind {
+                        Kind::Sum => (
+                            Some(Sum {
+                                data_points,
+                                aggregation_temporality: AGGREGATION_TEMPORALITY_CUMULATIVE,
+                                is_monotonic: true,
+                            }),
+                            None,
+                        ),
+                        Kind::Gauge => (None, Some(Gauge { data_points })),
+                    };
+                    Metric {
+                        name,
+                        unit: unit.to_string(),
+                        sum,
+                        gauge,
+                    }
+                })
+                .collect(),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporter::Exporter;
+    use serde_json::Value;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn sample(category: &str, subcategory: &str, name: &str, value: f64) -> Sample {
+        Sample {
+            category: category.to_string(),
+            subcategory: subcategory.to_string(),
+            name: name.to_string(),
+            value,
+            per_second: 0_f64,
+            timestamp: DateTime::parse_from_rfc3339("2023-12-13T15:20:24+00:00")
+                .unwrap()
+                .into(),
+        }
+    }
+
+    fn test_samples() -> Vec<Sample> {
+        vec![
+            sample("stat", "all", "user", 9000_f64),
+            sample("stat", "cpu1", "irq", 1500_f64),
+            sample("blockdevice", "sda", "stat_writes_sectors", 8_f64),
+            sample("net_dev", "eth0", "receive_bytes", 100_f64),
+            sample("meminfo", "", "memtotal", 1000_f64),
+            sample("meminfo", "", "memfree", 500_f64),
+            sample("meminfo", "", "buffers", 100_f64),
+            sample("meminfo", "", "cached", 200_f64),
+            sample("vmstat", "", "nr_dirty", 10_f64),
+        ]
+    }
+
+    #[test]
+    fn statistics_are_mapped_to_semantic_conventions() {
+        let start_time = DateTime::parse_from_rfc3339("2023-12-13T00:00:00+00:00")
+            .unwrap()
+            .into();
+        let metrics = serde_json::to_value(resource_metrics(
+            &test_samples(),
+            "procstat",
+            "db01",
+            start_time,
+        ))
+        .unwrap();
+        let metrics = &metrics["scopeMetrics"][0]["metrics"];
+        let metric = |name: &str| {
+            metrics
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|metric| metric["name"] == name)
+                .unwrap()
+                .clone()
+        };
+        // the time of all cpus is not exported as the cpu time.
+        let cpu_time = metric("system.cpu.time");
+        assert_eq!(cpu_time["unit"], "s");
+        assert_eq!(cpu_time["sum"]["isMonotonic"], true);
+        assert_eq!(cpu_time["sum"]["dataPoints"].as_array().unwrap().len(), 1);
+        let point = &cpu_time["sum"]["dataPoints"][0];
+        assert_eq!(point["asDouble"], 1.5);
+        assert_eq!(point["startTimeUnixNano"], "1702425600000000000");
+        assert_eq!(point["attributes"][0]["value"]["stringValue"], "interrupt");
+        assert_eq!(point["attributes"][1]["value"]["intValue"], "1");
+        assert_eq!(
+            metric("system.disk.io")["sum"]["dataPoints"][0]["asDouble"],
+            4096.0
+        );
+        assert_eq!(
+            metric("system.network.io")["sum"]["dataPoints"][0]["attributes"][1]["value"]
+                ["stringValue"],
+            "receive"
+        );
+        let usage = metric("system.memory.usage");
+        assert_eq!(usage["gauge"]["dataPoints"].as_array().unwrap().len(), 4);
+        assert_eq!(usage["gauge"]["dataPoints"][3]["asDouble"], 200.0);
+        assert!(metric("procstat.vmstat.nr_dirty")["gauge"].is_object());
+        assert!(metric("procstat.stat.user")["sum"].is_object());
+    }
+
+    #[test]
+    fn values_that_are_not_finite_are_skipped() {
+        let samples = vec![
+            sample("vmstat", "", "nr_dirty", f64::NAN),
+            sample("vmstat", "", "nr_writeback", f64::INFINITY),
+            sample("vmstat", "", "nr_free_pages", 10_f64),
+        ];
+        let metrics =
+            serde_json::to_value(resource_metrics(&samples, "procstat", "db01", Local::now()))
+                .unwrap();
+        let metrics = metrics["scopeMetrics"][0]["metrics"].as_array().unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0]["name"], "procstat.vmstat.nr_free_pages");
+    }
+
+    #[tokio::test]
+    async fn metrics_are_posted_to_a_collector() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("otlp-http://{}", listener.local_addr().unwrap());
+        let mut exporter = Exporter::new(&url, "procstat", "db01", 1024 * 1024).unwrap();
+        exporter.add(&test_samples());
+        exporter.add(&test_samples());
+        let (flushed, request) = tokio::join!(exporter.flush(), async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0_u8; 4096];
+            // the request is complete when the body has the length of the content length.
+            loop {
+                let length = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..length]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((header, body)) = text.split_once("\r\n\r\n") {
+                    let content_length = header
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Content-Length: "))
+                        .unwrap()
+                        .parse::<usize>()
+                        .unwrap();
+                    if body.len() >= content_length {
+                        break;
+                    }
+                }
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        flushed.unwrap();
+        assert!(request.starts_with("POST /v1/metrics HTTP/1.1\r\n"));
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["resourceMetrics"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            body["resourceMetrics"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+            "db01"
+        );
+        assert_eq!(exporter.queued(), 0);
+    }
+}
@@ -1,17 +1,26 @@
 use crate::processor::{
-    single_statistic_option_u64, single_statistic_u64, ProcData, ProcessorError, Statistic,
+    push_to_history, single_statistic_option_u64, single_statistic_u64, ProcData, ProcessorError,
+    Statistic,
 };
 use crate::Data;
 use crate::ARGS;
 use crate::DATA;
+use crate::{timestamp_format, timestamp_header};
 use anyhow::Result;
 use chrono::{DateTime, Local};
 use log::debug;
-use proc_sys_parser::block::{Builder, SysBlock};
+use once_cell::sync::Lazy;
+use proc_sys_parser::block::{BlockDevice, Builder, SysBlock};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct BlockDeviceInfo {
     pub timestamp: DateTime<Local>,
     pub device_name: String,
@@ -49,15 +58,404 @@ pub struct BlockDeviceInfo {
     pub queue_read_ahead_kb: f64,
     pub queue_discard_max_hw_bytes: f64,
     pub queue_discard_max_bytes: f64,
+    /// the kernel name (dm-N) of a device-mapper device, which is shown by its dm name.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub device_kernel_name: String,
+    /// the devices this device is built on, such as the physical volumes of a logical volume.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub slaves: Vec<String>,
+    /// the devices built on this device.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub holders: Vec<String>,
 }
 
+/// The device-mapper name and stacking of a block device, from /sys/block/<device>.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BlockDeviceTopology {
+    pub kernel_name: String,
+    pub layer: BlockDeviceLayer,
+    pub slaves: Vec<String>,
+    pub holders: Vec<String>,
+}
+
+/// The layer of a block device. The IO of a device in a layer above the physical layer is
+/// also counted in the devices below it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlockDeviceLayer {
+    #[default]
+    Physical,
+    Partition,
+    DeviceMapper,
+    Md,
+}
+
+impl BlockDeviceLayer {
+    /// The name of the total device of the layer. Partitions are part of a disk, and therefore
+    /// do not have a total.
+    pub fn total_name(&self) -> Option<&'static str> {
+        match self {
+            BlockDeviceLayer::Physical => Some("TOTAL"),
+            BlockDeviceLayer::DeviceMapper => Some("TOTAL-dm"),
+            BlockDeviceLayer::Md => Some("TOTAL-md"),
+            BlockDeviceLayer::Partition => None,
+        }
+    }
+}
+
+/// The TOTAL device, or a per layer total device.
+pub fn is_total(device_name: &str) -> bool {
+    device_name == "TOTAL" || device_name.starts_with("TOTAL-")
+}
+
+/// The topology of the block devices by device name, read again when the devices change.
+static TOPOLOGY: Lazy<RwLock<HashMap<String, BlockDeviceTopology>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+/// The (kernel) names of the devices of which the topology is read.
+static TOPOLOGY_DEVICES: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+/// The disk filter, which is matched with the kernel name by the parser, and here with the dm
+/// name, which is the name that is shown.
+static DISK_FILTER: Lazy<Option<Regex>> = Lazy::new(|| {
+    (!ARGS.disk_filter.is_empty())
+        .then(|| Regex::new(&ARGS.disk_filter).ok())
+        .flatten()
+});
+
 pub async fn read_blockdevice_sys_data() -> Result<SysBlock> {
     //let sys_block = proc_sys_parser::block::read()?;
-    let sys_block = Builder::new().regex(&ARGS.disk_filter).read()?;
+    let mut sys_block = Builder::new().regex(&ARGS.disk_filter).read()?;
+    if ARGS.partitions {
+        add_partitions("/sys/block", &mut sys_block)?;
+    }
+    // reading the topology reads a number of files for every device, so it is only read again
+    // when a device is added or removed.
+    let mut devices = directory_names("/sys/block");
+    devices.extend(
+        sys_block
+            .block_devices
+            .iter()
+            .map(|disk| disk.device_name.clone()),
+    );
+    devices.sort();
+    devices.dedup();
+    if *TOPOLOGY_DEVICES.read().unwrap() != devices {
+        *TOPOLOGY.write().unwrap() = read_blockdevice_topology("/sys/block", ARGS.partitions);
+        *TOPOLOGY_DEVICES.write().unwrap() = devices;
+    }
+    // device-mapper devices are shown with their dm name, like iostat -N.
+    let topology = TOPOLOGY.read().unwrap();
+    for disk in sys_block.block_devices.iter_mut() {
+        if let Some((device_name, _)) = topology
+            .iter()
+            .find(|(_, device)| device.kernel_name == disk.device_name)
+        {
+            disk.device_name = device_name.to_string();
+        }
+    }
+    if let Some(filter) = DISK_FILTER.as_ref() {
+        sys_block
+            .block_devices
+            .retain(|disk| !filter.is_match(&disk.device_name));
+    }
     debug!("{:?}", sys_block);
     Ok(sys_block)
 }
 
+fn directory_names(directory: &str) -> Vec<String> {
+    read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The partitions of a disk are the subdirectories of the disk with a partition file.
+fn partition_names(sys_block: &str, disk: &str) -> Vec<String> {
+    directory_names(&format!("{}/{}", sys_block, disk))
+        .into_iter()
+        .filter(|name| {
+            name.starts_with(disk)
+                && Path::new(&format!("{}/{}/{}/partition", sys_block, disk, name)).exists()
+        })
+        .collect()
+}
+
+/// Add the partitions of the disks with the statistics from /proc/diskstats, like iostat -p.
+/// A partition has the queue settings of its disk.
+fn add_partitions(sys_block: &str, block_devices: &mut SysBlock) -> Result<()> {
+    let proc_diskstats = proc_sys_parser::diskstats::read()?;
+    let mut partitions = Vec::new();
+    for disk in &block_devices.block_devices {
+        for partition in partition_names(sys_block, &disk.device_name) {
+            let Some(diskstats) = proc_diskstats
+                .disk_stats
+                .iter()
+                .find(|diskstats| diskstats.device_name == partition)
+            else {
+                continue;
+            };
+            let inflight = read_to_string(format!(
+                "{}/{}/{}/inflight",
+                sys_block, disk.device_name, partition
+            ))
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|value| value.parse::<u64>().unwrap_or_default())
+            .collect::<Vec<u64>>();
+            partitions.push(BlockDevice {
+                device_name: partition,
+                dev_block_major: diskstats.block_major,
+                dev_block_minor: diskstats.block_minor,
+                inflight_reads: inflight.first().copied().unwrap_or_default(),
+                inflight_writes: inflight.get(1).copied().unwrap_or_default(),
+                removable: disk.removable,
+                ro: disk.ro,
+                queue_max_hw_sectors_kb: disk.queue_max_hw_sectors_kb,
+                queue_max_sectors_kb: disk.queue_max_sectors_kb,
+                queue_nr_requests: disk.queue_nr_requests,
+                queue_rotational: disk.queue_rotational,
+                queue_dax: disk.queue_dax,
+                queue_hw_sector_size: disk.queue_hw_sector_size,
+                queue_logical_block_size: disk.queue_logical_block_size,
+                queue_nomerges: disk.queue_nomerges,
+                queue_physical_block_size: disk.queue_physical_block_size,
+                queue_read_ahead_kb: disk.queue_read_ahead_kb,
+                queue_discard_max_bytes: disk.queue_discard_max_bytes,
+                queue_discard_max_hw_bytes: disk.queue_discard_max_hw_bytes,
+                stat_reads_completed_success: diskstats.reads_completed_success,
+                stat_reads_merged: diskstats.reads_merged,
+                stat_reads_sectors: diskstats.reads_sectors,
+                stat_reads_time_spent_ms: diskstats.reads_time_spent_ms,
+                stat_writes_completed_success: diskstats.writes_completed_success,
+                stat_writes_merged: diskstats.writes_merged,
+                stat_writes_sectors: diskstats.writes_sectors,
+                stat_writes_time_spent_ms: diskstats.writes_time_spent_ms,
+                stat_ios_in_progress: diskstats.ios_in_progress,
+                stat_ios_time_spent_ms: diskstats.ios_time_spent_ms,
+                stat_ios_weighted_time_spent_ms: diskstats.ios_weighted_time_spent_ms,
+                stat_discards_completed_success: diskstats.discards_completed_success,
+                stat_discards_merged: diskstats.discards_merged,
+                stat_discards_sectors: diskstats.discards_sectors,
+                stat_discards_time_spent_ms: diskstats.discards_time_spent_ms,
+                stat_flush_requests_completed_success: diskstats.flush_requests_completed_success,
+                stat_flush_requests_time_spent_ms: diskstats.flush_requests_time_spent_ms,
+                ..Default::default()
+            });
+        }
+    }
+    block_devices.block_devices.append(&mut partitions);
+    Ok(())
+}
+
+/// Read the dm name, the layer and the slaves and holders of all devices in /sys/block.
+/// The devices are named by their dm name if they have one. If partitions are collected, the
+/// partitions are added, otherwise partitions in the slaves are shown as the disk they are part
+/// of.
+pub fn read_blockdevice_topology(
+    sys_block: &str,
+    partitions: bool,
+) -> HashMap<String, BlockDeviceTopology> {
+    let kernel_names = directory_names(sys_block);
+    let names: HashMap<String, String> = kernel_names
+        .iter()
+        .map(|kernel_name| {
+            let name = read_to_string(format!("{}/{}/dm/name", sys_block, kernel_name))
+                .map(|name| name.trim().to_string())
+                .unwrap_or_default();
+            let name = if name.is_empty() {
+                kernel_name.to_string()
+            } else {
+                name
+            };
+            (kernel_name.to_string(), name)
+        })
+        .collect();
+    let disk_of_partition: HashMap<String, String> = kernel_names
+        .iter()
+        .flat_map(|kernel_name| {
+            partition_names(sys_block, kernel_name)
+                .into_iter()
+                .map(|partition| (partition, kernel_name.to_string()))
+        })
+        .collect();
+    let device_name = |kernel_name: String| -> String {
+        if let Some(name) = names.get(&kernel_name) {
+            name.to_string()
+        } else if let (false, Some(disk)) = (partitions, disk_of_partition.get(&kernel_name)) {
+            names[disk].to_string()
+        } else {
+            kernel_name
+        }
+    };
+    let device_names = |directory: String| -> Vec<String> {
+        let mut devices = directory_names(&directory)
+            .into_iter()
+            .map(device_name)
+            .collect::<Vec<String>>();
+        devices.sort();
+        devices.dedup();
+        devices
+    };
+    let mut topology: HashMap<String, BlockDeviceTopology> = kernel_names
+        .iter()
+        .map(|kernel_name| {
+            let layer = if Path::new(&format!("{}/{}/dm", sys_block, kernel_name)).exists() {
+                BlockDeviceLayer::DeviceMapper
+            } else if Path::new(&format!("{}/{}/md", sys_block, kernel_name)).exists() {
+                BlockDeviceLayer::Md
+            } else {
+                BlockDeviceLayer::Physical
+            };
+            let mut holders = device_names(format!("{}/{}/holders", sys_block, kernel_name));
+            // without partitions, the devices built on a partition are shown as built on the
+            // disk.
+            if !partitions {
+                for partition in partition_names(sys_block, kernel_name) {
+                    holders.append(&mut device_names(format!(
+                        "{}/{}/{}/holders",
+                        sys_block, kernel_name, partition
+                    )));
+                }
+                holders.sort();
+                holders.dedup();
+            }
+            let name = names[kernel_name].clone();
+            (
+                name.clone(),
+                BlockDeviceTopology {
+                    kernel_name: if &name == kernel_name {
+                        String::new()
+                    } else {
+                        kernel_name.to_string()
+                    },
+                    layer,
+                    slaves: device_names(format!("{}/{}/slaves", sys_block, kernel_name)),
+                    holders,
+                },
+            )
+        })
+        .collect();
+    if partitions {
+        for (partition, disk) in &disk_of_partition {
+            topology.insert(
+                partition.to_string(),
+                BlockDeviceTopology {
+                    layer: BlockDeviceLayer::Partition,
+                    holders: device_names(format!("{}/{}/{}/holders", sys_block, disk, partition)),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    topology
+}
+
+/// The topology of a device as currently known.
+pub fn blockdevice_topology(device_name: &str) -> BlockDeviceTopology {
+    TOPOLOGY
+        .read()
+        .unwrap()
+        .get(device_name)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Set the topology from the devices in the history, for printing archived data. The layer is
+/// not archived: it is taken from the kernel name (dm-N) and the device name (mdN, or the name
+/// of another device with a partition number).
+pub fn set_blockdevice_topology_from_history(devices: &[&BlockDeviceInfo]) {
+    let is_partition_of = |device_name: &str, disk_name: &str| {
+        device_name
+            .strip_prefix(disk_name)
+            .map(|number| number.strip_prefix('p').unwrap_or(number))
+            .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+    };
+    let topology = devices
+        .iter()
+        .filter(|device| !is_total(&device.device_name))
+        .map(|device| {
+            let layer = if device.device_kernel_name.starts_with("dm-") {
+                BlockDeviceLayer::DeviceMapper
+            } else if device.device_name.starts_with("md") {
+                BlockDeviceLayer::Md
+            } else if devices
+                .iter()
+                .any(|disk| is_partition_of(&device.device_name, &disk.device_name))
+            {
+                BlockDeviceLayer::Partition
+            } else {
+                BlockDeviceLayer::Physical
+            };
+            (
+                device.device_name.clone(),
+                BlockDeviceTopology {
+                    kernel_name: device.device_kernel_name.clone(),
+                    layer,
+                    slaves: device.slaves.clone(),
+                    holders: device.holders.clone(),
+                },
+            )
+        })
+        .collect();
+    *TOPOLOGY.write().unwrap() = topology;
+    TOPOLOGY_DEVICES.write().unwrap().clear();
+}
+
+/// The total device that a device is added to, if any. The IO of devices built on other devices,
+/// such as logical volumes, and of partitions is done on the physical devices below it, so only
+/// the physical devices are added to the total. The layer totals add the devices of the layer
+/// that are not below another device of the same layer.
+pub(crate) fn total_name(
+    topology: &BlockDeviceTopology,
+    topology_of: impl Fn(&str) -> BlockDeviceTopology,
+    layer_totals: bool,
+) -> Option<&'static str> {
+    match topology.layer {
+        BlockDeviceLayer::Physical if topology.slaves.is_empty() => Some("TOTAL"),
+        BlockDeviceLayer::Physical => None,
+        layer if layer_totals => topology
+            .holders
+            .iter()
+            .all(|holder| topology_of(holder).layer != layer)
+            .then(|| layer.total_name())
+            .flatten(),
+        _ => None,
+    }
+}
+
+/// The devices in the order of the stacking: every device that is not built on by another
+/// device, followed by the devices it is built on, indented per level.
+fn topology_order(disk_list: &[&String]) -> Vec<(String, String)> {
+    fn add(
+        disk_name: &str,
+        level: usize,
+        disk_list: &[&String],
+        ordered: &mut Vec<(String, String)>,
+    ) {
+        ordered.push((
+            format!("{}{}", " ".repeat(level * 2), disk_name),
+            disk_name.to_string(),
+        ));
+        for slave in blockdevice_topology(disk_name).slaves {
+            if disk_list.iter().any(|disk| **disk == slave) {
+                add(&slave, level + 1, disk_list, ordered);
+            }
+        }
+    }
+    let mut ordered = Vec::new();
+    for disk_name in disk_list.iter().filter(|disk_name| {
+        !blockdevice_topology(disk_name)
+            .holders
+            .iter()
+            .any(|holder| disk_list.contains(&holder))
+    }) {
+        add(disk_name, 0, disk_list, &mut ordered);
+    }
+    ordered
+}
+
 pub async fn process_blockdevice_data(
     proc_data: &ProcData,
     statistics: &mut HashMap<(String, String, String), Statistic>,
@@ -145,8 +543,9 @@ pub async fn add_blockdevices_to_history(
         return Ok(());
     };
 
-    // this is an array where the statistics for the 'TOTAL' device are stored by simply adding the statistics to the elements in the array.
-    let mut totals = [0_f64; 20];
+    // these are arrays where the statistics for the 'TOTAL' device, and the per layer total devices,
+    // are stored by simply adding the statistics to the elements in the array.
+    let mut totals: BTreeMap<&str, [f64; 20]> = BTreeMap::from([("TOTAL", [0_f64; 20])]);
 
     let timestamp = statistics
         .get(&(
@@ -167,6 +566,7 @@ pub async fn add_blockdevices_to_history(
         .iter()
         .filter(|disk_name| !disk_name.starts_with("loop") & !disk_name.starts_with("sr"))
     {
+        let topology = blockdevice_topology(disk_name);
         // reads
         let reads_completed_success = statistics
             .get(&(
@@ -181,7 +581,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_reads_completed_success".to_string(),
             })?
             .per_second_value;
-        totals[0] += reads_completed_success;
         let reads_merged = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -195,7 +594,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_reads_merged".to_string(),
             })?
             .per_second_value;
-        totals[1] += reads_merged;
         let reads_bytes = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -210,7 +608,6 @@ pub async fn add_blockdevices_to_history(
             })?
             .per_second_value
             * 512_f64; // convert 512 bytes sector reads to bytes
-        totals[2] += reads_bytes;
         let reads_time_spent_ms = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -224,7 +621,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_reads_time_spent_ms".to_string(),
             })?
             .per_second_value;
-        totals[3] += reads_time_spent_ms;
         // writes
         let writes_completed_success = statistics
             .get(&(
@@ -239,7 +635,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_writes_completed_success".to_string(),
             })?
             .per_second_value;
-        totals[4] += writes_completed_success;
         let writes_merged = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -253,7 +648,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_writes_merged".to_string(),
             })?
             .per_second_value;
-        totals[5] += writes_merged;
         let writes_bytes = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -268,7 +662,6 @@ pub async fn add_blockdevices_to_history(
             })?
             .per_second_value
             * 512_f64; // convert 512 bytes sector writes to bytes
-        totals[6] += writes_bytes;
         let writes_time_spent_ms = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -282,7 +675,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_writes_time_spent_ms".to_string(),
             })?
             .per_second_value;
-        totals[7] += writes_time_spent_ms;
         // ios
         let ios_in_progress = statistics
             .get(&(
@@ -297,7 +689,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_ios_in_progress".to_string(),
             })?
             .per_second_value;
-        totals[8] += ios_in_progress;
         let ios_time_spent_ms = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -311,7 +702,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_ios_time_spent_ms".to_string(),
             })?
             .per_second_value;
-        totals[9] += ios_time_spent_ms;
         let ios_weighted_time_spent_ms = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -325,7 +715,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_ios_weighted_time_spent_ms".to_string(),
             })?
             .per_second_value;
-        totals[10] += ios_weighted_time_spent_ms;
         // discards
         let discards_completed_success = statistics
             .get(&(
@@ -340,7 +729,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_discards_completed_success".to_string(),
             })?
             .per_second_value;
-        totals[11] += discards_completed_success;
         let discards_merged = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -354,7 +742,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_discards_merged".to_string(),
             })?
             .per_second_value;
-        totals[12] += discards_merged;
         let discards_bytes = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -369,7 +756,6 @@ pub async fn add_blockdevices_to_history(
             })?
             .per_second_value
             * 512_f64; // convert 512 sectors discards to bytes
-        totals[13] += discards_bytes;
         let discards_time_spent_ms = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -383,7 +769,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_discards_time_spent_ms".to_string(),
             })?
             .per_second_value;
-        totals[14] += discards_time_spent_ms;
         // flushes
         let flush_requests_completed_success = statistics
             .get(&(
@@ -398,7 +783,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_flush_requests_completed_success".to_string(),
             })?
             .per_second_value;
-        totals[15] += flush_requests_completed_success;
         let flush_requests_time_spent_ms = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -412,7 +796,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "stat_flush_requests_time_spent_ms".to_string(),
             })?
             .per_second_value;
-        totals[16] += flush_requests_time_spent_ms;
         // extras
         let inflight_reads = statistics
             .get(&(
@@ -427,7 +810,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "inflight_reads".to_string(),
             })?
             .last_value;
-        totals[17] += inflight_reads;
         let inflight_writes = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -441,7 +823,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "inflight_writes".to_string(),
             })?
             .last_value;
-        totals[18] += inflight_writes;
         let queue_nr_requests = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -455,7 +836,6 @@ pub async fn add_blockdevices_to_history(
                 key3: "queue_nr_requests".to_string(),
             })?
             .last_value;
-        totals[19] += queue_nr_requests;
         let queue_max_sectors_kb = statistics
             .get(&(
                 "blockdevice".to_string(),
@@ -652,6 +1032,34 @@ pub async fn add_blockdevices_to_history(
             })?
             .last_value;
 
+        if let Some(total_name) = total_name(&topology, blockdevice_topology, ARGS.layer_totals) {
+            let totals = totals.entry(total_name).or_insert([0_f64; 20]);
+            for (total, value) in totals.iter_mut().zip([
+                reads_completed_success,
+                reads_merged,
+                reads_bytes,
+                reads_time_spent_ms,
+                writes_completed_success,
+                writes_merged,
+                writes_bytes,
+                writes_time_spent_ms,
+                ios_in_progress,
+                ios_time_spent_ms,
+                ios_weighted_time_spent_ms,
+                discards_completed_success,
+                discards_merged,
+                discards_bytes,
+                discards_time_spent_ms,
+                flush_requests_completed_success,
+                flush_requests_time_spent_ms,
+                inflight_reads,
+                inflight_writes,
+                queue_nr_requests,
+            ]) {
+                *total += value;
+            }
+        }
+
         DATA.blockdevices
             .write()
             .unwrap()
@@ -692,34 +1100,39 @@ pub async fn add_blockdevices_to_history(
                 queue_read_ahead_kb,
                 queue_discard_max_hw_bytes,
                 queue_discard_max_bytes,
+                device_kernel_name: topology.kernel_name,
+                slaves: topology.slaves,
+                holders: topology.holders,
             });
     }
-    Data::push_blockdevices(BlockDeviceInfo {
-        timestamp,
-        device_name: "TOTAL".to_string(),
-        reads_completed_success: totals[0],
-        reads_merged: totals[1],
-        reads_bytes: totals[2],
-        reads_time_spent_ms: totals[3],
-        writes_completed_success: totals[4],
-        writes_merged: totals[5],
-        writes_bytes: totals[6],
-        writes_time_spent_ms: totals[7],
-        ios_in_progress: totals[8],
-        ios_time_spent_ms: totals[9],
-        ios_weighted_time_spent_ms: totals[10],
-        discards_completed_success: totals[11],
-        discards_merged: totals[12],
-        discards_bytes: totals[13],
-        discards_time_spent_ms: totals[14],
-        flush_requests_completed_success: totals[15],
-        flush_requests_time_spent_ms: totals[16],
-        inflight_reads: totals[17],
-        inflight_writes: totals[18],
-        queue_nr_requests: totals[19],
-        ..Default::default()
-    })
-    .await;
+    for (total_name, totals) in totals {
+        Data::push_blockdevices(BlockDeviceInfo {
+            timestamp,
+            device_name: total_name.to_string(),
+            reads_completed_success: totals[0],
+            reads_merged: totals[1],
+            reads_bytes: totals[2],
+            reads_time_spent_ms: totals[3],
+            writes_completed_success: totals[4],
+            writes_merged: totals[5],
+            writes_bytes: totals[6],
+            writes_time_spent_ms: totals[7],
+            ios_in_progress: totals[8],
+            ios_time_spent_ms: totals[9],
+            ios_weighted_time_spent_ms: totals[10],
+            discards_completed_success: totals[11],
+            discards_merged: totals[12],
+            discards_bytes: totals[13],
+            discards_time_spent_ms: totals[14],
+            flush_requests_completed_success: totals[15],
+            flush_requests_time_spent_ms: totals[16],
+            inflight_reads: totals[17],
+            inflight_writes: totals[18],
+            queue_nr_requests: totals[19],
+            ..Default::default()
+        })
+        .await;
+    }
     /*
         DATA.blockdevices
             .write()
@@ -756,13 +1169,14 @@ pub async fn add_blockdevices_to_history(
 
 impl Data {
     pub async fn push_blockdevices(blockdevice: BlockDeviceInfo) {
-        while DATA.blockdevices.read().unwrap().len() >= ARGS.history {
-            DATA.blockdevices.write().unwrap().pop_front();
-        }
-        DATA.blockdevices.write().unwrap().push_back(blockdevice);
+        push_to_history(&DATA.blockdevices, blockdevice);
     }
 }
 
+static NON_ROTATIONAL_UTILIZATION_NOTE: &str = "* non-rotational device: %util near 100 does not mean saturation, %qsat (aqu-sz as % of nr_requests) estimates the saturation.";
+/// The note is printed once, after the first rows that show a non-rotational device.
+static NON_ROTATIONAL_UTILIZATION_NOTE_PRINTED: AtomicBool = AtomicBool::new(false);
+
 pub async fn print_diskstats(
     statistics: &HashMap<(String, String, String), Statistic>,
     output: &str,
@@ -781,7 +1195,15 @@ pub async fn print_diskstats(
     if output == "sar-b" && print_header {
         println!(
             "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-            "timestamp", "", "tps", "rtps", "wtps", "dtps", "bread/s", "bwrtn/s", "bdscd/s",
+            timestamp_header("timestamp"),
+            "",
+            "tps",
+            "rtps",
+            "wtps",
+            "dtps",
+            "bread/s",
+            "bwrtn/s",
+            "bdscd/s",
         );
     }
 
@@ -805,19 +1227,35 @@ pub async fn print_diskstats(
     match output {
         "sar-d" => {
             println!(
-                "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                "timestamp", "DEV", "tps", "rMB/s", "wMB/s", "areq-sz", "aqu-sz", "await",
+                "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                timestamp_header("timestamp"),
+                "DEV",
+                "tps",
+                "rMB/s",
+                "wMB/s",
+                "areq-sz",
+                "aqu-sz",
+                "await",
+                "%util",
+                "svctm",
+                "%qsat",
             );
         }
         "iostat" => {
             println!(
                 "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10}",
-                "timestamp", "Device", "tps", "MB_read/s", "MB_wrtn/s", "MB_read", "MB_wrtn",
+                timestamp_header("timestamp"),
+                "Device",
+                "tps",
+                "MB_read/s",
+                "MB_wrtn/s",
+                "MB_read",
+                "MB_wrtn",
             );
         }
         "iostat-x" => {
-            println!("{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                     "timestamp",
+            println!("{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                     timestamp_header("timestamp"),
                      "Device",
                      "r/s",
                      "w/s",
@@ -832,12 +1270,15 @@ pub async fn print_diskstats(
                      "aqu-sz",
                      "rareq-sz",
                      "wareq-sz",
+                     "svctm",
+                     "%util",
+                     "%qsat",
             );
         }
         "sar-b" => {}
         "ioq" => {
             println!("{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                 "timestamp",
+                 timestamp_header("timestamp"),
                  "Device",
                  "r/s",
                  "rMB/s",
@@ -859,7 +1300,7 @@ pub async fn print_diskstats(
         }
         "ios" => {
             println!("{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                 "timestamp",
+                 timestamp_header("timestamp"),
                  "Device",
                  "r/s",
                  "rMB/s",
@@ -902,7 +1343,14 @@ pub async fn print_diskstats(
         let mut total_writes_sectors = 0_f64;
         let mut total_discards_completed_success = 0_f64;
         let mut total_discards_sectors = 0_f64;
-        for disk_name in &disk_list {
+        // partitions and devices built on other devices would count the IO twice.
+        for disk_name in disk_list.iter().filter(|disk_name| {
+            total_name(
+                &blockdevice_topology(disk_name),
+                blockdevice_topology,
+                false,
+            ) == Some("TOTAL")
+        }) {
             total_reads_completed_success += statistics
                 .get(&(
                     "blockdevice".to_string(),
@@ -984,7 +1432,7 @@ pub async fn print_diskstats(
         }
         println!(
             "{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-            timestamp.format("%H:%M:%S"),
+            timestamp.format(timestamp_format()),
             "",
             total_reads_completed_success
                 + total_writes_completed_success
@@ -997,7 +1445,17 @@ pub async fn print_diskstats(
             total_discards_sectors,
         );
     } else {
-        for disk_name in disk_list {
+        // iostat-x shows the devices built on other devices with the devices below them.
+        let disk_list = if output == "iostat-x" {
+            topology_order(&disk_list)
+        } else {
+            disk_list
+                .iter()
+                .map(|disk_name| (disk_name.to_string(), disk_name.to_string()))
+                .collect()
+        };
+        let mut non_rotational_shown = false;
+        for (disk_label, disk_name) in disk_list {
             let timestamp = statistics
                 .get(&(
                     "blockdevice".to_string(),
@@ -1281,6 +1739,58 @@ pub async fn print_diskstats(
                     key3: "queue_max_hw_sectors_kb".to_string(),
                 })?
                 .last_value;
+            let ios_time_ms = statistics
+                .get(&(
+                    "blockdevice".to_string(),
+                    disk_name.to_string(),
+                    "stat_ios_time_spent_ms".to_string(),
+                ))
+                .ok_or(ProcessorError::UnableToFindKeyInHashMap {
+                    hashmap: "statistics".to_string(),
+                    key1: "blockdevice".to_string(),
+                    key2: disk_name.to_string(),
+                    key3: "stat_ios_time_spent_ms".to_string(),
+                })?
+                .per_second_value;
+            let queue_rotational = statistics
+                .get(&(
+                    "blockdevice".to_string(),
+                    disk_name.to_string(),
+                    "queue_rotational".to_string(),
+                ))
+                .ok_or(ProcessorError::UnableToFindKeyInHashMap {
+                    hashmap: "statistics".to_string(),
+                    key1: "blockdevice".to_string(),
+                    key2: disk_name.to_string(),
+                    key3: "queue_rotational".to_string(),
+                })?
+                .last_value;
+
+            // the time the device was busy with IO per second of time.
+            // a device that can handle requests in parallel, such as an SSD or NVMe device, can be
+            // busy all the time with a fraction of the requests it can handle, so for a
+            // non-rotational device, %util is marked with a '*', and the queue size as a
+            // percentage of nr_requests is an estimate for the saturation.
+            let utilization = (ios_time_ms / 10_f64).min(100_f64);
+            let utilization_column = if queue_rotational == 0_f64 {
+                non_rotational_shown |= output == "sar-d" || output == "iostat-x";
+                format!("{:9.2}*", utilization)
+            } else {
+                format!("{:10.2}", utilization)
+            };
+            let mut service_time = ios_time_ms
+                / (reads_completed_success + writes_completed_success + discards_completed_success);
+            service_time = if service_time.is_nan() || service_time.is_infinite() {
+                0_f64
+            } else {
+                service_time
+            };
+            let mut queue_saturation = queue_size / queue_nr_requests * 100_f64;
+            queue_saturation = if queue_saturation.is_nan() || queue_saturation.is_infinite() {
+                0_f64
+            } else {
+                queue_saturation
+            };
 
             let mut total_average_request_size =
                 (reads_bytes + writes_bytes) / (reads_completed_success + writes_completed_success);
@@ -1344,8 +1854,8 @@ pub async fn print_diskstats(
             match output {
                 "sar-d" => {
                     println!(
-                        "{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                        timestamp.format("%H:%M:%S"),
+                        "{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:>10} {:10.2} {:10.2}",
+                        timestamp.format(timestamp_format()),
                         disk_name,
                         reads_completed_success + writes_completed_success,
                         reads_bytes / (1024_f64 * 1024_f64),
@@ -1353,12 +1863,15 @@ pub async fn print_diskstats(
                         total_average_request_size / (1024_f64 * 1024_f64),
                         queue_size,
                         total_average_request_time,
+                        utilization_column,
+                        service_time,
+                        queue_saturation,
                     );
                 }
                 "iostat" => {
                     println!(
                         "{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                        timestamp.format("%H:%M:%S"),
+                        timestamp.format(timestamp_format()),
                         disk_name,
                         reads_completed_success + writes_completed_success,
                         reads_bytes / (1024_f64 * 1024_f64),
@@ -1368,9 +1881,9 @@ pub async fn print_diskstats(
                     );
                 }
                 "iostat-x" => {
-                    println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                        timestamp.format("%H:%M:%S"),
-                        disk_name,
+                    println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:>10} {:10.2}",
+                        timestamp.format(timestamp_format()),
+                        disk_label,
                         reads_completed_success,
                         writes_completed_success,
                         reads_bytes / (1024_f64 * 1024_f64),
@@ -1384,11 +1897,14 @@ pub async fn print_diskstats(
                         queue_size,
                         reads_average_request_size / (1024_f64 * 1024_f64),
                         writes_average_request_size / (1024_f64 * 1024_f64),
+                        service_time,
+                        utilization_column,
+                        queue_saturation,
                     );
                 }
                 "ioq" => {
                     println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.0} {:10.0} {:10.0}",
-                        timestamp.format("%H:%M:%S"),
+                        timestamp.format(timestamp_format()),
                         disk_name,
                         reads_completed_success,
                         reads_bytes / (1024_f64 * 1024_f64),
@@ -1410,7 +1926,7 @@ pub async fn print_diskstats(
                 }
                 "ios" => {
                     println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                        timestamp.format("%H:%M:%S"),
+                        timestamp.format(timestamp_format()),
                         disk_name,
                         reads_completed_success,
                         reads_bytes / (1024_f64 * 1024_f64),
@@ -1433,6 +1949,117 @@ pub async fn print_diskstats(
                 &_ => todo!(),
             }
         }
+        if non_rotational_shown
+            && !NON_ROTATIONAL_UTILIZATION_NOTE_PRINTED.swap(true, Ordering::Relaxed)
+        {
+            println!("{}", NON_ROTATIONAL_UTILIZATION_NOTE);
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    #[test]
+    fn read_blockdevice_topology_dm_names() {
+        let test_path = format!("/tmp/procstat_test_topology_{}", std::process::id());
+        // dm-0 (vg0-data) is built on the disks sda and sdb, dm-1 (vg1-log) on partition sdc1,
+        // and md0 on the partitions sdd1 and sde1.
+        for directory in [
+            "sda/holders/dm-0",
+            "sdb/holders/dm-0",
+            "sdc/sdc1/holders/dm-1",
+            "sdd/sdd1/holders/md0",
+            "sde/sde1/holders/md0",
+            "dm-0/dm",
+            "dm-0/slaves/sda",
+            "dm-0/slaves/sdb",
+            "dm-1/dm",
+            "dm-1/slaves/sdc1",
+            "md0/md",
+            "md0/slaves/sdd1",
+            "md0/slaves/sde1",
+        ] {
+            create_dir_all(format!("{}/{}", test_path, directory)).unwrap();
+        }
+        write(format!("{}/dm-0/dm/name", test_path), "vg0-data\n").unwrap();
+        write(format!("{}/dm-1/dm/name", test_path), "vg1-log\n").unwrap();
+        for partition in ["sdc/sdc1", "sdd/sdd1", "sde/sde1"] {
+            write(format!("{}/{}/partition", test_path, partition), "1\n").unwrap();
+        }
+
+        let topology = read_blockdevice_topology(&test_path, false);
+        let topology_with_partitions = read_blockdevice_topology(&test_path, true);
+        remove_dir_all(test_path).unwrap();
+
+        assert_eq!(
+            topology["vg0-data"],
+            BlockDeviceTopology {
+                kernel_name: "dm-0".to_string(),
+                layer: BlockDeviceLayer::DeviceMapper,
+                slaves: vec!["sda".to_string(), "sdb".to_string()],
+                holders: vec![],
+            }
+        );
+        assert_eq!(topology["vg1-log"].slaves, vec!["sdc".to_string()]);
+        assert_eq!(topology["sda"].holders, vec!["vg0-data".to_string()]);
+        assert_eq!(topology["sdc"].holders, vec!["vg1-log".to_string()]);
+        assert_eq!(topology["sdc"].layer, BlockDeviceLayer::Physical);
+        assert!(topology["sdc"].kernel_name.is_empty());
+        assert_eq!(topology["md0"].layer, BlockDeviceLayer::Md);
+        assert_eq!(
+            topology["md0"].slaves,
+            vec!["sdd".to_string(), "sde".to_string()]
+        );
+        assert!(!topology.contains_key("sdc1"));
+
+        assert_eq!(
+            topology_with_partitions["vg1-log"].slaves,
+            vec!["sdc1".to_string()]
+        );
+        assert!(topology_with_partitions["sdc"].holders.is_empty());
+        assert_eq!(
+            topology_with_partitions["sdc1"],
+            BlockDeviceTopology {
+                layer: BlockDeviceLayer::Partition,
+                holders: vec!["vg1-log".to_string()],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn layered_stack_is_counted_once_in_the_total() {
+        let test_path = format!("/tmp/procstat_test_layered_stack_{}", std::process::id());
+        // dm-0 (vg0-root) is built on the partition sda1 of the disk sda.
+        for directory in ["sda/sda1/holders/dm-0", "dm-0/dm", "dm-0/slaves/sda1"] {
+            create_dir_all(format!("{}/{}", test_path, directory)).unwrap();
+        }
+        write(format!("{}/dm-0/dm/name", test_path), "vg0-root\n").unwrap();
+        write(format!("{}/sda/sda1/partition", test_path), "1\n").unwrap();
+        let topologies = [
+            read_blockdevice_topology(&test_path, false),
+            read_blockdevice_topology(&test_path, true),
+        ];
+        remove_dir_all(test_path).unwrap();
+
+        for topology in topologies {
+            let topology_of =
+                |device_name: &str| topology.get(device_name).cloned().unwrap_or_default();
+            // every device of the stack does the same IO.
+            let mut totals: BTreeMap<&str, f64> = BTreeMap::new();
+            for device in topology.values() {
+                if let Some(total_name) = total_name(device, topology_of, true) {
+                    *totals.entry(total_name).or_default() += 10_f64;
+                }
+            }
+            assert_eq!(
+                totals,
+                BTreeMap::from([("TOTAL", 10_f64), ("TOTAL-dm", 10_f64)])
+            );
+        }
+    }
+}
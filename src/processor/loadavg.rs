@@ -1,9 +1,11 @@
 #![allow(unused_assignments)]
-use crate::processor::{single_statistic_f64, single_statistic_u64, ProcData, Statistic};
+use crate::processor::{
+    push_to_history, single_statistic_f64, single_statistic_u64, ProcData, Statistic,
+};
 use crate::Data;
-use crate::ARGS;
 use crate::DATA;
 use crate::{add_list_of_f64_data_to_statistics, add_list_of_u64_data_to_statistics};
+use crate::{timestamp_format, timestamp_header};
 use anyhow::Result;
 use chrono::{DateTime, Local};
 use log::debug;
@@ -14,6 +16,7 @@ use std::collections::HashMap;
 use super::ProcessorError;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct LoadavgInfo {
     pub timestamp: DateTime<Local>,
     pub load_1: f64,
@@ -173,10 +176,7 @@ pub async fn add_loadavg_to_history(
 
 impl Data {
     pub async fn push_loadavg(loadavg: LoadavgInfo) {
-        while DATA.loadavg.read().unwrap().len() >= ARGS.history {
-            DATA.loadavg.write().unwrap().pop_front();
-        }
-        DATA.loadavg.write().unwrap().push_back(loadavg);
+        push_to_history(&DATA.loadavg, loadavg);
     }
 }
 
@@ -190,7 +190,7 @@ pub async fn print_loadavg(
             "sar-q-LOAD" => {
                 println!(
                     "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                    "Timestamp",
+                    timestamp_header("Timestamp"),
                     "",
                     "runq-sz",
                     "plist-sz",
@@ -300,7 +300,7 @@ pub async fn print_loadavg(
         "sar-q-LOAD" => {
             println!(
                 "{:10} {:7}    {:10.0} {:10.0} {:10.2} {:10.2} {:10.2} {:10.0}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 "",
                 current_runnable,
                 total,
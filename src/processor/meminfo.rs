@@ -1,8 +1,10 @@
 use crate::add_list_of_u64_data_to_statistics;
-use crate::processor::{single_statistic_u64, ProcData, ProcessorError, Statistic};
+use crate::processor::{
+    push_to_history, single_statistic_u64, ProcData, ProcessorError, Statistic,
+};
 use crate::Data;
-use crate::ARGS;
 use crate::DATA;
+use crate::{timestamp_format, timestamp_header};
 use anyhow::Result;
 use chrono::{DateTime, Local};
 use log::debug;
@@ -11,6 +13,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct MemInfo {
     pub timestamp: DateTime<Local>,
     pub memfree: f64,
@@ -42,6 +45,9 @@ pub struct MemInfo {
     pub inactive_file: f64,
     pub committed_as: f64,
     pub commitlimit: f64,
+    /// not present in archives of older versions.
+    pub vmallocused: f64,
+    pub hugepages_surplus: f64,
 }
 
 pub async fn read_meminfo_proc_data() -> Result<ProcMemInfo> {
@@ -128,7 +134,7 @@ pub async fn print_meminfo(
         match output {
             "sar-r" => {
                 println!("{:10}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                         "Timestamp",
+                         timestamp_header("Timestamp"),
                          "mbmemfree",
                          "mbavail",
                          "mbmemused",
@@ -144,7 +150,7 @@ pub async fn print_meminfo(
             }
             "sar-r-ALL" => {
                 println!("{:10}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                         "Timestamp",
+                         timestamp_header("Timestamp"),
                          "mbmemfree",
                          "mbavail",
                          "mbmemused",
@@ -166,13 +172,23 @@ pub async fn print_meminfo(
             "sar-H" => {
                 println!(
                     "{:10}    {:>10} {:>10} {:>10} {:>10} {:>10}",
-                    "Timestamp", "mbhugfree", "mbhugused", "%hugused", "mbhugrsvd", "mbhugsurp",
+                    timestamp_header("Timestamp"),
+                    "mbhugfree",
+                    "mbhugused",
+                    "%hugused",
+                    "mbhugrsvd",
+                    "mbhugsurp",
                 );
             }
             "sar-S" => {
                 println!(
                     "{:10}    {:>10} {:>10} {:>10} {:>10} {:>10}",
-                    "Timestamp", "mbswpfree", "mbswpused", "%swpused", "mbswpcad", "%swpcad",
+                    timestamp_header("Timestamp"),
+                    "mbswpfree",
+                    "mbswpused",
+                    "%swpused",
+                    "mbswpcad",
+                    "%swpcad",
                 );
             }
             &_ => todo!(),
@@ -473,7 +489,7 @@ pub async fn print_meminfo(
         // https://github.com/sysstat/sysstat/blob/499f5b153e9707892bb8841d37e6ed3a0aa617e2/pr_stats.c#L789
         "sar-r" => {
             println!("{:10}    {:10.0} {:10.0} {:10.0} {:10.2} {:10.0} {:10.0} {:10.0} {:10.2} {:10.0} {:10.0} {:10.0}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 memfree / 1024_f64,
                 memavailable / 1024_f64,
                 (memtotal - non_used_memory) / 1024_f64,
@@ -489,7 +505,7 @@ pub async fn print_meminfo(
         }
         "sar-r-ALL" => {
             println!("{:10}    {:10.0} {:10.0} {:10.0} {:10.2} {:10.0} {:10.0} {:10.0} {:10.2} {:10.0} {:10.0} {:10.0} {:10.0} {:10.0} {:10.0} {:10.0} {:10.0}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 memfree / 1024_f64,
                 memavailable / 1024_f64,
                 (memtotal - non_used_memory) / 1024_f64,
@@ -511,7 +527,7 @@ pub async fn print_meminfo(
         "sar-H" => {
             println!(
                 "{:10}    {:10.0} {:10.0} {:10.2} {:10.0} {:10.0}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 (hugepages_free * hugepagesize) / (1024_f64 * 1024_f64),
                 ((hugepages_total - hugepages_free) * hugepagesize) / (1024_f64 * 1024_f64),
                 if hugepages_total == 0_f64 {
@@ -526,7 +542,7 @@ pub async fn print_meminfo(
         "sar-S" => {
             println!(
                 "{:10}    {:10.0} {:10.0} {:10.2} {:10.0} {:10.0}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 swap_free / 1024_f64,
                 (swap_total - swap_free) / 1024_f64,
                 if swap_total == 0_f64 {
@@ -924,6 +940,32 @@ pub async fn add_memory_to_history(
             key3: "commitlimit".to_string(),
         })?
         .last_value;
+    let vmallocused = statistics
+        .get(&(
+            "meminfo".to_string(),
+            "".to_string(),
+            "vmallocused".to_string(),
+        ))
+        .ok_or(ProcessorError::UnableToFindKeyInHashMap {
+            hashmap: "statistics".to_string(),
+            key1: "meminfo".to_string(),
+            key2: "".to_string(),
+            key3: "vmallocused".to_string(),
+        })?
+        .last_value;
+    let hugepages_surplus = statistics
+        .get(&(
+            "meminfo".to_string(),
+            "".to_string(),
+            "hugepages_surp".to_string(),
+        ))
+        .ok_or(ProcessorError::UnableToFindKeyInHashMap {
+            hashmap: "statistics".to_string(),
+            key1: "meminfo".to_string(),
+            key2: "".to_string(),
+            key3: "hugepages_surp".to_string(),
+        })?
+        .last_value;
     Data::push_memory(MemInfo {
         timestamp,
         memfree,
@@ -955,6 +997,8 @@ pub async fn add_memory_to_history(
         inactive_file,
         committed_as,
         commitlimit,
+        vmallocused,
+        hugepages_surplus,
     })
     .await;
     /*
@@ -997,9 +1041,6 @@ pub async fn add_memory_to_history(
 
 impl Data {
     pub async fn push_memory(meminfo: MemInfo) {
-        while DATA.memory.read().unwrap().len() >= ARGS.history {
-            DATA.memory.write().unwrap().pop_front();
-        }
-        DATA.memory.write().unwrap().push_back(meminfo);
+        push_to_history(&DATA.memory, meminfo);
     }
 }
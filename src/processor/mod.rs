@@ -3,11 +3,13 @@ pub mod loadavg;
 pub mod meminfo;
 pub mod net_dev;
 pub mod pressure;
+pub mod rollup;
 pub mod schedstat;
 pub mod stat;
 pub mod vmstat;
 pub mod xfs;
 
+use crate::metadata::ArchiveMetadata;
 use crate::processor::blockdevice::{
     add_blockdevices_to_history, process_blockdevice_data, read_blockdevice_sys_data,
     BlockDeviceInfo,
@@ -24,19 +26,31 @@ use crate::processor::net_dev::{
 use crate::processor::pressure::{
     add_pressure_to_history, process_pressure_data, read_pressure_proc_data, PressureInfo,
 };
-use crate::processor::schedstat::{process_schedstat_data, read_schedstat_proc_data};
+use crate::processor::rollup::{Aggregate, Rollup, RollupLevel};
+use crate::processor::schedstat::{
+    add_schedstat_to_history, process_schedstat_data, read_schedstat_proc_data, SchedStatInfo,
+};
 use crate::processor::vmstat::{
     add_vmstat_to_history, process_vmstat_data, read_vmstat_proc_data, VmStatInfo,
 };
 use crate::processor::xfs::{add_xfs_to_history, process_xfs_data, read_xfs_proc_data, XfsInfo};
-use crate::ARGS;
+use crate::{ARGS, DATA};
 use anyhow::{Context, Result};
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, TimeDelta};
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use stat::CpuStat;
-use stat::{add_cpu_total_to_history, process_stat_data, read_stat_proc_data};
+use stat::{
+    add_cpu_total_to_history, add_per_cpu_to_history, process_stat_data, read_stat_proc_data,
+};
+use stat::{CpuStat, PerCpuStat};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::ops::Deref;
 use std::sync::RwLock;
 use thiserror::Error;
 
@@ -51,9 +65,135 @@ enum ProcessorError {
     },
 }
 
+/// The collectors of the statistics, which each read a source in /proc or /sys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Collector {
+    Cpu,
+    Schedstat,
+    #[value(alias = "mem")]
+    Memory,
+    Disk,
+    #[value(alias = "net")]
+    Network,
+    Loadavg,
+    Pressure,
+    Vmstat,
+    Xfs,
+}
+
+impl Collector {
+    pub fn all() -> Vec<Collector> {
+        Collector::value_variants().to_vec()
+    }
+    /// The collector is set to run, and its source is available.
+    pub fn enabled(&self) -> bool {
+        ARGS.collectors.contains(self) && !UNAVAILABLE.read().unwrap().contains(self)
+    }
+    /// The interval of the collector in seconds: its own interval, or else the interval.
+    pub fn interval(&self) -> f64 {
+        ARGS.collector_interval
+            .iter()
+            .rev()
+            .find(|(collector, _)| collector == self)
+            .map_or(ARGS.interval, |(_, interval)| *interval)
+    }
+    /// The collector is read every so many fetches: its interval is rounded to a multiple of the
+    /// interval of the fetches.
+    fn due(&self, fetch: u64) -> bool {
+        fetch.is_multiple_of(fetches_per_read(self.interval(), ARGS.interval))
+    }
+    /// Read the source of the collector, to find out if it is available.
+    async fn probe(&self) -> Result<()> {
+        match self {
+            Collector::Cpu => read_stat_proc_data().await.map(|_| ()),
+            Collector::Schedstat => read_schedstat_proc_data().await.map(|_| ()),
+            Collector::Memory => read_meminfo_proc_data().await.map(|_| ()),
+            Collector::Disk => read_blockdevice_sys_data().await.map(|_| ()),
+            Collector::Network => read_netdev_proc_data().await.map(|_| ()),
+            Collector::Loadavg => read_loadavg_proc_data().await.map(|_| ()),
+            Collector::Pressure => read_pressure_proc_data().await.map(|_| ()),
+            Collector::Vmstat => read_vmstat_proc_data().await.map(|_| ()),
+            Collector::Xfs => read_xfs_proc_data().await.map(|_| ()),
+        }
+    }
+}
+
+/// The number of fetches from one read of a collector to the next: the interval of the collector
+/// divided by the interval of the fetches, rounded, and at least one.
+fn fetches_per_read(collector_interval: f64, interval: f64) -> u64 {
+    (collector_interval / interval).round().max(1_f64) as u64
+}
+
+impl fmt::Display for Collector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => write!(f, "{}", value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// The collectors of which the source is not available on this system, found at startup.
+static UNAVAILABLE: Lazy<RwLock<BTreeSet<Collector>>> = Lazy::new(Default::default);
+/// The collectors of which the source failed to be read at the last fetch.
+static FAILING: Lazy<RwLock<BTreeSet<Collector>>> = Lazy::new(Default::default);
+/// The time of the last fetch of which all statistics are added to the history.
+pub static LAST_FETCH: RwLock<Option<DateTime<Local>>> = RwLock::new(None);
+
+/// Find out which sources of the enabled collectors are available, and skip the collectors of
+/// which the source is not, such as pressure on kernels without PSI, or schedstat in containers.
+pub async fn probe_collectors() {
+    for collector in Collector::all() {
+        if !collector.enabled() {
+            continue;
+        }
+        if let Err(error) = collector.probe().await {
+            eprintln!(
+                "Collector {} is skipped, its source is not available: {:#}",
+                collector, error
+            );
+            UNAVAILABLE.write().unwrap().insert(collector);
+        }
+    }
+}
+
+/// Read the source of an enabled collector, if it is due in this fetch. If it fails, the collector
+/// is skipped for this fetch, and the failure and the recovery are reported once.
+async fn read_collector<T: Default>(
+    collector: Collector,
+    reader: impl Future<Output = Result<T>>,
+    fetch: u64,
+    collected: &mut BTreeSet<Collector>,
+) -> T {
+    if !collector.enabled() || !collector.due(fetch) {
+        return T::default();
+    }
+    match reader.await {
+        Ok(data) => {
+            if FAILING.write().unwrap().remove(&collector) {
+                eprintln!("Collector {} is read again.", collector);
+            }
+            collected.insert(collector);
+            data
+        }
+        Err(error) => {
+            if FAILING.write().unwrap().insert(collector) {
+                eprintln!(
+                    "Collector {} is skipped, reading its source failed: {:#}",
+                    collector, error
+                );
+            }
+            T::default()
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ProcData {
     pub timestamp: DateTime<Local>,
+    /// the collectors of which the source is read.
+    pub collectors: BTreeSet<Collector>,
     pub stat: proc_sys_parser::stat::ProcStat,
     pub schedstat: proc_sys_parser::schedstat::ProcSchedStat,
     pub meminfo: proc_sys_parser::meminfo::ProcMemInfo,
@@ -77,6 +217,8 @@ pub struct Statistic {
 #[derive(Debug)]
 pub struct Data {
     pub cpu: RwLock<VecDeque<CpuStat>>,
+    pub per_cpu: RwLock<VecDeque<PerCpuStat>>,
+    pub schedstat: RwLock<VecDeque<SchedStatInfo>>,
     pub memory: RwLock<VecDeque<MemInfo>>,
     pub blockdevices: RwLock<VecDeque<BlockDeviceInfo>>,
     pub networkdevices: RwLock<VecDeque<NetworkDeviceInfo>>,
@@ -84,26 +226,231 @@ pub struct Data {
     pub pressure: RwLock<VecDeque<PressureInfo>>,
     pub vmstat: RwLock<VecDeque<VmStatInfo>>,
     pub xfs: RwLock<VecDeque<XfsInfo>>,
+    /// the metadata of the last read archive.
+    pub metadata: RwLock<Option<ArchiveMetadata>>,
+    /// the roll-ups of the history, from the finest to the coarsest level.
+    pub rollups: Vec<Rollup>,
+}
+
+/// A row of a history.
+pub trait HistoryRow: Serialize + DeserializeOwned + Clone {
+    fn timestamp(&self) -> DateTime<Local>;
+}
+
+macro_rules! impl_history_row {
+    ($($row:ty),*) => {
+        $(
+        impl HistoryRow for $row {
+            fn timestamp(&self) -> DateTime<Local> {
+                self.timestamp
+            }
+        }
+        )*
+    };
+}
+
+impl_history_row!(
+    CpuStat,
+    PerCpuStat,
+    SchedStatInfo,
+    MemInfo,
+    BlockDeviceInfo,
+    NetworkDeviceInfo,
+    LoadavgInfo,
+    PressureInfo,
+    VmStatInfo,
+    XfsInfo
+);
+
+/// Add a row to a history, and remove the rows that are older than the history time.
+pub fn push_to_history<T: HistoryRow>(history: &RwLock<VecDeque<T>>, row: T) {
+    let oldest = row.timestamp() - TimeDelta::seconds(ARGS.history as i64);
+    let mut history = history.write().unwrap();
+    while history.front().is_some_and(|row| row.timestamp() < oldest) {
+        history.pop_front();
+    }
+    history.push_back(row);
 }
 
 impl Data {
-    pub fn new(history: usize) -> Data {
+    /// A history with the roll-ups of the levels.
+    pub fn new(rollup_levels: &'static [RollupLevel]) -> Data {
         Data {
-            cpu: RwLock::new(VecDeque::with_capacity(history)),
-            memory: RwLock::new(VecDeque::with_capacity(history)),
-            blockdevices: RwLock::new(VecDeque::with_capacity(history)),
-            networkdevices: RwLock::new(VecDeque::with_capacity(history)),
-            loadavg: RwLock::new(VecDeque::with_capacity(history)),
-            pressure: RwLock::new(VecDeque::with_capacity(history)),
-            vmstat: RwLock::new(VecDeque::with_capacity(history)),
-            xfs: RwLock::new(VecDeque::with_capacity(history)),
+            cpu: RwLock::new(VecDeque::new()),
+            per_cpu: RwLock::new(VecDeque::new()),
+            schedstat: RwLock::new(VecDeque::new()),
+            memory: RwLock::new(VecDeque::new()),
+            blockdevices: RwLock::new(VecDeque::new()),
+            networkdevices: RwLock::new(VecDeque::new()),
+            loadavg: RwLock::new(VecDeque::new()),
+            pressure: RwLock::new(VecDeque::new()),
+            vmstat: RwLock::new(VecDeque::new()),
+            xfs: RwLock::new(VecDeque::new()),
+            metadata: RwLock::new(None),
+            rollups: rollup_levels.iter().map(Rollup::new).collect(),
+        }
+    }
+    /// The time range of a plot: the start and end time that are set, or else the time in which
+    /// all histories have rows, from the latest first row to the earliest last row. The collectors
+    /// can have their own interval, so histories with the same number of rows span a different
+    /// time; the shared range lines up the panels.
+    pub fn time_range(
+        &self,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+    ) -> (Option<DateTime<Local>>, Option<DateTime<Local>>) {
+        fn first_and_last<T>(
+            rows: &RwLock<VecDeque<T>>,
+            timestamp: fn(&T) -> DateTime<Local>,
+        ) -> Option<(DateTime<Local>, DateTime<Local>)> {
+            let rows = rows.read().unwrap();
+            Some((timestamp(rows.front()?), timestamp(rows.back()?)))
+        }
+        let ranges = [
+            first_and_last(&self.cpu, |r| r.timestamp),
+            first_and_last(&self.schedstat, |r| r.timestamp),
+            first_and_last(&self.memory, |r| r.timestamp),
+            first_and_last(&self.blockdevices, |r| r.timestamp),
+            first_and_last(&self.networkdevices, |r| r.timestamp),
+            first_and_last(&self.loadavg, |r| r.timestamp),
+            first_and_last(&self.pressure, |r| r.timestamp),
+            first_and_last(&self.vmstat, |r| r.timestamp),
+            first_and_last(&self.xfs, |r| r.timestamp),
+        ];
+        let ranges = ranges.iter().flatten();
+        (
+            start_time.or(ranges.clone().map(|(first, _)| *first).max()),
+            end_time.or(ranges.map(|(_, last)| *last).min()),
+        )
+    }
+    /// The history has rows of the statistics of the collector.
+    pub fn has_rows(&self, collector: Collector) -> bool {
+        match collector {
+            Collector::Cpu => !self.cpu.read().unwrap().is_empty(),
+            Collector::Schedstat => !self.schedstat.read().unwrap().is_empty(),
+            Collector::Memory => !self.memory.read().unwrap().is_empty(),
+            Collector::Disk => !self.blockdevices.read().unwrap().is_empty(),
+            Collector::Network => !self.networkdevices.read().unwrap().is_empty(),
+            Collector::Loadavg => !self.loadavg.read().unwrap().is_empty(),
+            Collector::Pressure => !self.pressure.read().unwrap().is_empty(),
+            Collector::Vmstat => !self.vmstat.read().unwrap().is_empty(),
+            Collector::Xfs => !self.xfs.read().unwrap().is_empty(),
+        }
+    }
+}
+
+thread_local! {
+    static SELECTED_HOST: RefCell<Option<String>> = const { RefCell::new(None) };
+    static SELECTED_ROLLUP: Cell<Option<(usize, Aggregate)>> = const { Cell::new(None) };
+}
+
+/// The histories by host name. The statistics that are collected, and the archives that are
+/// read together (such as for a report), are kept in the default history; the archives that are
+/// read by host, and the histories of federated instances, are kept in the history of their host
+/// name.
+///
+/// The histories dereference to the history of the host that is selected for the current thread
+/// with `with_host`, or else to the first host or the default history, so the code that uses the
+/// history doesn't need to know about hosts. In the same way, `with_rollup` selects a roll-up of
+/// that history.
+#[derive(Debug)]
+pub struct Histories {
+    rollup_levels: &'static [RollupLevel],
+    default: Data,
+    // a history lives as long as procstat, so it can be handed out without holding the lock.
+    hosts: RwLock<BTreeMap<String, &'static Data>>,
+}
+
+/// Restores the previously selected host when a `with_host` closure ends, even if it panics.
+struct SelectedHostGuard(Option<String>);
+
+impl Drop for SelectedHostGuard {
+    fn drop(&mut self) {
+        SELECTED_HOST.with_borrow_mut(|selected_host| *selected_host = self.0.take());
+    }
+}
+
+/// Restores the previously selected roll-up when a `with_rollup` closure ends.
+struct SelectedRollupGuard(Option<(usize, Aggregate)>);
+
+impl Drop for SelectedRollupGuard {
+    fn drop(&mut self) {
+        SELECTED_ROLLUP.set(self.0);
+    }
+}
+
+impl Histories {
+    pub fn new(rollup_levels: &'static [RollupLevel]) -> Histories {
+        Histories {
+            rollup_levels,
+            default: Data::new(rollup_levels),
+            hosts: RwLock::new(BTreeMap::new()),
         }
     }
+    /// The history of the host, which is created if it doesn't exist.
+    pub fn host(&self, hostname: &str) -> &'static Data {
+        if let Some(data) = self.hosts.read().unwrap().get(hostname) {
+            return data;
+        }
+        self.hosts
+            .write()
+            .unwrap()
+            .entry(hostname.to_string())
+            .or_insert_with(|| Box::leak(Box::new(Data::new(self.rollup_levels))))
+    }
+    pub fn hostnames(&self) -> Vec<String> {
+        self.hosts.read().unwrap().keys().cloned().collect()
+    }
+    /// Run the function with the history of the host as the history of the current thread. A
+    /// host that doesn't exist selects the first host.
+    pub fn with_host<T>(&self, hostname: Option<&str>, function: impl FnOnce() -> T) -> T {
+        let _guard = SelectedHostGuard(SELECTED_HOST.with_borrow_mut(|selected_host| {
+            selected_host.replace(hostname.unwrap_or_default().to_string())
+        }));
+        function()
+    }
+    /// Run the function with a roll-up of the level as the history of the current thread; None
+    /// selects the history itself.
+    pub fn with_rollup<T>(
+        &self,
+        rollup: Option<(usize, Aggregate)>,
+        function: impl FnOnce() -> T,
+    ) -> T {
+        let _guard = SelectedRollupGuard(SELECTED_ROLLUP.replace(rollup));
+        function()
+    }
+}
+
+impl Deref for Histories {
+    type Target = Data;
+
+    fn deref(&self) -> &Data {
+        let data = SELECTED_HOST
+            .with_borrow(|selected_host| {
+                let hosts = self.hosts.read().unwrap();
+                selected_host
+                    .as_ref()
+                    .and_then(|hostname| hosts.get(hostname))
+                    .or_else(|| hosts.values().next())
+                    .copied()
+            })
+            .unwrap_or(&self.default);
+        SELECTED_ROLLUP
+            .get()
+            .and_then(|(level, aggregate)| Some(data.rollups.get(level)?.aggregate(aggregate)))
+            .unwrap_or(data)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct HistoricalDataTransit {
     pub cpu: Vec<CpuStat>,
+    /// not present in archives of older versions.
+    #[serde(default)]
+    pub per_cpu: Vec<PerCpuStat>,
+    /// not present in archives of older versions.
+    #[serde(default)]
+    pub schedstat: Vec<SchedStatInfo>,
     pub memory: Vec<MemInfo>,
     pub blockdevices: Vec<BlockDeviceInfo>,
     pub networkdevices: Vec<NetworkDeviceInfo>,
@@ -111,39 +458,80 @@ pub struct HistoricalDataTransit {
     pub pressure: Vec<PressureInfo>,
     pub vmstat: Vec<VmStatInfo>,
     pub xfs: Vec<XfsInfo>,
+    /// not present in archives of older versions.
+    #[serde(default)]
+    pub metadata: Option<ArchiveMetadata>,
 }
 
+/// Read the sources of the collectors that are due in this fetch, and process them. Returns the
+/// collectors that are read.
 pub async fn read_proc_data_and_process(
     statistics: &mut HashMap<(String, String, String), Statistic>,
-) -> Result<()> {
+    fetch: u64,
+) -> Result<BTreeSet<Collector>> {
     let timestamp = Local::now();
-    let proc_stat = read_stat_proc_data()
-        .await
-        .with_context(|| "Proc stat reader")?;
-    let proc_schedstat = read_schedstat_proc_data()
-        .await
-        .with_context(|| "Proc schedstat reader")?;
-    let proc_meminfo = read_meminfo_proc_data()
-        .await
-        .with_context(|| "Proc meminfo reader")?;
-    let sys_block_devices = read_blockdevice_sys_data()
-        .await
-        .with_context(|| "Sys block reader")?;
-    let proc_netdev = read_netdev_proc_data()
-        .await
-        .with_context(|| "Proc netdev reader")?;
-    let proc_loadavg = read_loadavg_proc_data()
-        .await
-        .with_context(|| "Proc loadavg reader")?;
-    let proc_pressure = read_pressure_proc_data()
-        .await
-        .with_context(|| "Proc pressure reader")?;
-    let proc_vmstat = read_vmstat_proc_data()
-        .await
-        .with_context(|| "proc vmstat reader")?;
-    let proc_xfs = read_xfs_proc_data().await;
+    let mut collectors = BTreeSet::new();
+    let proc_stat = read_collector(
+        Collector::Cpu,
+        read_stat_proc_data(),
+        fetch,
+        &mut collectors,
+    )
+    .await;
+    let proc_schedstat = read_collector(
+        Collector::Schedstat,
+        read_schedstat_proc_data(),
+        fetch,
+        &mut collectors,
+    )
+    .await;
+    let proc_meminfo = read_collector(
+        Collector::Memory,
+        read_meminfo_proc_data(),
+        fetch,
+        &mut collectors,
+    )
+    .await;
+    let sys_block_devices = read_collector(
+        Collector::Disk,
+        read_blockdevice_sys_data(),
+        fetch,
+        &mut collectors,
+    )
+    .await;
+    let proc_netdev = read_collector(
+        Collector::Network,
+        read_netdev_proc_data(),
+        fetch,
+        &mut collectors,
+    )
+    .await;
+    let proc_loadavg = read_collector(
+        Collector::Loadavg,
+        read_loadavg_proc_data(),
+        fetch,
+        &mut collectors,
+    )
+    .await;
+    let proc_pressure = read_collector(
+        Collector::Pressure,
+        read_pressure_proc_data(),
+        fetch,
+        &mut collectors,
+    )
+    .await;
+    let proc_vmstat = read_collector(
+        Collector::Vmstat,
+        read_vmstat_proc_data(),
+        fetch,
+        &mut collectors,
+    )
+    .await;
+    let proc_xfs =
+        read_collector(Collector::Xfs, read_xfs_proc_data(), fetch, &mut collectors).await;
     let proc_data = ProcData {
         timestamp,
+        collectors: collectors.clone(),
         stat: proc_stat,
         schedstat: proc_schedstat,
         meminfo: proc_meminfo,
@@ -158,75 +546,124 @@ pub async fn read_proc_data_and_process(
         .await
         .with_context(|| "Process data")?;
     if ARGS.webserver || ARGS.archiver {
-        add_to_history(statistics)
+        add_to_history(statistics, &collectors)
             .await
             .with_context(|| "Add to history")?;
+        *LAST_FETCH.write().unwrap() = Some(timestamp);
     }
-    Ok(())
+    if ARGS.webserver {
+        DATA.roll_up();
+    }
+    Ok(collectors)
 }
 
 pub async fn process_data(
     proc_data: ProcData,
     statistics: &mut HashMap<(String, String, String), Statistic>,
 ) -> Result<()> {
-    process_stat_data(&proc_data, statistics)
-        .await
-        .with_context(|| "Proc stat processor")?;
-    process_schedstat_data(&proc_data, statistics)
-        .await
-        .with_context(|| "Proc schedstat processor")?;
-    process_meminfo_data(&proc_data, statistics)
-        .await
-        .with_context(|| "Proc meminfo processor")?;
-    process_blockdevice_data(&proc_data, statistics)
-        .await
-        .with_context(|| "Sys block processor")?;
-    process_net_dev_data(&proc_data, statistics)
-        .await
-        .with_context(|| "Proc netdev processor")?;
-    process_loadavg_data(&proc_data, statistics)
-        .await
-        .with_context(|| "Proc loadavg processor")?;
-    process_pressure_data(&proc_data, statistics)
-        .await
-        .with_context(|| "Proc pressure processor")?;
-    process_vmstat_data(&proc_data, statistics)
-        .await
-        .with_context(|| "Proc vmstat processor")?;
-    process_xfs_data(&proc_data, statistics)
-        .await
-        .with_context(|| "Proc xfs processor")?;
+    if proc_data.collectors.contains(&Collector::Cpu) {
+        process_stat_data(&proc_data, statistics)
+            .await
+            .with_context(|| "Proc stat processor")?;
+    }
+    if proc_data.collectors.contains(&Collector::Schedstat) {
+        process_schedstat_data(&proc_data, statistics)
+            .await
+            .with_context(|| "Proc schedstat processor")?;
+    }
+    if proc_data.collectors.contains(&Collector::Memory) {
+        process_meminfo_data(&proc_data, statistics)
+            .await
+            .with_context(|| "Proc meminfo processor")?;
+    }
+    if proc_data.collectors.contains(&Collector::Disk) {
+        process_blockdevice_data(&proc_data, statistics)
+            .await
+            .with_context(|| "Sys block processor")?;
+    }
+    if proc_data.collectors.contains(&Collector::Network) {
+        process_net_dev_data(&proc_data, statistics)
+            .await
+            .with_context(|| "Proc netdev processor")?;
+    }
+    if proc_data.collectors.contains(&Collector::Loadavg) {
+        process_loadavg_data(&proc_data, statistics)
+            .await
+            .with_context(|| "Proc loadavg processor")?;
+    }
+    if proc_data.collectors.contains(&Collector::Pressure) {
+        process_pressure_data(&proc_data, statistics)
+            .await
+            .with_context(|| "Proc pressure processor")?;
+    }
+    if proc_data.collectors.contains(&Collector::Vmstat) {
+        process_vmstat_data(&proc_data, statistics)
+            .await
+            .with_context(|| "Proc vmstat processor")?;
+    }
+    if proc_data.collectors.contains(&Collector::Xfs) {
+        process_xfs_data(&proc_data, statistics)
+            .await
+            .with_context(|| "Proc xfs processor")?;
+    }
 
     Ok(())
 }
 
 pub async fn add_to_history(
     statistics: &HashMap<(String, String, String), Statistic>,
+    collectors: &BTreeSet<Collector>,
 ) -> Result<()> {
-    add_cpu_total_to_history(statistics)
-        .await
-        .with_context(|| "Proc stat history addition")?;
-    add_memory_to_history(statistics)
-        .await
-        .with_context(|| "Proc meminfo history addition")?;
-    add_blockdevices_to_history(statistics)
-        .await
-        .with_context(|| "Sys blockdevices history addition")?;
-    add_networkdevices_to_history(statistics)
-        .await
-        .with_context(|| "Proc netdev history addition")?;
-    add_loadavg_to_history(statistics)
-        .await
-        .with_context(|| "Proc loadavg history addition")?;
-    add_pressure_to_history(statistics)
-        .await
-        .with_context(|| "Proc pressure history addition")?;
-    add_vmstat_to_history(statistics)
-        .await
-        .with_context(|| "Proc vmstat history addition")?;
-    add_xfs_to_history(statistics)
-        .await
-        .with_context(|| "Proc xfs history addition")?;
+    if collectors.contains(&Collector::Cpu) {
+        add_cpu_total_to_history(statistics)
+            .await
+            .with_context(|| "Proc stat history addition")?;
+    }
+    if collectors.contains(&Collector::Cpu) {
+        add_per_cpu_to_history(statistics)
+            .await
+            .with_context(|| "Proc stat per cpu history addition")?;
+    }
+    if collectors.contains(&Collector::Schedstat) {
+        add_schedstat_to_history(statistics)
+            .await
+            .with_context(|| "Proc schedstat history addition")?;
+    }
+    if collectors.contains(&Collector::Memory) {
+        add_memory_to_history(statistics)
+            .await
+            .with_context(|| "Proc meminfo history addition")?;
+    }
+    if collectors.contains(&Collector::Disk) {
+        add_blockdevices_to_history(statistics)
+            .await
+            .with_context(|| "Sys blockdevices history addition")?;
+    }
+    if collectors.contains(&Collector::Network) {
+        add_networkdevices_to_history(statistics)
+            .await
+            .with_context(|| "Proc netdev history addition")?;
+    }
+    if collectors.contains(&Collector::Loadavg) {
+        add_loadavg_to_history(statistics)
+            .await
+            .with_context(|| "Proc loadavg history addition")?;
+    }
+    if collectors.contains(&Collector::Pressure) {
+        add_pressure_to_history(statistics)
+            .await
+            .with_context(|| "Proc pressure history addition")?;
+    }
+    if collectors.contains(&Collector::Vmstat) {
+        add_vmstat_to_history(statistics)
+            .await
+            .with_context(|| "Proc vmstat history addition")?;
+    }
+    if collectors.contains(&Collector::Xfs) {
+        add_xfs_to_history(statistics)
+            .await
+            .with_context(|| "Proc xfs history addition")?;
+    }
     Ok(())
 }
 
@@ -249,8 +686,9 @@ pub async fn single_statistic_u64(
             row.per_second_value = row.delta_value
                 / (timestamp
                     .signed_duration_since(row.last_timestamp)
-                    .num_milliseconds() as f64
-                    / 1000_f64);
+                    .num_microseconds()
+                    .unwrap_or_default() as f64
+                    / 1_000_000_f64);
             row.last_value = value as f64;
             row.last_timestamp = timestamp;
             row.updated_value = true;
@@ -285,8 +723,9 @@ pub async fn single_statistic_f64(
             row.per_second_value = row.delta_value
                 / (timestamp
                     .signed_duration_since(row.last_timestamp)
-                    .num_milliseconds() as f64
-                    / 1000_f64);
+                    .num_microseconds()
+                    .unwrap_or_default() as f64
+                    / 1_000_000_f64);
             row.last_value = value;
             row.last_timestamp = timestamp;
             row.updated_value = true;
@@ -322,8 +761,9 @@ pub async fn single_statistic_option_f64(
             row.per_second_value = row.delta_value
                 / (timestamp
                     .signed_duration_since(row.last_timestamp)
-                    .num_milliseconds() as f64
-                    / 1000_f64);
+                    .num_microseconds()
+                    .unwrap_or_default() as f64
+                    / 1_000_000_f64);
             row.last_value = value;
             row.last_timestamp = timestamp;
             row.updated_value = true;
@@ -359,8 +799,9 @@ pub async fn single_statistic_option_u64(
             row.per_second_value = row.delta_value
                 / (timestamp
                     .signed_duration_since(row.last_timestamp)
-                    .num_milliseconds() as f64
-                    / 1000_f64);
+                    .num_microseconds()
+                    .unwrap_or_default() as f64
+                    / 1_000_000_f64);
             row.last_value = value as f64;
             row.last_timestamp = timestamp;
             row.updated_value = true;
@@ -406,3 +847,82 @@ macro_rules! add_list_of_f64_data_to_statistics {
         )*
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn histories_are_selected_by_host() {
+        let histories = Histories::new(&[]);
+        histories
+            .host("db01")
+            .memory
+            .write()
+            .unwrap()
+            .push_back(MemInfo {
+                memtotal: 1_f64,
+                ..Default::default()
+            });
+        histories
+            .host("db02")
+            .memory
+            .write()
+            .unwrap()
+            .push_back(MemInfo {
+                memtotal: 2_f64,
+                ..Default::default()
+            });
+        let memtotal = || histories.memory.read().unwrap()[0].memtotal;
+        assert_eq!(histories.hostnames(), vec!["db01", "db02"]);
+        // without a selection, or with an unknown host, the first host is used.
+        assert_eq!(memtotal(), 1_f64);
+        assert_eq!(histories.with_host(Some("db02"), memtotal), 2_f64);
+        assert_eq!(histories.with_host(Some("db03"), memtotal), 1_f64);
+        assert_eq!(memtotal(), 1_f64);
+    }
+
+    #[test]
+    fn collector_intervals_are_rounded_to_fetches() {
+        assert_eq!(fetches_per_read(1_f64, 1_f64), 1);
+        assert_eq!(fetches_per_read(60_f64, 1_f64), 60);
+        // a collector can't be read more often than the fetches.
+        assert_eq!(fetches_per_read(0.5, 1_f64), 1);
+        assert_eq!(fetches_per_read(0.1, 0.2), 1);
+        // an interval that is not a multiple is rounded to the nearest multiple.
+        assert_eq!(fetches_per_read(2.4, 1_f64), 2);
+        assert_eq!(fetches_per_read(2.6, 1_f64), 3);
+        assert_eq!(fetches_per_read(1_f64, 0.3), 3);
+    }
+
+    #[test]
+    fn time_range_is_the_time_in_which_all_histories_have_rows() {
+        let data = Data::new(&[]);
+        let start = Local.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let at = |seconds: i64| start + TimeDelta::seconds(seconds);
+        for seconds in 0..11 {
+            data.cpu.write().unwrap().push_back(CpuStat {
+                timestamp: at(seconds),
+                ..Default::default()
+            });
+        }
+        // the memory is read every 3 seconds, so its history starts later and ends earlier.
+        for seconds in (3..10).step_by(3) {
+            data.memory.write().unwrap().push_back(MemInfo {
+                timestamp: at(seconds),
+                ..Default::default()
+            });
+        }
+        assert_eq!(data.time_range(None, None), (Some(at(3)), Some(at(9))));
+        assert_eq!(
+            data.time_range(Some(at(1)), None),
+            (Some(at(1)), Some(at(9)))
+        );
+        assert_eq!(
+            data.time_range(None, Some(at(10))),
+            (Some(at(3)), Some(at(10)))
+        );
+        assert_eq!(Data::new(&[]).time_range(None, None), (None, None));
+    }
+}
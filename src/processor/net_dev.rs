@@ -1,18 +1,20 @@
 use anyhow::Result;
 use chrono::{DateTime, Local};
 use log::debug;
-use proc_sys_parser::net_dev::ProcNetDev;
+use proc_sys_parser::net_dev::{Builder, ProcNetDev};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashMap};
 //
-use crate::processor::{single_statistic_u64, ProcData, Statistic};
+use crate::processor::{push_to_history, single_statistic_u64, ProcData, Statistic};
 use crate::Data;
 use crate::ARGS;
 use crate::DATA;
+use crate::{timestamp_format, timestamp_header};
 
 use super::ProcessorError;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct NetworkDeviceInfo {
     pub timestamp: DateTime<Local>,
     pub device_name: String,
@@ -35,7 +37,7 @@ pub struct NetworkDeviceInfo {
 }
 
 pub async fn read_netdev_proc_data() -> Result<ProcNetDev> {
-    let proc_netdev = proc_sys_parser::net_dev::read()?;
+    let proc_netdev = Builder::new().filter(&ARGS.network_filter).read()?;
     debug!("{:?}", proc_netdev);
     Ok(proc_netdev)
 }
@@ -425,13 +427,7 @@ pub async fn add_networkdevices_to_history(
 
 impl Data {
     pub async fn push_networkdevices(networkdeviceinfo: NetworkDeviceInfo) {
-        while DATA.networkdevices.read().unwrap().len() >= ARGS.history {
-            DATA.networkdevices.write().unwrap().pop_front();
-        }
-        DATA.networkdevices
-            .write()
-            .unwrap()
-            .push_back(networkdeviceinfo);
+        push_to_history(&DATA.networkdevices, networkdeviceinfo);
     }
 }
 
@@ -468,7 +464,7 @@ pub async fn print_net_dev(
         "sar-n-DEV" => {
             println!(
                 "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                "Timestamp",
+                timestamp_header("Timestamp"),
                 "IFACE",
                 "rxpck/s",
                 "txpck/s",
@@ -482,7 +478,7 @@ pub async fn print_net_dev(
         "sar-n-EDEV" => {
             println!(
                 "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                "Timestamp",
+                timestamp_header("Timestamp"),
                 "IFACE",
                 "rxerr/s",
                 "txerr/s",
@@ -725,7 +721,7 @@ pub async fn print_net_dev(
             "sar-n-DEV" => {
                 println!(
                     "{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                    timestamp.format("%H:%M:%S"),
+                    timestamp.format(timestamp_format()),
                     device,
                     receive_packets,
                     transmit_packets,
@@ -738,7 +734,7 @@ pub async fn print_net_dev(
             }
             "sar-n-EDEV" => {
                 println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                         timestamp.format("%H:%M:%S"),
+                         timestamp.format(timestamp_format()),
                          device,
                          receive_errors,
                          transmit_errors,
@@ -1,12 +1,12 @@
 #![allow(unused_assignments)]
 
 use crate::processor::{
-    single_statistic_f64, single_statistic_option_f64, single_statistic_option_u64,
-    single_statistic_u64, ProcData, Statistic,
+    push_to_history, single_statistic_f64, single_statistic_option_f64,
+    single_statistic_option_u64, single_statistic_u64, ProcData, Statistic,
 };
 use crate::Data;
-use crate::ARGS;
 use crate::DATA;
+use crate::{timestamp_format, timestamp_header};
 use anyhow::Result;
 use chrono::{DateTime, Local};
 use log::debug;
@@ -17,6 +17,7 @@ use std::collections::HashMap;
 use super::ProcessorError;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct PressureInfo {
     pub timestamp: DateTime<Local>,
     pub cpu_some_avg10: f64,
@@ -692,10 +693,7 @@ pub async fn add_pressure_to_history(
 
 impl Data {
     pub async fn push_pressure(pressure: PressureInfo) {
-        while DATA.pressure.read().unwrap().len() >= ARGS.history {
-            DATA.pressure.write().unwrap().pop_front();
-        }
-        DATA.pressure.write().unwrap().push_back(pressure);
+        push_to_history(&DATA.pressure, pressure);
     }
 }
 
@@ -709,13 +707,18 @@ pub async fn print_psi(
             "sar-q-CPU" => {
                 println!(
                     "{:10} {:7}    {:>10} {:>10} {:>10} {:>10}",
-                    "Timestamp", "", "%scpu-10", "%scpu-60", "%scpu-300", "%scpu",
+                    timestamp_header("Timestamp"),
+                    "",
+                    "%scpu-10",
+                    "%scpu-60",
+                    "%scpu-300",
+                    "%scpu",
                 );
             }
             "sar-q-IO" => {
                 println!(
                     "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                    "Timestamp",
+                    timestamp_header("Timestamp"),
                     "",
                     "%sio-10",
                     "%sio-60",
@@ -730,7 +733,7 @@ pub async fn print_psi(
             "sar-q-MEM" => {
                 println!(
                     "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                    "Timestamp",
+                    timestamp_header("Timestamp"),
                     "",
                     "%smem-10",
                     "%smem-60",
@@ -1039,7 +1042,7 @@ pub async fn print_psi(
         "sar-q-CPU" => {
             println!(
                 "{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 "",
                 cpu_some_avg10,
                 cpu_some_avg60,
@@ -1050,7 +1053,7 @@ pub async fn print_psi(
         "sar-q-IO" => {
             println!(
                 "{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 "",
                 io_some_avg10,
                 io_some_avg60,
@@ -1065,7 +1068,7 @@ pub async fn print_psi(
         "sar-q-MEM" => {
             println!(
                 "{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 "",
                 mem_some_avg10,
                 mem_some_avg60,
@@ -0,0 +1,348 @@
+//! The roll-ups of the history: the rows of every minute and of every ten minutes are aggregated
+//! into rows with their minimum, average and maximum, which are kept for a day and for a week,
+//! so a long time is kept and plotted with a bounded number of rows.
+
+use crate::processor::{Data, HistoryRow};
+use crate::ARGS;
+use chrono::{DateTime, Local, TimeDelta};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+/// A level of the roll-ups: the rows of every period of `width` seconds are aggregated, and the
+/// aggregated rows are kept for `retention` seconds.
+#[derive(Debug)]
+pub struct RollupLevel {
+    pub name: &'static str,
+    pub width: i64,
+    pub retention: i64,
+}
+
+/// The levels of the roll-ups, from the finest to the coarsest. A level is aggregated from the
+/// level before it, the first level from the history.
+pub static ROLLUP_LEVELS: [RollupLevel; 2] = [
+    RollupLevel {
+        name: "1 minute",
+        width: 60,
+        retention: 86_400,
+    },
+    RollupLevel {
+        name: "10 minutes",
+        width: 600,
+        retention: 604_800,
+    },
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    Min,
+    #[default]
+    Avg,
+    Max,
+}
+
+impl Aggregate {
+    const ALL: [Aggregate; 3] = [Aggregate::Min, Aggregate::Avg, Aggregate::Max];
+
+    fn of(&self, values: &[f64]) -> f64 {
+        match self {
+            Aggregate::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregate::Avg => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregate::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+/// The minimum, average and maximum rows of a level.
+#[derive(Debug)]
+pub struct Rollup {
+    pub level: &'static RollupLevel,
+    pub min: Data,
+    pub avg: Data,
+    pub max: Data,
+}
+
+impl Rollup {
+    pub fn new(level: &'static RollupLevel) -> Rollup {
+        Rollup {
+            level,
+            min: Data::new(&[]),
+            avg: Data::new(&[]),
+            max: Data::new(&[]),
+        }
+    }
+    pub fn aggregate(&self, aggregate: Aggregate) -> &Data {
+        match aggregate {
+            Aggregate::Min => &self.min,
+            Aggregate::Avg => &self.avg,
+            Aggregate::Max => &self.max,
+        }
+    }
+}
+
+impl Data {
+    /// Aggregate the periods that are complete into the roll-ups. A period is complete when the
+    /// history has a row of a later period.
+    pub fn roll_up(&self) {
+        for (index, rollup) in self.rollups.iter().enumerate() {
+            let sources = match index.checked_sub(1) {
+                None => [self, self, self],
+                Some(previous) => {
+                    let previous = &self.rollups[previous];
+                    [&previous.min, &previous.avg, &previous.max]
+                }
+            };
+            macro_rules! roll_up {
+                ($($history:ident),*) => {
+                    $(
+                    roll_up_history(
+                        sources.map(|data| &data.$history),
+                        [&rollup.min.$history, &rollup.avg.$history, &rollup.max.$history],
+                        rollup.level,
+                    );
+                    )*
+                };
+            }
+            roll_up!(
+                cpu,
+                per_cpu,
+                schedstat,
+                memory,
+                blockdevices,
+                networkdevices,
+                loadavg,
+                pressure,
+                vmstat,
+                xfs
+            );
+        }
+    }
+    /// The roll-up level to plot a time range with: the finest level that keeps a range of its
+    /// length and has rows at its start, or else the level with the oldest rows. Without a start
+    /// time, and for None, the history itself is plotted.
+    pub fn rollup_level(
+        &self,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+    ) -> Option<usize> {
+        let start_time = start_time?;
+        let (first, last) = self.time_range(None, None);
+        let length = (end_time.or(last).unwrap_or_else(Local::now) - start_time).num_seconds();
+        let levels = std::iter::once((None, ARGS.history as i64, first))
+            .chain(self.rollups.iter().enumerate().map(|(index, rollup)| {
+                (
+                    Some(index),
+                    rollup.level.retention,
+                    rollup.avg.time_range(None, None).0,
+                )
+            }))
+            .filter(|(_, _, first)| first.is_some())
+            .collect::<Vec<_>>();
+        let covering = levels
+            .iter()
+            .filter(|(_, _, first)| *first <= Some(start_time))
+            .collect::<Vec<_>>();
+        match covering
+            .iter()
+            .find(|(_, retention, _)| length <= *retention)
+            .or(covering.first())
+        {
+            Some((level, _, _)) => *level,
+            None => levels
+                .iter()
+                .min_by_key(|(_, _, first)| *first)
+                .and_then(|(level, _, _)| *level),
+        }
+    }
+}
+
+/// Aggregate the complete periods of a history since the last aggregated period, and remove the
+/// aggregated rows that are older than the retention of the level. The sources and roll-ups are
+/// the minimum, average and maximum histories.
+fn roll_up_history<T: HistoryRow>(
+    sources: [&RwLock<VecDeque<T>>; 3],
+    rollups: [&RwLock<VecDeque<T>>; 3],
+    level: &RollupLevel,
+) {
+    let period =
+        |timestamp: DateTime<Local>| timestamp.timestamp().div_euclid(level.width) * level.width;
+    let Some(incomplete) = sources[1]
+        .read()
+        .unwrap()
+        .back()
+        .map(|row| period(row.timestamp()))
+    else {
+        return;
+    };
+    let next = rollups[1]
+        .read()
+        .unwrap()
+        .back()
+        .map(|row| period(row.timestamp()) + level.width);
+    if next.is_some_and(|next| next >= incomplete) {
+        return;
+    }
+    for ((aggregate, source), rollup) in Aggregate::ALL.into_iter().zip(sources).zip(rollups) {
+        let source = source.read().unwrap();
+        let first = source
+            .iter()
+            .rposition(|row| next.is_some_and(|next| period(row.timestamp()) < next))
+            .map_or(0, |position| position + 1);
+        let rows = source
+            .range(first..)
+            .take_while(|row| period(row.timestamp()) < incomplete);
+        let aggregated = aggregate_rows(rows, aggregate, level.width);
+        drop(source);
+
+        let mut rollup = rollup.write().unwrap();
+        rollup.extend(aggregated);
+        if let Some(last) = rollup.back().map(|row| row.timestamp()) {
+            let oldest = last - TimeDelta::seconds(level.retention);
+            while rollup.front().is_some_and(|row| row.timestamp() < oldest) {
+                rollup.pop_front();
+            }
+        }
+    }
+}
+
+/// The rows of a period, by the text fields of the rows.
+type PeriodRows = Vec<(String, Vec<Map<String, Value>>)>;
+
+/// Aggregate the rows by period and, within a period, by their text fields, which are the cpu or
+/// device of a row. The aggregated rows have the start of the period as timestamp.
+fn aggregate_rows<'a, T: HistoryRow + 'a>(
+    rows: impl Iterator<Item = &'a T>,
+    aggregate: Aggregate,
+    width: i64,
+) -> Vec<T> {
+    let mut periods: Vec<(i64, PeriodRows)> = Vec::new();
+    for row in rows {
+        let period = row.timestamp().timestamp().div_euclid(width) * width;
+        let Ok(Value::Object(fields)) = serde_json::to_value(row) else {
+            continue;
+        };
+        let key = fields
+            .iter()
+            .filter(|(name, _)| *name != "timestamp")
+            .filter_map(|(_, value)| value.as_str())
+            .collect::<Vec<_>>()
+            .join("/");
+        if periods.last().is_none_or(|(last, _)| *last != period) {
+            periods.push((period, Vec::new()));
+        }
+        let groups = &mut periods.last_mut().unwrap().1;
+        match groups.iter_mut().find(|(group, _)| *group == key) {
+            Some((_, rows)) => rows.push(fields),
+            None => groups.push((key, vec![fields])),
+        }
+    }
+    periods
+        .into_iter()
+        .flat_map(|(period, groups)| {
+            let timestamp = DateTime::from_timestamp(period, 0).map(|t| t.with_timezone(&Local));
+            groups
+                .into_iter()
+                .filter_map(move |(_, rows)| aggregate_fields(&rows, aggregate, timestamp?))
+        })
+        .collect()
+}
+
+/// Aggregate the numeric fields of rows; the other fields are taken from the last row.
+fn aggregate_fields<T: HistoryRow>(
+    rows: &[Map<String, Value>],
+    aggregate: Aggregate,
+    timestamp: DateTime<Local>,
+) -> Option<T> {
+    let mut fields = rows.last()?.clone();
+    for (name, value) in fields.iter_mut() {
+        if name == "timestamp" {
+            *value = serde_json::to_value(timestamp).ok()?;
+        // a value that is not a number (NaN) is serialized as null.
+        } else if value.is_number() || value.is_null() {
+            let values = rows
+                .iter()
+                .filter_map(|row| row.get(name)?.as_f64())
+                .collect::<Vec<_>>();
+            *value = if values.is_empty() {
+                Value::from(0_f64)
+            } else {
+                Value::from(aggregate.of(&values))
+            };
+        }
+    }
+    serde_json::from_value(Value::Object(fields)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::stat::PerCpuStat;
+    use chrono::TimeZone;
+
+    #[test]
+    fn complete_periods_are_rolled_up_by_cpu() {
+        let data = Data::new(&ROLLUP_LEVELS);
+        let start = Local.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let row = |seconds: i64, cpu_name: &str, user: f64| PerCpuStat {
+            timestamp: start + TimeDelta::seconds(seconds),
+            cpu_name: cpu_name.to_string(),
+            user,
+            ..Default::default()
+        };
+        for (seconds, user) in [(0, 1_f64), (30, 3_f64), (59, 8_f64)] {
+            data.per_cpu
+                .write()
+                .unwrap()
+                .push_back(row(seconds, "cpu0", user));
+            data.per_cpu
+                .write()
+                .unwrap()
+                .push_back(row(seconds, "cpu1", user * 10_f64));
+        }
+        // the minute is not complete until a row of the next minute exists.
+        data.roll_up();
+        assert!(data.rollups[0].avg.per_cpu.read().unwrap().is_empty());
+
+        data.per_cpu
+            .write()
+            .unwrap()
+            .push_back(row(60, "cpu0", 5_f64));
+        data.roll_up();
+        data.roll_up();
+        let rolled_up = |aggregate: Aggregate| {
+            data.rollups[0]
+                .aggregate(aggregate)
+                .per_cpu
+                .read()
+                .unwrap()
+                .iter()
+                .map(|row| (row.timestamp, row.cpu_name.clone(), row.user))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rolled_up(Aggregate::Min),
+            vec![
+                (start, "cpu0".into(), 1_f64),
+                (start, "cpu1".into(), 10_f64)
+            ]
+        );
+        assert_eq!(
+            rolled_up(Aggregate::Avg),
+            vec![
+                (start, "cpu0".into(), 4_f64),
+                (start, "cpu1".into(), 40_f64)
+            ]
+        );
+        assert_eq!(
+            rolled_up(Aggregate::Max),
+            vec![
+                (start, "cpu0".into(), 8_f64),
+                (start, "cpu1".into(), 80_f64)
+            ]
+        );
+        // the ten minutes are not complete yet.
+        assert!(data.rollups[1].avg.per_cpu.read().unwrap().is_empty());
+    }
+}
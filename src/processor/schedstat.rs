@@ -1,8 +1,37 @@
-use crate::processor::{single_statistic_u64, ProcData, Statistic};
+use crate::processor::{
+    push_to_history, single_statistic_u64, ProcData, ProcessorError, Statistic,
+};
+use crate::Data;
+use crate::DATA;
 use anyhow::Result;
+use chrono::{DateTime, Local};
 use log::debug;
 use proc_sys_parser::schedstat::ProcSchedStat;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// The scheduler statistics per second of a single CPU: the time running and waiting on the
+/// run queue in seconds, and the number of timeslices.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SchedStatInfo {
+    pub timestamp: DateTime<Local>,
+    pub cpu_name: String,
+    pub time_running: f64,
+    pub time_waiting: f64,
+    pub timeslices: f64,
+}
+
+impl SchedStatInfo {
+    /// The average time waiting on the run queue per timeslice in milliseconds.
+    pub fn wait_per_timeslice_ms(&self) -> f64 {
+        if self.timeslices == 0_f64 {
+            0_f64
+        } else {
+            self.time_waiting * 1000_f64 / self.timeslices
+        }
+    }
+}
 
 pub async fn read_schedstat_proc_data() -> Result<ProcSchedStat> {
     let proc_schedstat = proc_sys_parser::schedstat::read()?;
@@ -79,9 +108,57 @@ pub async fn process_schedstat_data(
     Ok(())
 }
 
+pub async fn add_schedstat_to_history(
+    statistics: &HashMap<(String, String, String), Statistic>,
+) -> Result<()> {
+    let cpu_list: BTreeSet<(usize, String)> = statistics
+        .iter()
+        .filter(|((group, cpu_name, name), statistic)| {
+            group == "schedstat"
+                && cpu_name.starts_with("cpu")
+                && name == "time_running"
+                && statistic.updated_value
+        })
+        .map(|((_, cpu_name, _), _)| {
+            (
+                cpu_name[3..].parse::<usize>().unwrap_or_default(),
+                cpu_name.clone(),
+            )
+        })
+        .collect();
+    for (_, cpu_name) in cpu_list {
+        let statistic = |name: &str| {
+            statistics
+                .get(&("schedstat".to_string(), cpu_name.clone(), name.to_string()))
+                .ok_or(ProcessorError::UnableToFindKeyInHashMap {
+                    hashmap: "statistics".to_string(),
+                    key1: "schedstat".to_string(),
+                    key2: cpu_name.clone(),
+                    key3: name.to_string(),
+                })
+        };
+        Data::push_schedstat(SchedStatInfo {
+            timestamp: statistic("time_running")?.last_timestamp,
+            cpu_name: cpu_name.clone(),
+            time_running: statistic("time_running")?.per_second_value / 1_000_000_000_f64,
+            time_waiting: statistic("time_waiting")?.per_second_value / 1_000_000_000_f64,
+            timeslices: statistic("timeslices")?.per_second_value,
+        })
+        .await;
+    }
+    Ok(())
+}
+
+impl Data {
+    pub async fn push_schedstat(schedstat: SchedStatInfo) {
+        push_to_history(&DATA.schedstat, schedstat);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::processor::Collector;
     use chrono::DateTime;
     use proc_sys_parser::block::{BlockDevice, SysBlock};
     use proc_sys_parser::meminfo::ProcMemInfo;
@@ -97,6 +174,7 @@ mod tests {
             timestamp: DateTime::parse_from_rfc3339("2023-12-13T15:20:24.291337737+00:00")
                 .unwrap()
                 .into(),
+            collectors: [Collector::Cpu, Collector::Schedstat].into(),
             stat: ProcStat {
                 cpu_total: CpuStat {
                     name: "cpu".to_string(),
@@ -450,4 +528,97 @@ mod tests {
             .unwrap();
         println!("{:#?}", statistics);
     }
+
+    #[tokio::test]
+    async fn schedstat_deltas_are_per_second_per_cpu_and_for_all() {
+        let start: DateTime<Local> = DateTime::parse_from_rfc3339("2023-12-13T15:20:24+00:00")
+            .unwrap()
+            .into();
+        let proc_data = |seconds: i64, cpu: Vec<Vec<u64>>| ProcData {
+            timestamp: start + chrono::TimeDelta::seconds(seconds),
+            collectors: [Collector::Schedstat].into(),
+            stat: Default::default(),
+            schedstat: ProcSchedStat {
+                cpu,
+                ..Default::default()
+            },
+            meminfo: Default::default(),
+            blockdevices: Default::default(),
+            net_dev: Default::default(),
+            loadavg: Default::default(),
+            pressure: Default::default(),
+            vmstat: Default::default(),
+            xfs: Default::default(),
+        };
+        let mut statistics: HashMap<(String, String, String), Statistic> = HashMap::new();
+        process_schedstat_data(
+            &proc_data(
+                0,
+                vec![
+                    vec![0, 0, 0, 0, 0, 0, 0, 1_000_000_000, 500_000_000, 100],
+                    vec![1, 0, 0, 0, 0, 0, 0, 2_000_000_000, 0, 200],
+                ],
+            ),
+            &mut statistics,
+        )
+        .await
+        .unwrap();
+        let statistic = |statistics: &HashMap<(String, String, String), Statistic>,
+                         cpu_name: &str,
+                         name: &str| {
+            let statistic = &statistics[&(
+                "schedstat".to_string(),
+                cpu_name.to_string(),
+                name.to_string(),
+            )];
+            (statistic.updated_value, statistic.per_second_value)
+        };
+        // the first read has no delta yet.
+        assert_eq!(
+            statistic(&statistics, "cpu0", "time_running"),
+            (false, 0_f64)
+        );
+
+        process_schedstat_data(
+            &proc_data(
+                2,
+                vec![
+                    vec![0, 0, 0, 0, 0, 0, 0, 2_000_000_000, 1_500_000_000, 300],
+                    vec![1, 0, 0, 0, 0, 0, 0, 2_000_000_000, 400_000_000, 240],
+                ],
+            ),
+            &mut statistics,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            statistic(&statistics, "cpu0", "time_running"),
+            (true, 500_000_000_f64)
+        );
+        assert_eq!(
+            statistic(&statistics, "cpu0", "time_waiting"),
+            (true, 500_000_000_f64)
+        );
+        assert_eq!(
+            statistic(&statistics, "cpu0", "timeslices"),
+            (true, 100_f64)
+        );
+        assert_eq!(
+            statistic(&statistics, "cpu1", "time_running"),
+            (true, 0_f64)
+        );
+        assert_eq!(
+            statistic(&statistics, "all", "time_waiting"),
+            (true, 700_000_000_f64)
+        );
+        assert_eq!(statistic(&statistics, "all", "timeslices"), (true, 120_f64));
+
+        let schedstat = SchedStatInfo {
+            time_waiting: 0.5,
+            timeslices: 100_f64,
+            ..Default::default()
+        };
+        assert_eq!(schedstat.wait_per_timeslice_ms(), 5_f64);
+        assert_eq!(SchedStatInfo::default().wait_per_timeslice_ms(), 0_f64);
+    }
 }
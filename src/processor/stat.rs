@@ -1,10 +1,11 @@
 use crate::add_list_of_u64_data_to_statistics;
 use crate::processor::{
-    single_statistic_option_u64, single_statistic_u64, ProcData, ProcessorError, Statistic,
+    push_to_history, single_statistic_option_u64, single_statistic_u64, ProcData, ProcessorError,
+    Statistic,
 };
 use crate::Data;
-use crate::ARGS;
 use crate::DATA;
+use crate::{timestamp_format, timestamp_header};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Local};
 use log::debug;
@@ -13,6 +14,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashMap};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct CpuStat {
     pub timestamp: DateTime<Local>,
     pub user: f64,
@@ -27,6 +29,54 @@ pub struct CpuStat {
     pub guest_nice: f64,
     pub scheduler_running: f64,
     pub scheduler_waiting: f64,
+    /// the process, context switch and interrupt statistics; not present in archives of older
+    /// versions.
+    pub processes: f64,
+    pub processes_running: f64,
+    pub processes_blocked: f64,
+    pub context_switches: f64,
+    pub interrupts: f64,
+}
+
+/// The CPU times per second of a single CPU.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PerCpuStat {
+    pub timestamp: DateTime<Local>,
+    pub cpu_name: String,
+    pub user: f64,
+    pub nice: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+    pub steal: f64,
+    pub guest: f64,
+    pub guest_nice: f64,
+}
+
+impl PerCpuStat {
+    /// The total time, which includes the guest times in user and nice.
+    pub fn total(&self) -> f64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+    /// The percentage of the total time of a time.
+    pub fn percentage(&self, time: f64) -> f64 {
+        let total = self.total();
+        if total == 0_f64 {
+            0_f64
+        } else {
+            time / total * 100_f64
+        }
+    }
 }
 
 pub async fn read_stat_proc_data() -> Result<ProcStat> {
@@ -119,12 +169,19 @@ pub async fn print_all_cpu(
             "sar-u" => {
                 println!(
                     "{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                    "Timestamp", "cpu", "%usr", "%nice", "%sys", "%iowait", "%steal", "%idle",
+                    timestamp_header("Timestamp"),
+                    "cpu",
+                    "%usr",
+                    "%nice",
+                    "%sys",
+                    "%iowait",
+                    "%steal",
+                    "%idle",
                 );
             }
             "sar-u-ALL" => {
                 println!("{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                         "Timestamp",
+                         timestamp_header("Timestamp"),
                          "cpu",
                          "%usr",
                          "%nice",
@@ -140,7 +197,7 @@ pub async fn print_all_cpu(
             }
             "cpu-all" => {
                 println!("{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                         "Timestamp",
+                         timestamp_header("Timestamp"),
                          "cpu",
                          "usr_s",
                          "nice_s",
@@ -159,7 +216,10 @@ pub async fn print_all_cpu(
             "sar-w" => {
                 println!(
                     "{:10} {:7}    {:>10} {:>10}",
-                    "Timestamp", "", "proc/s", "cswch/s",
+                    timestamp_header("Timestamp"),
+                    "",
+                    "proc/s",
+                    "cswch/s",
                 );
             }
             &_ => todo! {},
@@ -286,7 +346,7 @@ pub async fn print_all_cpu(
         "sar-u" => {
             println!(
                 "{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 "all",
                 user / total * 100_f64,
                 nice / total * 100_f64,
@@ -298,7 +358,7 @@ pub async fn print_all_cpu(
         }
         "sar-u-ALL" => {
             println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                     timestamp.format("%H:%M:%S"),
+                     timestamp.format(timestamp_format()),
                      "all",
                      user/total*100_f64,
                      nice/total*100_f64,
@@ -342,7 +402,7 @@ pub async fn print_all_cpu(
                 .per_second_value
                 / 1_000_000_f64;
             println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                     timestamp.format("%H:%M:%S"),
+                     timestamp.format(timestamp_format()),
                      "all",
                      user/1000_f64,
                      nice/1000_f64,
@@ -383,7 +443,7 @@ pub async fn print_all_cpu(
                 .per_second_value;
             println!(
                 "{:10} {:7}    {:10.2} {:10.2}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 "",
                 processes,
                 context_switches,
@@ -428,7 +488,7 @@ pub async fn print_per_cpu(
     match output {
         "mpstat-P-ALL" => {
             println!("{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                     "Timestamp",
+                     timestamp_header("Timestamp"),
                      "cpu",
                      "%usr",
                      "%nice",
@@ -444,7 +504,7 @@ pub async fn print_per_cpu(
         }
         "per-cpu-all" => {
             println!("{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                     "Timestamp",
+                     timestamp_header("Timestamp"),
                      "cpu",
                      "usr_s",
                      "nice_s",
@@ -463,11 +523,34 @@ pub async fn print_per_cpu(
         "schedstat" => {
             println!(
                 "{:10} {:7}    {:>10} {:>10} {:>10}",
-                "Timestamp", "cpu", "sched_w/s", "sched_r/s", "avg slice",
+                timestamp_header("Timestamp"),
+                "cpu",
+                "sched_w/s",
+                "sched_r/s",
+                "avg slice",
             );
         }
         &_ => todo! {},
     }
+    // mpstat-P-ALL doesn't show the scheduler statistics, which might not be collected.
+    let scheduler = |cpu_name: &str, name: &str| -> Result<f64> {
+        if output == "mpstat-P-ALL" {
+            return Ok(0_f64);
+        }
+        Ok(statistics
+            .get(&(
+                "schedstat".to_string(),
+                cpu_name.to_string(),
+                name.to_string(),
+            ))
+            .ok_or(ProcessorError::UnableToFindKeyInHashMap {
+                hashmap: "statistics".to_string(),
+                key1: "schedstat".to_string(),
+                key2: cpu_name.to_string(),
+                key3: name.to_string(),
+            })?
+            .per_second_value)
+    };
     for cpu_name in cpu_list {
         let timestamp = statistics
             .get(&("stat".to_string(), cpu_name.to_string(), "user".to_string()))
@@ -594,47 +677,9 @@ pub async fn print_per_cpu(
             .per_second_value;
         let total =
             user + nice + system + iowait + steal + irq + softirq + guest_user + guest_nice + idle;
-        let scheduler_running = statistics
-            .get(&(
-                "schedstat".to_string(),
-                cpu_name.to_string(),
-                "time_running".to_string(),
-            ))
-            .ok_or(ProcessorError::UnableToFindKeyInHashMap {
-                hashmap: "statistics".to_string(),
-                key1: "schedstat".to_string(),
-                key2: cpu_name.to_string(),
-                key3: "time_running".to_string(),
-            })?
-            .per_second_value
-            / 1_000_000_f64;
-        let scheduler_waiting = statistics
-            .get(&(
-                "schedstat".to_string(),
-                cpu_name.to_string(),
-                "time_waiting".to_string(),
-            ))
-            .ok_or(ProcessorError::UnableToFindKeyInHashMap {
-                hashmap: "statistics".to_string(),
-                key1: "schedstat".to_string(),
-                key2: cpu_name.to_string(),
-                key3: "time_waiting".to_string(),
-            })?
-            .per_second_value
-            / 1_000_000_f64;
-        let scheduler_slices = statistics
-            .get(&(
-                "schedstat".to_string(),
-                cpu_name.to_string(),
-                "timeslices".to_string(),
-            ))
-            .ok_or(ProcessorError::UnableToFindKeyInHashMap {
-                hashmap: "statistics".to_string(),
-                key1: "schedstat".to_string(),
-                key2: cpu_name.to_string(),
-                key3: "timeslices".to_string(),
-            })?
-            .per_second_value;
+        let scheduler_running = scheduler(cpu_name, "time_running")? / 1_000_000_f64;
+        let scheduler_waiting = scheduler(cpu_name, "time_waiting")? / 1_000_000_f64;
+        let scheduler_slices = scheduler(cpu_name, "timeslices")?;
         let scheduler_slice_avg_length = if scheduler_slices == 0_f64 {
             0_f64
         } else {
@@ -643,7 +688,7 @@ pub async fn print_per_cpu(
         match output {
             "mpstat-P-ALL" => {
                 println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                         timestamp.format("%H:%M:%S"),
+                         timestamp.format(timestamp_format()),
                          cpu_name,
                          user / total * 100_f64,
                          nice / total * 100_f64,
@@ -659,7 +704,7 @@ pub async fn print_per_cpu(
             }
             "per-cpu-all" => {
                 println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                         timestamp.format("%H:%M:%S"),
+                         timestamp.format(timestamp_format()),
                          cpu_name,
                          user / 1000_f64,
                          nice / 1000_f64,
@@ -678,7 +723,7 @@ pub async fn print_per_cpu(
             "schedstat" => {
                 println!(
                     "{:10} {:7}    {:10.2} {:10.2} {:10.6}",
-                    timestamp.format("%H:%M:%S"),
+                    timestamp.format(timestamp_format()),
                     cpu_name,
                     scheduler_waiting / 1000_f64,
                     scheduler_running / 1000_f64,
@@ -691,6 +736,8 @@ pub async fn print_per_cpu(
     Ok(())
 }
 
+/// The scheduler statistics are the last ones that are read, because schedstat can have its own
+/// interval, and zero if schedstat isn't read.
 pub async fn add_cpu_total_to_history(
     statistics: &HashMap<(String, String, String), Statistic>,
 ) -> Result<()> {
@@ -820,36 +867,12 @@ pub async fn add_cpu_total_to_history(
         .per_second_value
         / 1000_f64;
     //let total = user+nice+system+iowait+steal+irq+softirq+guest_user+guest_nice+idle;
-    let scheduler_running = statistics
-        .get(&(
-            "schedstat".to_string(),
-            "all".to_string(),
-            "time_running".to_string(),
-        ))
-        .ok_or(ProcessorError::UnableToFindKeyInHashMap {
-            hashmap: "statistics".to_string(),
-            key1: "schedstat".to_string(),
-            key2: "all".to_string(),
-            key3: "time_running".to_string(),
-        })?
-        .per_second_value
-        / 1_000_000_f64
-        / 1000_f64;
-    let scheduler_waiting = statistics
-        .get(&(
-            "schedstat".to_string(),
-            "all".to_string(),
-            "time_waiting".to_string(),
-        ))
-        .ok_or(ProcessorError::UnableToFindKeyInHashMap {
-            hashmap: "statistics".to_string(),
-            key1: "schedstat".to_string(),
-            key2: "all".to_string(),
-            key3: "time_waiting".to_string(),
-        })?
-        .per_second_value
-        / 1_000_000_f64
-        / 1000_f64;
+    let schedstat = |name: &str| {
+        statistic_of(statistics, "schedstat", "all", name)
+            .map_or(0_f64, |statistic| statistic.per_second_value)
+    };
+    let scheduler_running = schedstat("time_running") / 1_000_000_f64 / 1000_f64;
+    let scheduler_waiting = schedstat("time_waiting") / 1_000_000_f64 / 1000_f64;
     Data::push_cpu(CpuStat {
         timestamp,
         user,
@@ -864,6 +887,12 @@ pub async fn add_cpu_total_to_history(
         guest_nice,
         scheduler_running,
         scheduler_waiting,
+        processes: statistic_of(statistics, "stat", "", "processes")?.per_second_value,
+        processes_running: statistic_of(statistics, "stat", "", "processes_running")?.last_value,
+        processes_blocked: statistic_of(statistics, "stat", "", "processes_blocked")?.last_value,
+        context_switches: statistic_of(statistics, "stat", "", "context_switches")?
+            .per_second_value,
+        interrupts: statistic_of(statistics, "stat", "", "interrupts_total")?.per_second_value,
     })
     .await;
     /*
@@ -886,11 +915,70 @@ pub async fn add_cpu_total_to_history(
     Ok(())
 }
 
+fn statistic_of<'a>(
+    statistics: &'a HashMap<(String, String, String), Statistic>,
+    group: &str,
+    subcategory: &str,
+    name: &str,
+) -> Result<&'a Statistic, ProcessorError> {
+    statistics
+        .get(&(group.to_string(), subcategory.to_string(), name.to_string()))
+        .ok_or(ProcessorError::UnableToFindKeyInHashMap {
+            hashmap: "statistics".to_string(),
+            key1: group.to_string(),
+            key2: subcategory.to_string(),
+            key3: name.to_string(),
+        })
+}
+
+/// The scheduler statistics are the last ones that are read, because schedstat can have its own
+/// interval, and zero if schedstat isn't read.
+pub async fn add_per_cpu_to_history(
+    statistics: &HashMap<(String, String, String), Statistic>,
+) -> Result<()> {
+    let cpu_list: BTreeSet<(usize, String)> = statistics
+        .keys()
+        .filter(|(group, cpu_name, name)| {
+            group == "stat" && cpu_name.starts_with("cpu") && name == "user"
+        })
+        .filter(|(group, cpu_name, name)| {
+            statistics
+                .get(&(group.clone(), cpu_name.clone(), name.clone()))
+                .is_some_and(|statistic| statistic.updated_value)
+        })
+        .map(|(_, cpu_name, _)| {
+            (
+                cpu_name[3..].parse::<usize>().unwrap_or_default(),
+                cpu_name.clone(),
+            )
+        })
+        .collect();
+    for (_, cpu_name) in cpu_list {
+        let statistic = |name: &str| statistic_of(statistics, "stat", &cpu_name, name);
+        Data::push_per_cpu(PerCpuStat {
+            timestamp: statistic("user")?.last_timestamp,
+            cpu_name: cpu_name.clone(),
+            user: statistic("user")?.per_second_value / 1000_f64,
+            nice: statistic("nice")?.per_second_value / 1000_f64,
+            system: statistic("system")?.per_second_value / 1000_f64,
+            idle: statistic("idle")?.per_second_value / 1000_f64,
+            iowait: statistic("iowait")?.per_second_value / 1000_f64,
+            irq: statistic("irq")?.per_second_value / 1000_f64,
+            softirq: statistic("softirq")?.per_second_value / 1000_f64,
+            steal: statistic("steal")?.per_second_value / 1000_f64,
+            guest: statistic("guest")?.per_second_value / 1000_f64,
+            guest_nice: statistic("guest_nice")?.per_second_value / 1000_f64,
+        })
+        .await;
+    }
+    Ok(())
+}
+
 impl Data {
+    pub async fn push_per_cpu(per_cpu_stat: PerCpuStat) {
+        push_to_history(&DATA.per_cpu, per_cpu_stat);
+    }
     pub async fn push_cpu(cpustat: CpuStat) {
-        while DATA.cpu.read().unwrap().len() >= ARGS.history {
-            DATA.cpu.write().unwrap().pop_front();
-        }
-        DATA.cpu.write().unwrap().push_back(cpustat);
+        push_to_history(&DATA.cpu, cpustat);
     }
 }
@@ -1,10 +1,14 @@
+use crate::processor::blockdevice::{
+    blockdevice_topology, is_total, total_name, BlockDeviceTopology,
+};
 use crate::processor::{
-    single_statistic_option_u64, single_statistic_u64, ProcData, ProcessorError, Statistic,
+    push_to_history, single_statistic_option_u64, single_statistic_u64, ProcData, ProcessorError,
+    Statistic,
 };
 use crate::Data;
-use crate::ARGS;
 use crate::DATA;
 use crate::{add_list_of_option_u64_data_to_statistics, add_list_of_u64_data_to_statistics};
+use crate::{timestamp_format, timestamp_header};
 use anyhow::Result;
 use chrono::{DateTime, Local};
 use log::debug;
@@ -13,6 +17,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashMap};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct VmStatInfo {
     pub timestamp: DateTime<Local>,
     pub nr_free_pages: f64,
@@ -844,11 +849,58 @@ pub async fn add_vmstat_to_history(
 
 impl Data {
     pub async fn push_vmstat(vmstat: VmStatInfo) {
-        while DATA.vmstat.read().unwrap().len() >= ARGS.history {
-            DATA.vmstat.write().unwrap().pop_front();
-        }
-        DATA.vmstat.write().unwrap().push_back(vmstat);
+        push_to_history(&DATA.vmstat, vmstat);
+    }
+}
+
+/// The sectors read and written per second of the devices that are added to the TOTAL device,
+/// so the IO of partitions and of devices built on other devices is counted once.
+fn total_sectors(
+    statistics: &HashMap<(String, String, String), Statistic>,
+    topology_of: impl Fn(&str) -> BlockDeviceTopology + Copy,
+) -> Result<(f64, f64)> {
+    let disk_list: Vec<_> = statistics
+        .keys()
+        .filter(|(group, _, _)| group == "blockdevice")
+        .map(|(_, disk_name, _)| disk_name)
+        .filter(|disk_name| {
+            !is_total(disk_name)
+                && total_name(&topology_of(disk_name), topology_of, false) == Some("TOTAL")
+        })
+        .collect::<BTreeSet<&String>>()
+        .into_iter()
+        .collect();
+    let mut total_reads_sectors = 0_f64;
+    let mut total_writes_sectors = 0_f64;
+    for disk_name in &disk_list {
+        total_reads_sectors += statistics
+            .get(&(
+                "blockdevice".to_string(),
+                disk_name.to_string(),
+                "stat_reads_sectors".to_string(),
+            ))
+            .ok_or(ProcessorError::UnableToFindKeyInHashMap {
+                hashmap: "statistics".to_string(),
+                key1: "blockdevice".to_string(),
+                key2: disk_name.to_string(),
+                key3: "stat_reads_sectors".to_string(),
+            })?
+            .per_second_value;
+        total_writes_sectors += statistics
+            .get(&(
+                "blockdevice".to_string(),
+                disk_name.to_string(),
+                "stat_writes_sectors".to_string(),
+            ))
+            .ok_or(ProcessorError::UnableToFindKeyInHashMap {
+                hashmap: "statistics".to_string(),
+                key1: "blockdevice".to_string(),
+                key2: disk_name.to_string(),
+                key3: "stat_writes_sectors".to_string(),
+            })?
+            .per_second_value;
     }
+    Ok((total_reads_sectors, total_writes_sectors))
 }
 
 pub async fn print_vmstat(
@@ -861,7 +913,7 @@ pub async fn print_vmstat(
             //https://github.com/sysstat/sysstat/blob/499f5b153e9707892bb8841d37e6ed3a0aa617e2/rd_stats.c#L737
             "sar-B" => {
                 println!("{:10} {:7}    {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                         "Timestamp",
+                         timestamp_header("Timestamp"),
                          "cpu",
                          "pgpgin/s",
                          "pgpgout/s",
@@ -878,13 +930,16 @@ pub async fn print_vmstat(
             "sar-W" => {
                 println!(
                     "{:10} {:7}    {:>10} {:>10}",
-                    "Timestamp", "", "pswpin/s", "pswpout/s",
+                    timestamp_header("Timestamp"),
+                    "",
+                    "pswpin/s",
+                    "pswpout/s",
                 );
             }
             "vmstat" => {
                 println!(
                     "{:10} {:9} {:35} {:17} {:17} {:17} {:25}",
-                    "",
+                    timestamp_header(""),
                     "--procs--",
                     "------------memory (mb)------------",
                     "-------swap------",
@@ -893,7 +948,7 @@ pub async fn print_vmstat(
                     "--------------cpu------------",
                 );
                 println!("{:10} {:>4} {:>4} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>4} {:>4} {:>4} {:>4} {:>4} {:>4}",
-                    "Timestamp",
+                    timestamp_header("Timestamp"),
                     "r",
                     "b",
                     "swpd",
@@ -920,7 +975,13 @@ pub async fn print_vmstat(
                 // Swap:              0           0           0
                 println!(
                     "{:10}       {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
-                    "Timestamp", "Total", "Used", "Free", "Shared", "Buff/cache", "Available",
+                    timestamp_header("Timestamp"),
+                    "Total",
+                    "Used",
+                    "Free",
+                    "Shared",
+                    "Buff/cache",
+                    "Available",
                 );
             }
             &_ => todo! {},
@@ -1078,43 +1139,8 @@ pub async fn print_vmstat(
             key3: "memavailable".to_string(),
         })?
         .last_value;
-    let disk_list: Vec<_> = statistics
-        .keys()
-        .filter(|(group, _, _)| group == "blockdevice")
-        .map(|(_, disk_name, _)| disk_name)
-        .collect::<BTreeSet<&String>>()
-        .into_iter()
-        .collect();
-    let mut total_reads_sectors = 0_f64;
-    let mut total_writes_sectors = 0_f64;
-    for disk_name in &disk_list {
-        total_reads_sectors += statistics
-            .get(&(
-                "blockdevice".to_string(),
-                disk_name.to_string(),
-                "stat_reads_sectors".to_string(),
-            ))
-            .ok_or(ProcessorError::UnableToFindKeyInHashMap {
-                hashmap: "statistics".to_string(),
-                key1: "blockdevice".to_string(),
-                key2: disk_name.to_string(),
-                key3: "stat_reads_sectors".to_string(),
-            })?
-            .per_second_value;
-        total_writes_sectors += statistics
-            .get(&(
-                "blockdevice".to_string(),
-                disk_name.to_string(),
-                "stat_writes_sectors".to_string(),
-            ))
-            .ok_or(ProcessorError::UnableToFindKeyInHashMap {
-                hashmap: "statistics".to_string(),
-                key1: "blockdevice".to_string(),
-                key2: disk_name.to_string(),
-                key3: "stat_writes_sectors".to_string(),
-            })?
-            .per_second_value;
-    }
+    let (total_reads_sectors, total_writes_sectors) =
+        total_sectors(statistics, blockdevice_topology)?;
     let interrupts = statistics
         .get(&(
             "stat".to_string(),
@@ -1241,7 +1267,7 @@ pub async fn print_vmstat(
     match output {
         "sar-B" => {
             println!("{:10} {:7}    {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2} {:10.2}",
-                     timestamp.format("%H:%M:%S"),
+                     timestamp.format(timestamp_format()),
                      "all",
                     pgpgin,
                     pgpgout,
@@ -1259,7 +1285,7 @@ pub async fn print_vmstat(
         "sar-W" => {
             println!(
                 "{:10} {:7}    {:10.2} {:10.2}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 "",
                 pswpin,
                 pswpout,
@@ -1267,8 +1293,8 @@ pub async fn print_vmstat(
         }
         "vmstat" => {
             println!("{:10} {:4.0} {:4.0} {:8.0} {:8.0} {:8.0} {:8.0} {:8.0} {:8.0} {:8.0} {:8.0} {:8.0} {:8.0} {:4.0} {:4.0} {:4.0} {:4.0} {:4.0} {:4.0}",
-                timestamp.format("%H:%M:%S"),
-                processes_running - 1_f64, // not count ourselves
+                timestamp.format(timestamp_format()),
+                (processes_running - 1_f64).max(0_f64), // not count ourselves
                 processes_blocked,
                 (swap_total-swap_free).max(0_f64) / 1024_f64,
                 mem_free / 1024_f64,
@@ -1294,7 +1320,7 @@ pub async fn print_vmstat(
             // Swap:              0           0           0
             println!(
                 "{:10} Mem:  {:>10.0} {:>10.0} {:>10.0} {:>10.0} {:>10.0} {:>10.0}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 mem_total / 1024_f64,
                 // it turns out there are multiple explanations of how 'used' is calculated
                 // .. and so far none of them create the same value as 'used' with the free command
@@ -1307,7 +1333,7 @@ pub async fn print_vmstat(
             );
             println!(
                 "{:10} Swap: {:>10.0} {:>10.0} {:>10.0}",
-                timestamp.format("%H:%M:%S"),
+                timestamp.format(timestamp_format()),
                 swap_total / 1024_f64,
                 (swap_total - swap_free).max(0_f64) / 1024_f64,
                 swap_free / 1024_f64,
@@ -1317,3 +1343,57 @@ pub async fn print_vmstat(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::blockdevice::BlockDeviceLayer;
+
+    #[test]
+    fn io_of_a_device_stacked_on_a_disk_is_counted_once() {
+        // vg0-root is built on sda, so its IO is the IO of sda.
+        let topology = HashMap::from([
+            (
+                "sda".to_string(),
+                BlockDeviceTopology {
+                    holders: vec!["vg0-root".to_string()],
+                    ..Default::default()
+                },
+            ),
+            (
+                "vg0-root".to_string(),
+                BlockDeviceTopology {
+                    kernel_name: "dm-0".to_string(),
+                    layer: BlockDeviceLayer::DeviceMapper,
+                    slaves: vec!["sda".to_string()],
+                    holders: vec![],
+                },
+            ),
+        ]);
+        let topology_of =
+            |device_name: &str| topology.get(device_name).cloned().unwrap_or_default();
+        let mut statistics = HashMap::new();
+        for device_name in ["sda", "vg0-root"] {
+            for (name, per_second_value) in [
+                ("stat_reads_sectors", 8_f64),
+                ("stat_writes_sectors", 16_f64),
+            ] {
+                statistics.insert(
+                    (
+                        "blockdevice".to_string(),
+                        device_name.to_string(),
+                        name.to_string(),
+                    ),
+                    Statistic {
+                        per_second_value,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        assert_eq!(
+            total_sectors(&statistics, topology_of).unwrap(),
+            (8_f64, 16_f64)
+        );
+    }
+}
@@ -1,19 +1,20 @@
 #![allow(unused_assignments)]
 use crate::add_list_of_option_u64_data_to_statistics;
-use crate::processor::{single_statistic_option_u64, ProcData, Statistic};
+use crate::processor::{push_to_history, single_statistic_option_u64, ProcData, Statistic};
 use crate::Data;
-use crate::ARGS;
 use crate::DATA;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::{DateTime, Local};
 use log::debug;
 use proc_sys_parser::fs_xfs_stat::ProcFsXfsStat;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs::metadata;
 
 use super::ProcessorError;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct XfsInfo {
     pub timestamp: DateTime<Local>,
     pub xs_write_calls: f64,
@@ -22,10 +23,13 @@ pub struct XfsInfo {
     pub xs_read_bytes: f64,
 }
 
-pub async fn read_xfs_proc_data() -> ProcFsXfsStat {
+/// The reader of proc_sys_parser returns empty statistics if the file can't be read, which is when
+/// the xfs module isn't loaded, so the existence of the file is checked first.
+pub async fn read_xfs_proc_data() -> Result<ProcFsXfsStat> {
+    metadata("/proc/fs/xfs/stat").with_context(|| "Unable to read /proc/fs/xfs/stat")?;
     let proc_xfs_stats = proc_sys_parser::fs_xfs_stat::read();
     debug!("{:?}", proc_xfs_stats);
-    proc_xfs_stats
+    Ok(proc_xfs_stats)
 }
 
 pub async fn process_xfs_data(
@@ -157,9 +161,6 @@ pub async fn add_xfs_to_history(
 
 impl Data {
     pub async fn push_xfs(xfs: XfsInfo) {
-        while DATA.xfs.read().unwrap().len() >= ARGS.history {
-            DATA.xfs.write().unwrap().pop_front();
-        }
-        DATA.xfs.write().unwrap().push_back(xfs);
+        push_to_history(&DATA.xfs, xfs);
     }
 }
@@ -0,0 +1,476 @@
+//! Print the statistics of archives as CLI output, like `sar -f`.
+//!
+//! The history rows hold the per second values of the counters and the values of the gauges.
+//! For every timestamp of the archives, the rows are turned back into the statistics that the
+//! CLI output functions print. With an interval, the timestamps within the interval are
+//! aggregated first: the per second values are averaged, and the last value is taken.
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::RwLock;
+
+use crate::app::print_statistics;
+use crate::archiver::load_archives;
+use crate::processor::blockdevice::{is_total, set_blockdevice_topology_from_history};
+use crate::processor::{Collector, Data, Statistic};
+use crate::{set_subsecond_timestamps, ReplayOpts, ARGS, DATA};
+
+type Statistics = HashMap<(String, String, String), Statistic>;
+
+/// Read the archives, and print the output for every timestamp, or every interval, between the
+/// start and end time.
+pub async fn replay(replay_opts: &ReplayOpts) -> Result<()> {
+    load_archives(&replay_opts.archives, replay_opts.allow_mixed_hosts)?;
+    // the collectors can have their own interval, so the output is printed for the timestamps at
+    // which all its collectors have rows.
+    let all_timestamps = replay_opts
+        .output
+        .collectors()
+        .iter()
+        .map(|collector| collector_timestamps(*collector))
+        .reduce(|shared, timestamps| &shared & &timestamps)
+        .unwrap_or_default()
+        .into_iter()
+        .collect::<Vec<DateTime<Local>>>();
+    let in_range = |timestamp: &DateTime<Local>| {
+        replay_opts
+            .start
+            .is_none_or(|start_time| *timestamp >= start_time)
+            && replay_opts
+                .end
+                .is_none_or(|end_time| *timestamp <= end_time)
+    };
+    let timestamps = all_timestamps
+        .iter()
+        .copied()
+        .zip(durations(&all_timestamps))
+        .filter(|(timestamp, _)| in_range(timestamp))
+        .collect::<Vec<(DateTime<Local>, f64)>>();
+    if timestamps.is_empty() {
+        bail!("No data found between the start and end time.");
+    }
+    // the timestamps are printed with milliseconds for archives with an interval under one second.
+    if replay_opts.interval.is_none() {
+        let mut durations = timestamps
+            .iter()
+            .map(|(_, duration)| *duration)
+            .collect::<Vec<f64>>();
+        durations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        set_subsecond_timestamps(durations[durations.len() / 2]);
+    }
+    replay_opts.output.check_rows(&DATA)?;
+    set_blockdevice_topology_from_history(
+        &DATA.blockdevices.read().unwrap().iter().collect::<Vec<_>>(),
+    );
+
+    let history = HistoryIndex::new(&DATA);
+    for (output_counter, rows) in intervals(&timestamps, replay_opts.interval)
+        .into_iter()
+        .enumerate()
+    {
+        let statistics = aggregate(
+            rows.iter()
+                .map(|(timestamp, duration)| {
+                    (history.statistics(&DATA, *timestamp, *duration), *duration)
+                })
+                .collect(),
+        );
+        let print_header = (output_counter as u64).is_multiple_of(ARGS.header_print);
+        print_statistics(&statistics, replay_opts.output, print_header).await?;
+    }
+    Ok(())
+}
+
+/// The timestamps of the history rows of a collector.
+fn collector_timestamps(collector: Collector) -> BTreeSet<DateTime<Local>> {
+    fn timestamps<T>(
+        rows: &RwLock<VecDeque<T>>,
+        timestamp: fn(&T) -> DateTime<Local>,
+    ) -> BTreeSet<DateTime<Local>> {
+        rows.read().unwrap().iter().map(timestamp).collect()
+    }
+    match collector {
+        // the scheduler statistics are stored with the cpu statistics.
+        Collector::Cpu | Collector::Schedstat => timestamps(&DATA.cpu, |r| r.timestamp),
+        Collector::Memory => timestamps(&DATA.memory, |r| r.timestamp),
+        Collector::Disk => timestamps(&DATA.blockdevices, |r| r.timestamp),
+        Collector::Network => timestamps(&DATA.networkdevices, |r| r.timestamp),
+        Collector::Loadavg => timestamps(&DATA.loadavg, |r| r.timestamp),
+        Collector::Pressure => timestamps(&DATA.pressure, |r| r.timestamp),
+        Collector::Vmstat => timestamps(&DATA.vmstat, |r| r.timestamp),
+        Collector::Xfs => timestamps(&DATA.xfs, |r| r.timestamp),
+    }
+}
+
+/// The time in seconds that every timestamp covers: the time since the previous timestamp. For
+/// the first timestamp, and after a gap in the archives (a gap of more than twice the usual
+/// time), the usual time between the timestamps is taken.
+fn durations(timestamps: &[DateTime<Local>]) -> Vec<f64> {
+    let mut gaps = timestamps
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).num_milliseconds() as f64 / 1000_f64)
+        .collect::<Vec<f64>>();
+    let mut sorted_gaps = gaps.clone();
+    sorted_gaps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let usual_gap = sorted_gaps
+        .get(sorted_gaps.len() / 2)
+        .copied()
+        .unwrap_or(ARGS.interval);
+    gaps.insert(0, usual_gap);
+    gaps.into_iter()
+        .map(|gap| {
+            if gap > 2_f64 * usual_gap {
+                usual_gap
+            } else {
+                gap
+            }
+        })
+        .collect()
+}
+
+/// The timestamps grouped per interval of seconds since the first timestamp, or every timestamp
+/// on its own without an interval.
+fn intervals(
+    timestamps: &[(DateTime<Local>, f64)],
+    interval: Option<u64>,
+) -> Vec<Vec<(DateTime<Local>, f64)>> {
+    let Some(interval) = interval.filter(|interval| *interval > 0) else {
+        return timestamps.iter().map(|row| vec![*row]).collect();
+    };
+    let first_timestamp = timestamps.first().map(|(timestamp, _)| *timestamp);
+    let mut intervals: BTreeMap<i64, Vec<(DateTime<Local>, f64)>> = BTreeMap::new();
+    for (timestamp, duration) in timestamps {
+        let number = first_timestamp.map_or(0, |first_timestamp| {
+            (*timestamp - first_timestamp).num_seconds() / interval as i64
+        });
+        intervals
+            .entry(number)
+            .or_default()
+            .push((*timestamp, *duration));
+    }
+    intervals.into_values().collect()
+}
+
+/// The statistics of the timestamps of an interval, with the per second value as the average
+/// over the duration of the timestamps, and the last value and timestamp of the last timestamp.
+fn aggregate(rows: Vec<(Statistics, f64)>) -> Statistics {
+    if rows.len() == 1 {
+        return rows.into_iter().next().unwrap().0;
+    }
+    let mut totals: HashMap<(String, String, String), (Statistic, f64)> = HashMap::new();
+    for (statistics, duration) in rows {
+        for (key, statistic) in statistics {
+            let (total, total_duration) = totals.entry(key).or_default();
+            total.last_timestamp = statistic.last_timestamp;
+            total.last_value = statistic.last_value;
+            total.delta_value += statistic.delta_value;
+            total.per_second_value += statistic.per_second_value * duration;
+            total.updated_value = true;
+            *total_duration += duration;
+        }
+    }
+    totals
+        .into_iter()
+        .map(|(key, (mut statistic, duration))| {
+            if duration > 0_f64 {
+                statistic.per_second_value /= duration;
+            }
+            (key, statistic)
+        })
+        .collect()
+}
+
+/// The numeric fields of a history row, by field name.
+fn row_values<T: Serialize>(row: &T) -> Vec<(String, f64)> {
+    match serde_json::to_value(row) {
+        Ok(serde_json::Value::Object(fields)) => fields
+            .into_iter()
+            .filter_map(|(name, value)| value.as_f64().map(|value| (name, value)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The rows of every history by timestamp.
+struct HistoryIndex {
+    cpu: BTreeMap<DateTime<Local>, Vec<usize>>,
+    per_cpu: BTreeMap<DateTime<Local>, Vec<usize>>,
+    schedstat: BTreeMap<DateTime<Local>, Vec<usize>>,
+    memory: BTreeMap<DateTime<Local>, Vec<usize>>,
+    blockdevices: BTreeMap<DateTime<Local>, Vec<usize>>,
+    networkdevices: BTreeMap<DateTime<Local>, Vec<usize>>,
+    loadavg: BTreeMap<DateTime<Local>, Vec<usize>>,
+    pressure: BTreeMap<DateTime<Local>, Vec<usize>>,
+    vmstat: BTreeMap<DateTime<Local>, Vec<usize>>,
+}
+
+fn index<T>(
+    rows: &VecDeque<T>,
+    timestamp: fn(&T) -> DateTime<Local>,
+) -> BTreeMap<DateTime<Local>, Vec<usize>> {
+    let mut index: BTreeMap<DateTime<Local>, Vec<usize>> = BTreeMap::new();
+    for (number, row) in rows.iter().enumerate() {
+        index.entry(timestamp(row)).or_default().push(number);
+    }
+    index
+}
+
+impl HistoryIndex {
+    fn new(data: &Data) -> Self {
+        HistoryIndex {
+            cpu: index(&data.cpu.read().unwrap(), |r| r.timestamp),
+            per_cpu: index(&data.per_cpu.read().unwrap(), |r| r.timestamp),
+            schedstat: index(&data.schedstat.read().unwrap(), |r| r.timestamp),
+            memory: index(&data.memory.read().unwrap(), |r| r.timestamp),
+            blockdevices: index(&data.blockdevices.read().unwrap(), |r| r.timestamp),
+            networkdevices: index(&data.networkdevices.read().unwrap(), |r| r.timestamp),
+            loadavg: index(&data.loadavg.read().unwrap(), |r| r.timestamp),
+            pressure: index(&data.pressure.read().unwrap(), |r| r.timestamp),
+            vmstat: index(&data.vmstat.read().unwrap(), |r| r.timestamp),
+        }
+    }
+
+    /// The statistics of all history rows at the timestamp, in the units of the proc and sys
+    /// files, as the processor functions create them. The data is the one the index is built from.
+    fn statistics(&self, data: &Data, timestamp: DateTime<Local>, duration: f64) -> Statistics {
+        let mut statistics = Statistics::new();
+        let mut add = |group: &str, subcategory: &str, name: &str, value: f64| {
+            statistics.insert(
+                (group.to_string(), subcategory.to_string(), name.to_string()),
+                Statistic {
+                    last_timestamp: timestamp,
+                    last_value: value,
+                    delta_value: value * duration,
+                    per_second_value: value,
+                    updated_value: true,
+                },
+            );
+        };
+        let rows = |index: &BTreeMap<DateTime<Local>, Vec<usize>>| {
+            index.get(&timestamp).cloned().unwrap_or_default()
+        };
+
+        // the cpu times are stored in seconds, and the scheduler times in seconds per second.
+        let cpu_values = |values: Vec<(String, f64)>| {
+            values
+                .into_iter()
+                .filter_map(|(name, value)| match name.as_str() {
+                    "scheduler_running" => Some((
+                        "schedstat",
+                        "time_running".to_string(),
+                        value * 1_000_000_000_f64,
+                    )),
+                    "scheduler_waiting" => Some((
+                        "schedstat",
+                        "time_waiting".to_string(),
+                        value * 1_000_000_000_f64,
+                    )),
+                    "processes" | "processes_running" | "processes_blocked"
+                    | "context_switches" | "interrupts" => None,
+                    _ => Some(("stat", name, value * 1000_f64)),
+                })
+                .collect::<Vec<(&str, String, f64)>>()
+        };
+        let per_cpu = data.per_cpu.read().unwrap();
+        for number in rows(&self.per_cpu) {
+            let row = &per_cpu[number];
+            for (group, name, value) in cpu_values(row_values(row)) {
+                add(group, &row.cpu_name, &name, value);
+            }
+        }
+        let schedstat = data.schedstat.read().unwrap();
+        let mut timeslices = 0_f64;
+        for number in rows(&self.schedstat) {
+            let row = &schedstat[number];
+            timeslices += row.timeslices;
+            add(
+                "schedstat",
+                &row.cpu_name,
+                "time_running",
+                row.time_running * 1_000_000_000_f64,
+            );
+            add(
+                "schedstat",
+                &row.cpu_name,
+                "time_waiting",
+                row.time_waiting * 1_000_000_000_f64,
+            );
+            add("schedstat", &row.cpu_name, "timeslices", row.timeslices);
+        }
+        let cpu = data.cpu.read().unwrap();
+        for number in rows(&self.cpu) {
+            let row = &cpu[number];
+            for (group, name, value) in cpu_values(row_values(row)) {
+                add(group, "all", &name, value);
+            }
+            add("schedstat", "all", "timeslices", timeslices);
+            add("stat", "", "processes", row.processes);
+            add("stat", "", "processes_running", row.processes_running);
+            add("stat", "", "processes_blocked", row.processes_blocked);
+            add("stat", "", "context_switches", row.context_switches);
+            add("stat", "", "interrupts_total", row.interrupts);
+        }
+
+        let memory = data.memory.read().unwrap();
+        for number in rows(&self.memory) {
+            let row = &memory[number];
+            for (name, value) in row_values(row) {
+                let name = match name.as_str() {
+                    "hugepages_reserved" => "hugepages_rsvd",
+                    "hugepages_surplus" => "hugepages_surp",
+                    name => name,
+                };
+                add("meminfo", "", name, value);
+            }
+            add("meminfo", "", "active", row.active_anon + row.active_file);
+            add(
+                "meminfo",
+                "",
+                "inactive",
+                row.inactive_anon + row.inactive_file,
+            );
+        }
+
+        // the blockdevice statistics are stored without the stat_ prefix, and in bytes instead
+        // of sectors.
+        let blockdevices = data.blockdevices.read().unwrap();
+        for number in rows(&self.blockdevices) {
+            let row = &blockdevices[number];
+            if is_total(&row.device_name) {
+                continue;
+            }
+            for (name, value) in row_values(row) {
+                if ["reads_", "writes_", "discards_", "ios_", "flush_requests_"]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+                {
+                    match name.strip_suffix("_bytes") {
+                        Some(name) => add(
+                            "blockdevice",
+                            &row.device_name,
+                            &format!("stat_{}_sectors", name),
+                            value / 512_f64,
+                        ),
+                        None => add(
+                            "blockdevice",
+                            &row.device_name,
+                            &format!("stat_{}", name),
+                            value,
+                        ),
+                    }
+                } else {
+                    add("blockdevice", &row.device_name, &name, value);
+                }
+            }
+        }
+
+        let networkdevices = data.networkdevices.read().unwrap();
+        for number in rows(&self.networkdevices) {
+            let row = &networkdevices[number];
+            if row.device_name == "TOTAL" {
+                continue;
+            }
+            for (name, value) in row_values(row) {
+                add("net_dev", &row.device_name, &name, value);
+            }
+        }
+
+        let loadavg = data.loadavg.read().unwrap();
+        for number in rows(&self.loadavg) {
+            for (name, value) in row_values(&loadavg[number]) {
+                add("loadavg", "", &name, value);
+            }
+        }
+        let pressure = data.pressure.read().unwrap();
+        for number in rows(&self.pressure) {
+            for (name, value) in row_values(&pressure[number]) {
+                add("pressure", "", &name, value);
+            }
+        }
+        let vmstat = data.vmstat.read().unwrap();
+        for number in rows(&self.vmstat) {
+            for (name, value) in row_values(&vmstat[number]) {
+                add("vmstat", "", &name, value);
+            }
+        }
+        statistics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn aggregate_averages_per_second_and_keeps_last_value() {
+        let start = Local::now();
+        let statistics = |timestamp: DateTime<Local>, value: f64, duration: f64| {
+            Statistics::from([(
+                ("loadavg".to_string(), "".to_string(), "load_1".to_string()),
+                Statistic {
+                    last_timestamp: timestamp,
+                    last_value: value,
+                    delta_value: value * duration,
+                    per_second_value: value,
+                    updated_value: true,
+                },
+            )])
+        };
+        let aggregated = aggregate(vec![
+            (statistics(start, 1_f64, 1_f64), 1_f64),
+            (
+                statistics(start + Duration::seconds(3), 5_f64, 3_f64),
+                3_f64,
+            ),
+        ]);
+        let statistic = &aggregated[&("loadavg".to_string(), "".to_string(), "load_1".to_string())];
+        assert_eq!(statistic.per_second_value, 4_f64);
+        assert_eq!(statistic.delta_value, 16_f64);
+        assert_eq!(statistic.last_value, 5_f64);
+        assert_eq!(statistic.last_timestamp, start + Duration::seconds(3));
+
+        let timestamps = (0..5)
+            .map(|second| (start + Duration::seconds(second * 10), 10_f64))
+            .collect::<Vec<_>>();
+        assert_eq!(intervals(&timestamps, None).len(), 5);
+        assert_eq!(
+            intervals(&timestamps, Some(20))
+                .iter()
+                .map(|rows| rows.len())
+                .collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+    }
+
+    #[test]
+    fn per_cpu_scheduler_statistics_are_taken_from_the_schedstat_history() {
+        use crate::processor::schedstat::SchedStatInfo;
+        use chrono::TimeZone;
+
+        let data = Data::new(&[]);
+        let timestamp = Local.with_ymd_and_hms(2001, 2, 3, 4, 5, 6).unwrap();
+        for (cpu_name, timeslices) in [("cpu0", 10_f64), ("cpu1", 30_f64)] {
+            data.schedstat.write().unwrap().push_back(SchedStatInfo {
+                timestamp,
+                cpu_name: cpu_name.to_string(),
+                time_running: 0.5,
+                time_waiting: 0.25,
+                timeslices,
+            });
+        }
+        let statistics = HistoryIndex::new(&data).statistics(&data, timestamp, 1_f64);
+        let value = |cpu_name: &str, name: &str| {
+            statistics[&(
+                "schedstat".to_string(),
+                cpu_name.to_string(),
+                name.to_string(),
+            )]
+                .per_second_value
+        };
+        assert_eq!(value("cpu0", "time_running"), 500_000_000_f64);
+        assert_eq!(value("cpu0", "time_waiting"), 250_000_000_f64);
+        assert_eq!(value("cpu1", "timeslices"), 30_f64);
+    }
+}
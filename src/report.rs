@@ -0,0 +1,478 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::Write;
+use std::fs::{create_dir_all, write};
+use std::path::Path;
+
+use crate::archiver::load_archives;
+use crate::processor::blockdevice::is_total;
+use crate::processor::stat::CpuStat;
+use crate::processor::Data;
+use crate::webserver::compare::{comparison_summary_html, Comparison};
+use crate::webserver::{render_comparison, render_plot, PlotFormat, PlotOptions};
+use crate::{ReportOpts, DATA};
+
+/// The plot groups that are not per device, as plot group, device and title.
+/// The cpu and cpu_load plot groups are left out, because these are part of cpu_load_psi.
+static PLOTS: [(&str, &str, &str); 14] = [
+    ("cpu_load_psi", "x", "CPU total-load-psi"),
+    ("cpu_heatmap", "busy", "CPU per-cpu heatmap"),
+    ("schedstat", "x", "CPU scheduler-psi"),
+    ("memory", "x", "Memory"),
+    ("memory_alloc", "x", "Memory-alloc"),
+    ("memory_commit", "x", "Memory-committed"),
+    ("memory_psi", "x", "Memory-psi"),
+    ("memory_psi_alloc", "x", "Memory-psi-alloc"),
+    ("memory_swap", "x", "Memory-swapspace"),
+    ("memory_swap_inout", "x", "Memory-swapspace-swapio"),
+    ("memory_act_inact", "x", "Memory-active-inactive"),
+    ("memory_dirty", "x", "Memory-dirty"),
+    ("xfs", "x", "Filesystem-XFS"),
+    ("blockdevice_psi", "TOTAL", "Blockdevice-psi TOTAL"),
+];
+
+/// Read the archives, and write every plot group as image plus an index.html with a summary
+/// table to the report directory.
+pub fn report(report_opts: &ReportOpts) -> Result<()> {
+    load_archives(&report_opts.archives, report_opts.allow_mixed_hosts)?;
+    let baseline_rows = DATA.cpu.read().unwrap().len();
+    if baseline_rows == 0 {
+        bail!("No data found in the archives.");
+    }
+    if let Some(candidate_archives) = &report_opts.compare {
+        load_archives(candidate_archives, report_opts.allow_mixed_hosts)?;
+    }
+    let comparison = comparison(report_opts, &DATA.cpu.read().unwrap(), baseline_rows)?;
+    let start_time = report_opts.start;
+    let end_time = report_opts.end;
+    let in_range = |timestamp: DateTime<Local>| {
+        start_time.is_none_or(|start_time| timestamp >= start_time)
+            && end_time.is_none_or(|end_time| timestamp <= end_time)
+    };
+    if !DATA
+        .cpu
+        .read()
+        .unwrap()
+        .iter()
+        .any(|c| in_range(c.timestamp))
+    {
+        bail!("No data found between the start and end time.");
+    }
+
+    let directory = Path::new(&report_opts.directory);
+    create_dir_all(directory)
+        .with_context(|| format!("Error creating report directory {}.", directory.display()))?;
+
+    let mut plots: Vec<(String, String, String)> = PLOTS
+        .iter()
+        .filter(|(plot, _, _)| *plot != "xfs" || !DATA.xfs.read().unwrap().is_empty())
+        .filter(|(plot, _, _)| *plot != "cpu_heatmap" || !DATA.per_cpu.read().unwrap().is_empty())
+        .filter(|(plot, _, _)| *plot != "schedstat" || !DATA.schedstat.read().unwrap().is_empty())
+        .filter(|(plot, _, _)| {
+            *plot != "blockdevice_psi" || !DATA.blockdevices.read().unwrap().is_empty()
+        })
+        .map(|(plot, device, title)| (plot.to_string(), device.to_string(), title.to_string()))
+        .collect();
+    DATA.blockdevices
+        .read()
+        .unwrap()
+        .iter()
+        .map(|b| b.device_name.clone())
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .for_each(|device| {
+            let plot = if is_total(&device) {
+                "blockdevice"
+            } else {
+                "blockdevice_extra"
+            };
+            plots.push((
+                plot.to_string(),
+                device.clone(),
+                format!("Blockdevice {}", device),
+            ));
+            // the latency buckets differ per range, so the heatmaps are not compared.
+            if comparison.is_none() {
+                plots.push((
+                    "blockdevice_latency".to_string(),
+                    device.clone(),
+                    format!("Blockdevice-latency {}", device),
+                ))
+            }
+        });
+    DATA.networkdevices
+        .read()
+        .unwrap()
+        .iter()
+        .map(|n| n.device_name.clone())
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .for_each(|device| {
+            plots.push((
+                "networkdevice".to_string(),
+                device.clone(),
+                format!("Networkdevice {}", device),
+            ))
+        });
+
+    let plot_options = PlotOptions {
+        format: report_opts.format,
+        scale: Some(report_opts.scale),
+        ..Default::default()
+    };
+    let extension = match report_opts.format {
+        PlotFormat::Png => "png",
+        PlotFormat::Svg => "svg",
+    };
+    let mut html_for_plots = String::new();
+    let mut html_for_navigation = String::new();
+    for (plot, device, title) in plots {
+        let filename = format!("{}_{}.{}", plot, device.replace('/', "_"), extension);
+        let (_, image) = match &comparison {
+            Some(comparison) => render_comparison(&plot, device, comparison, &plot_options),
+            None => render_plot(&plot, device, start_time, end_time, &plot_options),
+        }
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Error rendering plot {}.", title))?;
+        write(directory.join(&filename), image)
+            .with_context(|| format!("Error writing {}.", filename))?;
+        let _ = write!(
+            html_for_navigation,
+            r##"<li><a href="#{filename}">{title}</a></li>"##
+        );
+        let _ = write!(
+            html_for_plots,
+            r##"<h2 id="{filename}">{title}</h2><img src="{filename}" width="100%">"##
+        );
+        println!("✔ {}", filename);
+    }
+
+    let html_for_summary = match &comparison {
+        Some(comparison) => comparison_summary_html(comparison),
+        None => {
+            let rows = summary(&DATA, start_time, end_time).iter().fold(
+                String::new(),
+                |mut output, (metric, min, avg, max)| {
+                    let _ = write!(
+                        output,
+                        "<tr><td>{}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td></tr>",
+                        metric, min, avg, max
+                    );
+                    output
+                },
+            );
+            format!(
+                r##"<table>
+    <tr><th>metric</th><th>min</th><th>avg</th><th>max</th></tr>
+    {rows}
+   </table>"##
+            )
+        }
+    };
+    let cpu = DATA.cpu.read().unwrap();
+    let first = cpu
+        .iter()
+        .find(|c| in_range(c.timestamp))
+        .unwrap()
+        .timestamp;
+    let last = cpu
+        .iter()
+        .rfind(|c| in_range(c.timestamp))
+        .unwrap()
+        .timestamp;
+    let index = format!(
+        r##"<!doctype html>
+ <html>
+  <head>
+   <meta charset="utf-8">
+   <title>procstat report {first} - {last}</title>
+   <style>
+    body {{ font-family: monospace; }}
+    table {{ border-collapse: collapse; }}
+    td, th {{ border: 1px solid grey; padding: 2px 8px; text-align: right; }}
+    td:first-child {{ text-align: left; }}
+   </style>
+  </head>
+  <body>
+   <h1>procstat report</h1>
+   <p>{first} - {last}, from {archives} archive(s).</p>
+   {html_for_summary}
+   <ul>
+    {html_for_navigation}
+   </ul>
+   {html_for_plots}
+  </body>
+ </html>
+ "##,
+        first = first.format("%Y-%m-%d %H:%M:%S %:z"),
+        last = last.format("%Y-%m-%d %H:%M:%S %:z"),
+        archives = report_opts.archives.len(),
+    );
+    write(directory.join("index.html"), index).with_context(|| "Error writing index.html.")?;
+    println!(
+        "Report written to {}.",
+        directory.join("index.html").display()
+    );
+
+    Ok(())
+}
+
+/// The baseline and candidate ranges if the report is a comparison.
+/// The candidate is either the candidate archives, which are loaded after the baseline_rows
+/// rows of the baseline archives, or the candidate start to end range of all loaded data.
+fn comparison(
+    report_opts: &ReportOpts,
+    cpu: &VecDeque<CpuStat>,
+    baseline_rows: usize,
+) -> Result<Option<Comparison>> {
+    if report_opts.compare.is_none() && report_opts.candidate_start.is_none() {
+        return Ok(None);
+    }
+    let (baseline, candidate) = if report_opts.compare.is_some() {
+        (
+            cpu.range(..baseline_rows).collect::<Vec<_>>(),
+            cpu.range(baseline_rows..).collect::<Vec<_>>(),
+        )
+    } else {
+        (cpu.iter().collect(), cpu.iter().collect())
+    };
+    let candidate_start = match report_opts.candidate_start {
+        Some(candidate_start) => candidate_start,
+        None => match candidate.iter().map(|c| c.timestamp).min() {
+            Some(candidate_start) => candidate_start,
+            None => bail!("No data found in the candidate archives."),
+        },
+    };
+    let baseline_start = report_opts
+        .start
+        .or(baseline.iter().map(|c| c.timestamp).min())
+        .unwrap();
+    // without an end time, a baseline before the candidate ends at the last row before the
+    // candidate.
+    let baseline_end = report_opts
+        .end
+        .or(baseline
+            .iter()
+            .map(|c| c.timestamp)
+            .filter(|timestamp| candidate_start < baseline_start || *timestamp < candidate_start)
+            .max())
+        .unwrap_or(baseline_start);
+    let candidate_end = report_opts
+        .candidate_end
+        .or(candidate.iter().map(|c| c.timestamp).max())
+        .unwrap_or(candidate_start);
+    if baseline_end <= baseline_start || candidate_end <= candidate_start {
+        bail!(
+            "The baseline ({} - {}) or the candidate ({} - {}) is empty.",
+            baseline_start,
+            baseline_end,
+            candidate_start,
+            candidate_end
+        );
+    }
+    if baseline_start <= candidate_end && candidate_start <= baseline_end {
+        bail!(
+            "The baseline ({} - {}) and the candidate ({} - {}) overlap.",
+            baseline_start,
+            baseline_end,
+            candidate_start,
+            candidate_end
+        );
+    }
+    Ok(Some(Comparison {
+        baseline_start,
+        baseline_end,
+        candidate_start,
+        candidate_end,
+    }))
+}
+
+/// The minimum, average and maximum of the most important metrics of the history between
+/// start_time and end_time.
+pub fn summary(
+    data: &Data,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Vec<(String, f64, f64, f64)> {
+    let in_range = |timestamp: DateTime<Local>| {
+        start_time.is_none_or(|start_time| timestamp >= start_time)
+            && end_time.is_none_or(|end_time| timestamp <= end_time)
+    };
+    let min_avg_max = |values: Vec<f64>| {
+        if values.is_empty() {
+            return (0_f64, 0_f64, 0_f64);
+        }
+        (
+            values.iter().cloned().fold(f64::MAX, f64::min),
+            values.iter().sum::<f64>() / values.len() as f64,
+            values.iter().cloned().fold(f64::MIN, f64::max),
+        )
+    };
+    let mut summary = Vec::new();
+    macro_rules! add_to_summary {
+        ($metric:expr, $data:ident, $filter:expr, $value:expr) => {
+            let (min, avg, max) = min_avg_max(
+                data.$data
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .filter(|row| in_range(row.timestamp))
+                    .filter($filter)
+                    .map($value)
+                    .collect(),
+            );
+            summary.push(($metric.to_string(), min, avg, max));
+        };
+    }
+    add_to_summary!("CPU busy (cpus)", cpu, |_| true, |c| c.user
+        + c.nice
+        + c.system
+        + c.iowait
+        + c.irq
+        + c.softirq
+        + c.steal);
+    add_to_summary!("CPU iowait (cpus)", cpu, |_| true, |c| c.iowait);
+    add_to_summary!("Load 1", loadavg, |_| true, |l| l.load_1);
+    add_to_summary!("Memory available (MB)", memory, |_| true, |m| m
+        .memavailable
+        / 1024_f64);
+    add_to_summary!("Memory free (MB)", memory, |_| true, |m| m.memfree
+        / 1024_f64);
+    add_to_summary!("Pressure cpu some avg10 (%)", pressure, |_| true, |p| p
+        .cpu_some_avg10);
+    add_to_summary!("Pressure memory some avg10 (%)", pressure, |_| true, |p| p
+        .memory_some_avg10);
+    add_to_summary!("Pressure io some avg10 (%)", pressure, |_| true, |p| p
+        .io_some_avg10);
+    add_to_summary!(
+        "Blockdevice TOTAL MBPS",
+        blockdevices,
+        |b| b.device_name == "TOTAL",
+        |b| (b.reads_bytes + b.writes_bytes) / (1024_f64 * 1024_f64)
+    );
+    add_to_summary!(
+        "Blockdevice TOTAL IOPS",
+        blockdevices,
+        |b| b.device_name == "TOTAL",
+        |b| b.reads_completed_success + b.writes_completed_success
+    );
+    let networkdevices = data
+        .networkdevices
+        .read()
+        .unwrap()
+        .iter()
+        .map(|n| n.device_name.clone())
+        .collect::<BTreeSet<String>>();
+    for device in networkdevices {
+        add_to_summary!(
+            format!("Networkdevice {} Mbit", device),
+            networkdevices,
+            |n| n.device_name == device,
+            |n| ((n.receive_bytes + n.transmit_bytes) / (1024_f64 * 1024_f64)) * 8_f64
+        );
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::blockdevice::BlockDeviceInfo;
+    use chrono::{TimeDelta, TimeZone};
+
+    fn report_opts(candidate_start: Option<DateTime<Local>>) -> ReportOpts {
+        ReportOpts {
+            archives: vec!["baseline.json".to_string()],
+            directory: "report".to_string(),
+            start: None,
+            end: None,
+            format: PlotFormat::Png,
+            scale: 1_f64,
+            compare: None,
+            candidate_start,
+            candidate_end: None,
+            allow_mixed_hosts: false,
+        }
+    }
+
+    #[test]
+    fn comparison_ranges_of_the_archives_and_of_a_candidate_start() {
+        let start = Local.with_ymd_and_hms(2024, 10, 20, 12, 0, 0).unwrap();
+        let minute = |minutes: i64| start + TimeDelta::minutes(minutes);
+        // the baseline archives have 12:00 - 12:05, the candidate archives 13:00 - 13:05.
+        let cpu = (0..=5)
+            .chain(60..=65)
+            .map(|minutes| CpuStat {
+                timestamp: minute(minutes),
+                ..Default::default()
+            })
+            .collect::<VecDeque<_>>();
+
+        assert!(comparison(&report_opts(None), &cpu, 6).unwrap().is_none());
+
+        let mut compare_archives = report_opts(None);
+        compare_archives.compare = Some(vec!["candidate.json".to_string()]);
+        let ranges = comparison(&compare_archives, &cpu, 6).unwrap().unwrap();
+        assert_eq!(
+            (ranges.baseline_start, ranges.baseline_end),
+            (minute(0), minute(5))
+        );
+        assert_eq!(
+            (ranges.candidate_start, ranges.candidate_end),
+            (minute(60), minute(65))
+        );
+
+        // without candidate archives, the baseline ends before the candidate start.
+        let ranges = comparison(&report_opts(Some(minute(60))), &cpu, 12)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (ranges.baseline_start, ranges.baseline_end),
+            (minute(0), minute(5))
+        );
+        assert_eq!(ranges.candidate_end, minute(65));
+
+        let mut overlapping = report_opts(Some(minute(3)));
+        overlapping.end = Some(minute(4));
+        assert!(comparison(&overlapping, &cpu, 12).is_err());
+    }
+
+    #[test]
+    fn summary_of_the_range_takes_the_total_device_only() {
+        let data = Data::new(&[]);
+        let start = Local.with_ymd_and_hms(2024, 10, 20, 12, 0, 0).unwrap();
+        for (seconds, user) in [(0, 1_f64), (10, 2_f64), (20, 6_f64), (30, 100_f64)] {
+            let timestamp = start + TimeDelta::seconds(seconds);
+            data.cpu.write().unwrap().push_back(CpuStat {
+                timestamp,
+                user,
+                ..Default::default()
+            });
+            for (device_name, iops) in [("sda", 5_f64), ("TOTAL", 10_f64)] {
+                data.blockdevices
+                    .write()
+                    .unwrap()
+                    .push_back(BlockDeviceInfo {
+                        timestamp,
+                        device_name: device_name.to_string(),
+                        reads_completed_success: iops,
+                        writes_completed_success: user,
+                        ..Default::default()
+                    });
+            }
+        }
+        let summary = summary(&data, Some(start), Some(start + TimeDelta::seconds(20)));
+        let metric = |name: &str| {
+            summary
+                .iter()
+                .find(|(metric, _, _, _)| metric == name)
+                .map(|(_, min, avg, max)| (*min, *avg, *max))
+                .unwrap()
+        };
+        assert_eq!(metric("CPU busy (cpus)"), (1_f64, 3_f64, 6_f64));
+        assert_eq!(metric("Blockdevice TOTAL IOPS"), (11_f64, 13_f64, 16_f64));
+        // the histories without rows have zeroes.
+        assert_eq!(metric("Load 1"), (0_f64, 0_f64, 0_f64));
+    }
+}
@@ -1,167 +1,631 @@
 use ordered_float::OrderedFloat;
 use std::collections::BTreeSet;
 
-use plotters::backend::{BitMapBackend, RGBPixel};
-
-use crate::webserver::pressure::pressure_io_plot;
-use crate::ARGS;
+use crate::processor::blockdevice::{is_total, BlockDeviceInfo};
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
 use crate::{
     CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, DATA, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
     LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, LABEL_AREA_SIZE_RIGHT, MESH_STYLE_FONT,
     MESH_STYLE_FONT_SIZE,
 };
 use chrono::{DateTime, Local};
-use plotters::chart::{ChartBuilder, LabelAreaPosition, SeriesLabelPosition::UpperLeft};
+use plotters::chart::{
+    ChartBuilder, LabelAreaPosition,
+    SeriesLabelPosition::{UpperLeft, UpperRight},
+};
 use plotters::coord::Shift;
 use plotters::element::Rectangle;
 use plotters::prelude::{full_palette::PURPLE, *};
 use plotters::style::colors::full_palette::{GREEN_500, GREY_100, RED_100};
 
-pub fn create_blockdevice_plot(
-    buffer: &mut [u8],
-    device_name: String,
+/// The devices a device is built on and the devices built on it, from the latest statistics of
+/// the device.
+pub(crate) fn topology_devices(device_name: &str) -> Vec<String> {
+    DATA.blockdevices
+        .read()
+        .unwrap()
+        .iter()
+        .rfind(|b| b.device_name == device_name)
+        .map(|b| b.slaves.iter().chain(b.holders.iter()).cloned().collect())
+        .unwrap_or_default()
+}
+
+fn total_mbps(b: &BlockDeviceInfo) -> f64 {
+    (b.reads_bytes + b.writes_bytes) / (1024_f64 * 1024_f64)
+}
+
+pub(crate) fn average_latency(b: &BlockDeviceInfo) -> f64 {
+    let ios = b.reads_completed_success + b.writes_completed_success;
+    if ios == 0_f64 {
+        0_f64
+    } else {
+        (b.reads_time_spent_ms + b.writes_time_spent_ms) / ios
+    }
+}
+
+/// The percentage of time the device was busy with IO. iostat calls this %util.
+fn utilization(b: &BlockDeviceInfo) -> f64 {
+    (b.ios_time_spent_ms / 10_f64).min(100_f64)
+}
+
+/// The average time the device was busy per IO (svctm).
+fn service_time(b: &BlockDeviceInfo) -> f64 {
+    let ios = b.reads_completed_success + b.writes_completed_success + b.discards_completed_success;
+    if ios == 0_f64 {
+        0_f64
+    } else {
+        b.ios_time_spent_ms / ios
+    }
+}
+
+/// The queue size as a percentage of nr_requests, which is an estimate of the saturation of a
+/// device that handles requests in parallel, for which utilization doesn't show saturation.
+fn queue_saturation(b: &BlockDeviceInfo) -> f64 {
+    if b.queue_nr_requests == 0_f64 {
+        0_f64
+    } else {
+        (b.ios_weighted_time_spent_ms / 1000_f64) / b.queue_nr_requests * 100_f64
+    }
+}
+
+fn utilization_caption(device_name: &str, rotational: bool) -> String {
+    if rotational {
+        format!("Blockdevice: {} utilization", device_name)
+    } else {
+        format!(
+            "Blockdevice: {} utilization (non-rotational: %util is not saturation, see %qsat)",
+            device_name
+        )
+    }
+}
+
+pub(crate) fn blockdevice_utilization_plot<DB: DrawingBackend>(
+    backend: &mut DrawingArea<DB, Shift>,
+    device_name: &str,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
 ) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let nr = if device_name == "TOTAL" { 3 } else { 4 };
-    let mut multi_backend = backend.split_evenly((nr, 1));
-    let mut mbps_graph = multi_backend[0].split_horizontally((60).percent_width());
-    blockdevice_mbps_plot(&mut mbps_graph.0, device_name.clone(), start_time, end_time);
-    blockdevice_mbps_percentile_plot(&mut mbps_graph.1, device_name.clone(), start_time, end_time);
-    let mut iops_graph = multi_backend[1].split_horizontally((60).percent_width());
-    blockdevice_iops_plot(&mut iops_graph.0, device_name.clone(), start_time, end_time);
-    blockdevice_iops_percentile_plot(&mut iops_graph.1, device_name.clone(), start_time, end_time);
-    let mut lat_queue_graph = multi_backend[2].split_horizontally((60).percent_width());
-    blockdevice_latency_queuedepth_plot(
-        &mut lat_queue_graph.0,
-        device_name.clone(),
-        start_time,
-        end_time,
-    );
-    blockdevice_latency_percentile_plot(
-        &mut lat_queue_graph.1,
-        device_name.clone(),
+    backend.fill(&WHITE).unwrap();
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| b.device_name == device_name),
+        |b| b.timestamp,
         start_time,
         end_time,
     );
-    /*
-        blockdevice_latency_queuedepth_plot(
-            &mut multi_backend,
-            2,
-            device_name.clone(),
-            start_time,
-            end_time,
+    let final_start_time = rows.iter().map(|b| b.timestamp).min().unwrap_or_default();
+    let final_end_time = rows.iter().map(|b| b.timestamp).max().unwrap_or_default();
+    let rotational = rows.last().is_some_and(|b| b.queue_rotational != 0_f64);
+    let high_value_percentage = rows
+        .iter()
+        .map(|b| queue_saturation(b) * 1.1_f64)
+        .fold(100_f64, f64::max);
+    let high_value_service_time = rows
+        .iter()
+        .map(|b| service_time(b) * 1.1_f64)
+        .max_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or_default();
+
+    // create the plot
+    let mut contextarea = ChartBuilder::on(backend)
+        .set_label_area_size(LabelAreaPosition::Left, LABEL_AREA_SIZE_LEFT)
+        .set_label_area_size(LabelAreaPosition::Bottom, LABEL_AREA_SIZE_BOTTOM)
+        .set_label_area_size(LabelAreaPosition::Right, LABEL_AREA_SIZE_RIGHT)
+        .caption(
+            utilization_caption(device_name, rotational),
+            (CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE),
+        )
+        .build_cartesian_2d(
+            final_start_time..final_end_time,
+            0_f64..high_value_percentage,
+        )
+        .unwrap()
+        .set_secondary_coord(
+            final_start_time..final_end_time,
+            0_f64..high_value_service_time,
         );
-    */
-    if device_name != "TOTAL" {
-        blockdevice_iosize_plot(&mut multi_backend, 3, device_name, start_time, end_time)
+    contextarea
+        .configure_mesh()
+        .x_label_formatter(&|timestamp| timestamp.format("%H:%M:%S").to_string())
+        .x_desc(format!(
+            "Time: {} to {} ({} minutes)",
+            final_start_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            final_end_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            (final_end_time - final_start_time).num_minutes(),
+        ))
+        .y_desc("%")
+        .y_label_formatter(&|percentage| format!("{:5.0}", percentage))
+        .label_style((MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE))
+        .draw()
+        .unwrap();
+    contextarea
+        .configure_secondary_axes()
+        .y_desc("service time")
+        .y_label_formatter(&|service_time| {
+            if service_time == &0_f64 {
+                format!("{:5.0} ms", service_time)
+            } else if service_time < &0.1_f64 {
+                format!("{:5.3} ms", service_time)
+            } else {
+                format!("{:5.1} ms", service_time)
+            }
+        })
+        .label_style((MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE))
+        .draw()
+        .unwrap();
+    //
+    // This is a dummy plot for the sole intention to write a header in the legend.
+    contextarea
+        .draw_series(LineSeries::new(
+            std::iter::once((Local::now(), 0_f64)),
+            ShapeStyle {
+                color: TRANSPARENT,
+                filled: false,
+                stroke_width: 1,
+            },
+        ))
+        .unwrap()
+        .label(format!(
+            "{:25} {:>10} {:>10} {:>10}",
+            "", "min", "max", "last"
+        ));
+    let statistics = |value: fn(&BlockDeviceInfo) -> f64| {
+        let min = rows
+            .iter()
+            .map(|b| value(b))
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap_or_default();
+        let max = rows
+            .iter()
+            .map(|b| value(b))
+            .max_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap_or_default();
+        let last = rows.last().map_or(0_f64, |b| value(b));
+        (min, max, last)
     };
+    let (min, max, last) = statistics(utilization);
+    contextarea
+        .draw_series(LineSeries::new(
+            rows.iter().map(|b| (b.timestamp, utilization(b))),
+            BLACK,
+        ))
+        .unwrap()
+        .label(format!(
+            "{:25} {:10.2} {:10.2} {:10.2}",
+            if rotational {
+                "%util"
+            } else {
+                "%util (not saturation)"
+            },
+            min,
+            max,
+            last
+        ))
+        .legend(move |(x, y)| Rectangle::new([(x - 3, y - 3), (x + 3, y + 3)], BLACK.filled()));
+    let (min, max, last) = statistics(queue_saturation);
+    contextarea
+        .draw_series(LineSeries::new(
+            rows.iter().map(|b| (b.timestamp, queue_saturation(b))),
+            RED,
+        ))
+        .unwrap()
+        .label(format!(
+            "{:25} {:10.2} {:10.2} {:10.2}",
+            "%qsat (aqu-sz/nr_requests)", min, max, last
+        ))
+        .legend(move |(x, y)| Rectangle::new([(x - 3, y - 3), (x + 3, y + 3)], RED.filled()));
+    let (min, max, last) = statistics(service_time);
+    contextarea
+        .draw_secondary_series(
+            rows.iter()
+                .filter(|b| service_time(b) > 0_f64)
+                .map(|b| Circle::new((b.timestamp, service_time(b)), 2, BLUE.filled())),
+        )
+        .unwrap()
+        .label(format!(
+            "{:25} {:10.2} {:10.2} {:10.2}",
+            "svctm (ms)", min, max, last
+        ))
+        .legend(move |(x, y)| Circle::new((x, y), 3, BLUE.filled()));
+    // legend
+    contextarea
+        .configure_series_labels()
+        .border_style(BLACK)
+        .background_style(WHITE.mix(0.7))
+        .label_font((LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE))
+        .position(UpperLeft)
+        .draw()
+        .unwrap();
 }
 
-pub fn create_blockdevice_plot_extra(
-    buffer: &mut [u8],
-    device_name: String,
+/// MBPS and latency of the device next to the devices it is built on or that are built on it,
+/// for example a logical volume and its physical volumes.
+pub(crate) fn blockdevice_topology_plot<DB: DrawingBackend>(
+    backend: &mut DrawingArea<DB, Shift>,
+    device_name: &str,
+    topology: &[String],
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
 ) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let nr = if device_name == "TOTAL" { 3 } else { 5 };
-    let mut multi_backend = backend.split_evenly((nr, 1));
-    let mut mbps_graph = multi_backend[0].split_horizontally((60).percent_width());
-    blockdevice_mbps_plot(&mut mbps_graph.0, device_name.clone(), start_time, end_time);
-    blockdevice_mbps_percentile_plot(&mut mbps_graph.1, device_name.clone(), start_time, end_time);
-    /*
-        blockdevice_mbps_array_plot(
-            &mut multi_backend,
-            0,
-            device_name.clone(),
-            start_time,
-            end_time,
-        );
-    */
-    let mut iops_graph = multi_backend[1].split_horizontally((60).percent_width());
-    blockdevice_iops_plot(&mut iops_graph.0, device_name.clone(), start_time, end_time);
-    blockdevice_iops_percentile_plot(&mut iops_graph.1, device_name.clone(), start_time, end_time);
-    /*
-    blockdevice_iops_plot(
-        &mut multi_backend,
-        1,
-        device_name.clone(),
+    let devices = std::iter::once(device_name.to_string())
+        .chain(topology.iter().cloned())
+        .collect::<Vec<String>>();
+    let mut graph = backend.split_horizontally((50).percent_width());
+    blockdevice_per_device_plot(
+        &mut graph.0,
+        format!("Blockdevice: {} and related devices MBPS", device_name),
+        "MBPS",
+        &devices,
+        total_mbps,
         start_time,
         end_time,
     );
-    */
-    let mut lat_queue_graph = multi_backend[2].split_horizontally((60).percent_width());
-    blockdevice_latency_queuedepth_plot(
-        &mut lat_queue_graph.0,
-        device_name.clone(),
+    blockdevice_per_device_plot(
+        &mut graph.1,
+        format!("Blockdevice: {} and related devices latency", device_name),
+        "Average latency (ms)",
+        &devices,
+        average_latency,
         start_time,
         end_time,
     );
-    blockdevice_latency_percentile_plot(
-        &mut lat_queue_graph.1,
-        device_name.clone(),
+}
+
+fn blockdevice_per_device_plot<DB: DrawingBackend>(
+    backend: &mut DrawingArea<DB, Shift>,
+    caption: String,
+    y_desc: &str,
+    devices: &[String],
+    value: fn(&BlockDeviceInfo) -> f64,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) {
+    backend.fill(&WHITE).unwrap();
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| devices.contains(&b.device_name)),
+        |b| b.timestamp,
         start_time,
         end_time,
     );
-    /*
-        blockdevice_latency_queuedepth_plot(
-            &mut multi_backend,
-            2,
-            device_name.clone(),
-            start_time,
-            end_time,
-        );
-    */
-    if device_name != "TOTAL" {
-        blockdevice_iosize_plot(
-            &mut multi_backend,
-            3,
-            device_name.clone(),
-            start_time,
-            end_time,
-        );
-        blockdevice_extra(&mut multi_backend, 4, device_name.clone());
+    let final_start_time = rows.iter().map(|b| b.timestamp).min().unwrap_or_default();
+    let final_end_time = rows.iter().map(|b| b.timestamp).max().unwrap_or_default();
+    let high_value = rows
+        .iter()
+        .map(|b| value(b) * 1.1_f64)
+        .max_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or_default();
+
+    // create the plot
+    let mut contextarea = ChartBuilder::on(backend)
+        .set_label_area_size(LabelAreaPosition::Left, LABEL_AREA_SIZE_LEFT)
+        .set_label_area_size(LabelAreaPosition::Bottom, LABEL_AREA_SIZE_BOTTOM)
+        .caption(caption, (CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE))
+        .build_cartesian_2d(final_start_time..final_end_time, 0_f64..high_value)
+        .unwrap();
+    contextarea
+        .configure_mesh()
+        .x_label_formatter(&|timestamp| timestamp.format("%H:%M:%S").to_string())
+        .x_desc(format!(
+            "Time: {} to {} ({} minutes)",
+            final_start_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            final_end_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            (final_end_time - final_start_time).num_minutes(),
+        ))
+        .y_desc(y_desc)
+        .y_label_formatter(&|value| {
+            if value == &0_f64 {
+                format!("{:5.0}", value)
+            } else if value < &1_f64 {
+                format!("{:5.3}", value)
+            } else {
+                format!("{:5.0}", value)
+            }
+        })
+        .label_style((MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE))
+        .draw()
+        .unwrap();
+    //
+    // This is a dummy plot for the sole intention to write a header in the legend.
+    contextarea
+        .draw_series(LineSeries::new(
+            std::iter::once((Local::now(), 0_f64)),
+            ShapeStyle {
+                color: TRANSPARENT,
+                filled: false,
+                stroke_width: 1,
+            },
+        ))
+        .unwrap()
+        .label(format!(
+            "{:25} {:>10} {:>10} {:>10}",
+            "", "min", "max", "last"
+        ));
+    for (index, device) in devices.iter().enumerate() {
+        let device_values = rows
+            .iter()
+            .filter(|b| &b.device_name == device)
+            .map(|b| (b.timestamp, value(b)))
+            .collect::<Vec<(DateTime<Local>, f64)>>();
+        let min = device_values
+            .iter()
+            .map(|(_, value)| *value)
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap_or_default();
+        let max = device_values
+            .iter()
+            .map(|(_, value)| *value)
+            .max_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap_or_default();
+        let last = device_values.last().map_or(0_f64, |(_, value)| *value);
+        let colour = Palette99::pick(index);
+        contextarea
+            .draw_series(LineSeries::new(
+                device_values,
+                ShapeStyle {
+                    color: colour.to_rgba(),
+                    filled: false,
+                    stroke_width: if index == 0 { 2 } else { 1 },
+                },
+            ))
+            .unwrap()
+            .label(format!(
+                "{:25} {:10.2} {:10.2} {:10.2}",
+                device, min, max, last
+            ))
+            .legend(move |(x, y)| {
+                Rectangle::new([(x - 3, y - 3), (x + 3, y + 3)], colour.filled())
+            });
+    }
+    // legend
+    contextarea
+        .configure_series_labels()
+        .border_style(BLACK)
+        .background_style(WHITE.mix(0.7))
+        .label_font((LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE))
+        .position(UpperLeft)
+        .draw()
+        .unwrap();
+}
+
+/// The upper bounds (ms) of the latency buckets of the latency heatmaps. The buckets are
+/// logarithmic (1-2-5), and the last bucket holds the latencies above the last bound.
+static LATENCY_BUCKETS_MS: [f64; 18] = [
+    0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1., 2., 5., 10., 20., 50., 100., 200., 500., 1000., 2000.,
+    5000.,
+];
+/// The maximal number of time columns of a latency heatmap; samples are grouped to fit.
+static LATENCY_HEATMAP_COLUMNS: usize = 240;
+
+fn latency_bucket(latency_ms: f64) -> usize {
+    LATENCY_BUCKETS_MS
+        .iter()
+        .position(|bound| latency_ms < *bound)
+        .unwrap_or(LATENCY_BUCKETS_MS.len())
+}
+
+fn format_latency(latency_ms: f64) -> String {
+    if latency_ms < 1_f64 {
+        format!("{}us", latency_ms * 1000_f64)
+    } else if latency_ms < 1000_f64 {
+        format!("{}ms", latency_ms)
+    } else {
+        format!("{}s", latency_ms / 1000_f64)
+    }
+}
+
+fn latency_bucket_name(bucket: usize) -> String {
+    match bucket {
+        0 => format!("<{}", format_latency(LATENCY_BUCKETS_MS[0])),
+        bucket if bucket == LATENCY_BUCKETS_MS.len() => {
+            format!(">={}", format_latency(LATENCY_BUCKETS_MS[bucket - 1]))
+        }
+        bucket => format!(
+            "{}-{}",
+            format_latency(LATENCY_BUCKETS_MS[bucket - 1]),
+            format_latency(LATENCY_BUCKETS_MS[bucket])
+        ),
+    }
+}
+
+/// The IOs per second and the average latency of the reads.
+pub(crate) fn read_latency(b: &BlockDeviceInfo) -> (f64, f64) {
+    if b.reads_completed_success == 0_f64 {
+        (0_f64, 0_f64)
+    } else {
+        (
+            b.reads_completed_success,
+            b.reads_time_spent_ms / b.reads_completed_success,
+        )
+    }
+}
+
+/// The IOs per second and the average latency of the writes.
+pub(crate) fn write_latency(b: &BlockDeviceInfo) -> (f64, f64) {
+    if b.writes_completed_success == 0_f64 {
+        (0_f64, 0_f64)
+    } else {
+        (
+            b.writes_completed_success,
+            b.writes_time_spent_ms / b.writes_completed_success,
+        )
+    }
+}
+
+/// The number of IOs per time column and latency bucket. The IOs of a sample are counted in the
+/// bucket of the average latency of the sample, and the number of IOs is the IOs per second
+/// times the time since the previous sample (the first sample takes the interval of the second).
+fn latency_histogram(
+    rows: &[&BlockDeviceInfo],
+    latency: fn(&BlockDeviceInfo) -> (f64, f64),
+    columns: usize,
+) -> Vec<Vec<f64>> {
+    let mut histogram = vec![vec![0_f64; LATENCY_BUCKETS_MS.len() + 1]; columns];
+    let (Some(first), Some(last)) = (rows.first(), rows.last()) else {
+        return histogram;
     };
+    let range = (last.timestamp - first.timestamp).num_milliseconds();
+    let interval = |nr: usize| {
+        let (previous, current) = match nr {
+            0 if rows.len() > 1 => (rows[0], rows[1]),
+            0 => return 1_f64,
+            nr => (rows[nr - 1], rows[nr]),
+        };
+        (current.timestamp - previous.timestamp).num_milliseconds() as f64 / 1000_f64
+    };
+    for (nr, row) in rows.iter().enumerate() {
+        let (ios_per_second, latency_ms) = latency(row);
+        if ios_per_second == 0_f64 {
+            continue;
+        }
+        let column = if range == 0 {
+            0
+        } else {
+            (((row.timestamp - first.timestamp).num_milliseconds() * columns as i64 / range)
+                as usize)
+                .min(columns - 1)
+        };
+        histogram[column][latency_bucket(latency_ms)] += ios_per_second * interval(nr);
+    }
+    histogram
 }
 
-pub fn create_blockdevice_psi_plot(
-    buffer: &mut [u8],
-    device_name: String,
+pub(crate) fn blockdevice_latency_heatmap_plot<DB: DrawingBackend>(
+    backend: &mut DrawingArea<DB, Shift>,
+    device_name: &str,
+    io_type: &str,
+    latency: fn(&BlockDeviceInfo) -> (f64, f64),
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
 ) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((4, 1));
-    let mut mbps_graph = multi_backend[0].split_horizontally((60).percent_width());
-    blockdevice_mbps_plot(&mut mbps_graph.0, device_name.clone(), start_time, end_time);
-    blockdevice_mbps_percentile_plot(&mut mbps_graph.1, device_name.clone(), start_time, end_time);
-    let mut iops_graph = multi_backend[1].split_horizontally((60).percent_width());
-    blockdevice_iops_plot(&mut iops_graph.0, device_name.clone(), start_time, end_time);
-    blockdevice_iops_percentile_plot(&mut iops_graph.1, device_name.clone(), start_time, end_time);
-    let mut lat_queue_graph = multi_backend[2].split_horizontally((60).percent_width());
-    blockdevice_latency_queuedepth_plot(
-        &mut lat_queue_graph.0,
-        device_name.clone(),
-        start_time,
-        end_time,
-    );
-    blockdevice_latency_percentile_plot(
-        &mut lat_queue_graph.1,
-        device_name.clone(),
+    backend.fill(&WHITE).unwrap();
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| b.device_name == device_name),
+        |b| b.timestamp,
         start_time,
         end_time,
     );
-    //blockdevice_latency_queuedepth_plot(&mut multi_backend, 2, device_name, start_time, end_time);
-    pressure_io_plot(&mut multi_backend, 3, start_time, end_time);
+    let final_start_time = rows.iter().map(|b| b.timestamp).min().unwrap_or_default();
+    let final_end_time = rows.iter().map(|b| b.timestamp).max().unwrap_or_default();
+    let columns = rows.len().clamp(1, LATENCY_HEATMAP_COLUMNS);
+    let histogram = latency_histogram(&rows, latency, columns);
+    let max_ios = histogram
+        .iter()
+        .flatten()
+        .fold(0_f64, |max, ios| max.max(*ios));
+    let color = |ios: f64| ViridisRGB.get_color_normalized(ios.ln_1p(), 0_f64, max_ios.ln_1p());
+    let column_time = |column: usize| {
+        final_start_time + (final_end_time - final_start_time) * column as i32 / columns as i32
+    };
+
+    // create the plot
+    let mut contextarea = ChartBuilder::on(backend)
+        .set_label_area_size(LabelAreaPosition::Left, LABEL_AREA_SIZE_LEFT)
+        .set_label_area_size(LabelAreaPosition::Bottom, LABEL_AREA_SIZE_BOTTOM)
+        .caption(
+            format!(
+                "Blockdevice: {} {} latency distribution (IOs per bucket)",
+                device_name, io_type
+            ),
+            (CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE),
+        )
+        .build_cartesian_2d(
+            final_start_time..final_end_time,
+            0_f64..(LATENCY_BUCKETS_MS.len() + 1) as f64,
+        )
+        .unwrap();
+    contextarea
+        .configure_mesh()
+        .disable_y_mesh()
+        .x_label_formatter(&|timestamp| timestamp.format("%H:%M:%S").to_string())
+        .x_desc(format!(
+            "Time: {} to {} ({} minutes)",
+            final_start_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            final_end_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            (final_end_time - final_start_time).num_minutes(),
+        ))
+        .y_desc("Latency")
+        .y_labels(LATENCY_BUCKETS_MS.len() + 2)
+        .y_label_formatter(&|bucket| match *bucket as usize {
+            _ if bucket.fract() != 0_f64 => String::new(),
+            0 => "0".to_string(),
+            bucket if bucket <= LATENCY_BUCKETS_MS.len() => {
+                format_latency(LATENCY_BUCKETS_MS[bucket - 1])
+            }
+            _ => String::new(),
+        })
+        .label_style((MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE))
+        .draw()
+        .unwrap();
+    contextarea
+        .draw_series(
+            histogram
+                .iter()
+                .enumerate()
+                .flat_map(|(column, buckets)| {
+                    buckets
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, ios)| **ios > 0_f64)
+                        .map(move |(bucket, ios)| (column, bucket, *ios))
+                })
+                .map(|(column, bucket, ios)| {
+                    Rectangle::new(
+                        [
+                            (column_time(column), bucket as f64),
+                            (column_time(column + 1), (bucket + 1) as f64),
+                        ],
+                        color(ios).filled(),
+                    )
+                }),
+        )
+        .unwrap();
+    //
+    // This is a dummy plot for the sole intention to write a header in the legend.
+    contextarea
+        .draw_series(LineSeries::new(
+            std::iter::once((final_start_time, 0_f64)),
+            ShapeStyle {
+                color: TRANSPARENT,
+                filled: false,
+                stroke_width: 1,
+            },
+        ))
+        .unwrap()
+        .label(format!("{:>10}", "IOs"));
+    for fraction in [1_f64, 0.75, 0.5, 0.25] {
+        let ios = (max_ios.ln_1p() * fraction).exp_m1();
+        contextarea
+            .draw_series(LineSeries::new(
+                std::iter::once((final_start_time, 0_f64)),
+                TRANSPARENT,
+            ))
+            .unwrap()
+            .label(format!("{:10.0}", ios))
+            .legend(move |(x, y)| {
+                Rectangle::new([(x - 3, y - 3), (x + 3, y + 3)], color(ios).filled())
+            });
+    }
+    // legend
+    contextarea
+        .configure_series_labels()
+        .border_style(BLACK)
+        .background_style(WHITE.mix(0.7))
+        .label_font((LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE))
+        .position(UpperRight)
+        .draw()
+        .unwrap();
 }
 
-fn blockdevice_mbps_percentile_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn blockdevice_mbps_percentile_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -419,8 +883,8 @@ fn blockdevice_mbps_percentile_plot(
         .draw()
         .unwrap();
 }
-fn blockdevice_mbps_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn blockdevice_mbps_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -456,8 +920,7 @@ fn blockdevice_mbps_plot(
         .unwrap_or_default();
     let latest = historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last();
+        .rfind(|b| b.device_name == device_name);
 
     // create the plot
     let mut contextarea = ChartBuilder::on(multi_backend)
@@ -638,8 +1101,8 @@ fn blockdevice_mbps_plot(
         .unwrap();
 }
 
-fn blockdevice_iops_percentile_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn blockdevice_iops_percentile_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -952,8 +1415,8 @@ fn blockdevice_iops_percentile_plot(
         .draw()
         .unwrap();
 }
-fn blockdevice_iops_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn blockdevice_iops_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -994,8 +1457,7 @@ fn blockdevice_iops_plot(
         .unwrap_or_default();
     let latest = historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last();
+        .rfind(|b| b.device_name == device_name);
 
     // create the plot
     multi_backend.fill(&WHITE).unwrap();
@@ -1215,8 +1677,8 @@ fn blockdevice_iops_plot(
         .unwrap();
 }
 
-fn blockdevice_latency_percentile_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn blockdevice_latency_percentile_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -1446,8 +1908,8 @@ fn blockdevice_latency_percentile_plot(
         .draw()
         .unwrap();
 }
-fn blockdevice_latency_queuedepth_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn blockdevice_latency_queuedepth_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -1548,7 +2010,7 @@ fn blockdevice_latency_queuedepth_plot(
         .unwrap_or_default();
     // For the TOTAL overview it doesn't make sense to use max_queue_size, because all devices
     // in it can have different max values. So it also doesn't make sense to show the max we find.
-    let high_value_queue = if device_name != "TOTAL" {
+    let high_value_queue = if !is_total(&device_name) {
         high_value_queue_depth.max(high_value_max_queue_size)
     } else {
         high_value_queue_depth
@@ -1646,8 +2108,7 @@ fn blockdevice_latency_queuedepth_plot(
         .unwrap_or_default();
     let latest_writes_latency = if historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last()
+        .rfind(|b| b.device_name == device_name)
         .map_or(0_f64, |latest| latest.writes_completed_success)
         == 0_f64
     {
@@ -1655,14 +2116,12 @@ fn blockdevice_latency_queuedepth_plot(
     } else {
         historical_data_read
             .iter()
-            .filter(|b| b.device_name == device_name)
-            .last()
+            .rfind(|b| b.device_name == device_name)
             .unwrap()
             .writes_time_spent_ms
             / historical_data_read
                 .iter()
-                .filter(|b| b.device_name == device_name)
-                .last()
+                .rfind(|b| b.device_name == device_name)
                 .unwrap()
                 .writes_completed_success
     };
@@ -1720,8 +2179,7 @@ fn blockdevice_latency_queuedepth_plot(
         .unwrap_or_default();
     let latest_reads_latency = if historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last()
+        .rfind(|b| b.device_name == device_name)
         .map_or(0_f64, |latest| latest.reads_time_spent_ms)
         == 0_f64
     {
@@ -1729,14 +2187,12 @@ fn blockdevice_latency_queuedepth_plot(
     } else {
         historical_data_read
             .iter()
-            .filter(|b| b.device_name == device_name)
-            .last()
+            .rfind(|b| b.device_name == device_name)
             .unwrap()
             .reads_time_spent_ms
             / historical_data_read
                 .iter()
-                .filter(|b| b.device_name == device_name)
-                .last()
+                .rfind(|b| b.device_name == device_name)
                 .unwrap()
                 .reads_completed_success
     };
@@ -1794,8 +2250,7 @@ fn blockdevice_latency_queuedepth_plot(
         .unwrap_or_default();
     let latest_discard_latency = if historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last()
+        .rfind(|b| b.device_name == device_name)
         .map_or(0_f64, |latest| latest.discards_completed_success)
         == 0_f64
     {
@@ -1803,14 +2258,12 @@ fn blockdevice_latency_queuedepth_plot(
     } else {
         historical_data_read
             .iter()
-            .filter(|b| b.device_name == device_name)
-            .last()
+            .rfind(|b| b.device_name == device_name)
             .unwrap()
             .discards_time_spent_ms
             / historical_data_read
                 .iter()
-                .filter(|b| b.device_name == device_name)
-                .last()
+                .rfind(|b| b.device_name == device_name)
                 .unwrap()
                 .discards_completed_success
     };
@@ -1868,8 +2321,7 @@ fn blockdevice_latency_queuedepth_plot(
         .unwrap_or_default();
     let latest_flush_latency = if historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last()
+        .rfind(|b| b.device_name == device_name)
         .map_or(0_f64, |latest| latest.flush_requests_completed_success)
         == 0_f64
     {
@@ -1877,14 +2329,12 @@ fn blockdevice_latency_queuedepth_plot(
     } else {
         historical_data_read
             .iter()
-            .filter(|b| b.device_name == device_name)
-            .last()
+            .rfind(|b| b.device_name == device_name)
             .unwrap()
             .flush_requests_time_spent_ms
             / historical_data_read
                 .iter()
-                .filter(|b| b.device_name == device_name)
-                .last()
+                .rfind(|b| b.device_name == device_name)
                 .unwrap()
                 .flush_requests_completed_success
     };
@@ -1945,8 +2395,7 @@ fn blockdevice_latency_queuedepth_plot(
         .unwrap_or_default();
     let latest_queue_depth = historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last()
+        .rfind(|b| b.device_name == device_name)
         .map_or(0_f64, |latest| latest.ios_weighted_time_spent_ms)
         / 1000_f64;
     contextarea
@@ -1987,8 +2436,7 @@ fn blockdevice_latency_queuedepth_plot(
         .unwrap_or_default();
     let latest_inflight_writes = historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last()
+        .rfind(|b| b.device_name == device_name)
         .map_or(0_f64, |latest| latest.inflight_writes);
     contextarea
         .draw_secondary_series(
@@ -2022,8 +2470,7 @@ fn blockdevice_latency_queuedepth_plot(
         .unwrap_or_default();
     let latest_inflight_reads = historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last()
+        .rfind(|b| b.device_name == device_name)
         .map_or(0_f64, |latest| latest.inflight_reads);
     contextarea
         .draw_secondary_series(
@@ -2043,11 +2490,10 @@ fn blockdevice_latency_queuedepth_plot(
         .legend(move |(x, y)| TriangleMarker::new((x, y), 3, GREEN_500.filled()));
     // max queue size
     // It wouldn't make sense to use total, because it combines different blockdevices.
-    if device_name != "TOTAL" {
+    if !is_total(&device_name) {
         let latest_queue_nr_requests = historical_data_read
             .iter()
-            .filter(|b| b.device_name == device_name)
-            .last()
+            .rfind(|b| b.device_name == device_name)
             .map_or(0_f64, |latest| latest.queue_nr_requests);
         contextarea
             .draw_secondary_series(LineSeries::new(
@@ -2075,8 +2521,8 @@ fn blockdevice_latency_queuedepth_plot(
         .unwrap();
 }
 
-fn blockdevice_iosize_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub(crate) fn blockdevice_iosize_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     device_name: String,
     start_time: Option<DateTime<Local>>,
@@ -2142,8 +2588,7 @@ fn blockdevice_iosize_plot(
         .max(high_value_max_io_size);
     let latest = historical_data_read
         .iter()
-        .filter(|blockdevice| blockdevice.device_name == device_name)
-        .last();
+        .rfind(|blockdevice| blockdevice.device_name == device_name);
 
     // create the plot
     let mut contextarea = ChartBuilder::on(&multi_backend[backend_number])
@@ -2328,8 +2773,8 @@ fn blockdevice_iosize_plot(
         .unwrap();
 }
 
-fn blockdevice_extra(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub(crate) fn blockdevice_extra<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     device_name: String,
 ) {
@@ -2341,8 +2786,7 @@ fn blockdevice_extra(
     // LABELS_STYLE_FONT _SIZE  15
     let latest = historical_data_read
         .iter()
-        .filter(|b| b.device_name == device_name)
-        .last();
+        .rfind(|b| b.device_name == device_name);
     multi_backend[backend_number]
         .draw(&Text::new(
             format!("device:              {:>10}", device_name),
@@ -2501,4 +2945,453 @@ fn blockdevice_extra(
             (MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE).into_font(),
         ))
         .unwrap();
+    multi_backend[backend_number]
+        .draw(&Text::new(
+            format!(
+                "kernel name:         {:>10}",
+                latest.map_or("".to_string(), |d| d.device_kernel_name.clone())
+            ),
+            (1100, 40),
+            (MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE).into_font(),
+        ))
+        .unwrap();
+    multi_backend[backend_number]
+        .draw(&Text::new(
+            format!(
+                "slaves:              {}",
+                latest.map_or("".to_string(), |d| d.slaves.join(", "))
+            ),
+            (1100, 60),
+            (MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE).into_font(),
+        ))
+        .unwrap();
+    multi_backend[backend_number]
+        .draw(&Text::new(
+            format!(
+                "holders:             {}",
+                latest.map_or("".to_string(), |d| d.holders.join(", "))
+            ),
+            (1100, 80),
+            (MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE).into_font(),
+        ))
+        .unwrap();
+}
+
+pub fn blockdevice_mbps_panel(
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| b.device_name == device_name),
+        |b| b.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: format!("Blockdevice: {} MBPS", device_name),
+        y_desc: "MBPS".to_string(),
+        series: vec![
+            Series::new("total", &rows, |b| {
+                (
+                    b.timestamp,
+                    (b.reads_bytes + b.writes_bytes) / (1024_f64 * 1024_f64),
+                )
+            }),
+            Series::new("write", &rows, |b| {
+                (b.timestamp, b.writes_bytes / (1024_f64 * 1024_f64))
+            })
+            .as_points(),
+            Series::new("read", &rows, |b| {
+                (b.timestamp, b.reads_bytes / (1024_f64 * 1024_f64))
+            })
+            .as_points(),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn blockdevice_iops_panel(
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| b.device_name == device_name),
+        |b| b.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: format!("Blockdevice: {} IOPS", device_name),
+        y_desc: "IOPS".to_string(),
+        series: vec![
+            Series::new("total", &rows, |b| {
+                (
+                    b.timestamp,
+                    b.reads_completed_success
+                        + b.writes_completed_success
+                        + b.discards_completed_success,
+                )
+            }),
+            Series::new("write", &rows, |b| {
+                (b.timestamp, b.writes_completed_success)
+            })
+            .as_points(),
+            Series::new("read", &rows, |b| (b.timestamp, b.reads_completed_success)).as_points(),
+            Series::new("discard", &rows, |b| {
+                (b.timestamp, b.discards_completed_success)
+            })
+            .as_points(),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn blockdevice_latency_queuedepth_panel(
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| b.device_name == device_name),
+        |b| b.timestamp,
+        start_time,
+        end_time,
+    );
+    let latency = |time_spent_ms: f64, completed: f64| {
+        if completed == 0_f64 {
+            0_f64
+        } else {
+            time_spent_ms / completed
+        }
+    };
+    let mut series = vec![
+        Series::new("read latency", &rows, |b| {
+            (
+                b.timestamp,
+                latency(b.reads_time_spent_ms, b.reads_completed_success),
+            )
+        }),
+        Series::new("write latency", &rows, |b| {
+            (
+                b.timestamp,
+                latency(b.writes_time_spent_ms, b.writes_completed_success),
+            )
+        }),
+        Series::new("discard latency", &rows, |b| {
+            (
+                b.timestamp,
+                latency(b.discards_time_spent_ms, b.discards_completed_success),
+            )
+        }),
+        Series::new("flush latency", &rows, |b| {
+            (
+                b.timestamp,
+                latency(
+                    b.flush_requests_time_spent_ms,
+                    b.flush_requests_completed_success,
+                ),
+            )
+        }),
+        Series::new("queue depth", &rows, |b| {
+            (b.timestamp, b.ios_weighted_time_spent_ms / 1000_f64)
+        })
+        .on_secondary_axis(),
+        Series::new("inflight reads", &rows, |b| (b.timestamp, b.inflight_reads))
+            .on_secondary_axis()
+            .as_points(),
+        Series::new("inflight writes", &rows, |b| {
+            (b.timestamp, b.inflight_writes)
+        })
+        .on_secondary_axis()
+        .as_points(),
+    ];
+    if !is_total(device_name) {
+        series.push(
+            Series::new("nr_requests", &rows, |b| (b.timestamp, b.queue_nr_requests))
+                .on_secondary_axis(),
+        );
+    }
+    Panel {
+        title: format!("Blockdevice: {} latency and queue depth", device_name),
+        y_desc: "ms".to_string(),
+        y2_desc: Some("queue depth".to_string()),
+        series,
+        ..Default::default()
+    }
+}
+
+pub fn blockdevice_iosize_panel(
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| b.device_name == device_name),
+        |b| b.timestamp,
+        start_time,
+        end_time,
+    );
+    let read_rows: Vec<_> = rows
+        .iter()
+        .copied()
+        .filter(|b| b.reads_completed_success > 0_f64)
+        .collect();
+    let write_rows: Vec<_> = rows
+        .iter()
+        .copied()
+        .filter(|b| b.writes_completed_success > 0_f64)
+        .collect();
+    Panel {
+        title: format!("Blockdevice: {} IO size", device_name),
+        y_desc: "KB".to_string(),
+        series: vec![
+            Series::new("write", &write_rows, |b| {
+                (
+                    b.timestamp,
+                    (b.writes_bytes / b.writes_completed_success) / 1024_f64,
+                )
+            })
+            .as_points(),
+            Series::new("read", &read_rows, |b| {
+                (
+                    b.timestamp,
+                    (b.reads_bytes / b.reads_completed_success) / 1024_f64,
+                )
+            })
+            .as_points(),
+            Series::new("max_sectors_kb", &rows, |b| {
+                (b.timestamp, b.queue_max_sectors_kb)
+            }),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn blockdevice_extra_panel(device_name: &str) -> Panel {
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let mut info = Vec::new();
+    if let Some(latest) = historical_data_read
+        .iter()
+        .rfind(|b| b.device_name == device_name)
+    {
+        info.push(("major:minor".to_string(), latest.device_major_minor.clone()));
+        macro_rules! add_info {
+            ($([$name:expr, $struct_field_name:ident]),*) => {
+                $(
+                    info.push(($name.to_string(), latest.$struct_field_name.to_string()));
+                )*
+            };
+        }
+        add_info!(
+            ["removable", removable],
+            ["readonly", ro],
+            ["rotational", queue_rotational],
+            ["dax", queue_dax],
+            ["nr_requests", queue_nr_requests],
+            ["max_sectors_kb", queue_max_sectors_kb],
+            ["max_hw_sectors_kb", queue_max_hw_sectors_kb],
+            ["hw_sector_size", queue_hw_sector_size],
+            ["logical_block_size", queue_logical_block_size],
+            ["nomerges", queue_nomerges],
+            ["physical_block_size", queue_physical_block_size],
+            ["discard_max_bytes", queue_discard_max_bytes],
+            ["discard_max_hw_bytes", queue_discard_max_hw_bytes],
+            ["read_ahead_kb", queue_read_ahead_kb]
+        );
+        if !latest.device_kernel_name.is_empty() {
+            info.push(("kernel name".to_string(), latest.device_kernel_name.clone()));
+        }
+        if !latest.slaves.is_empty() {
+            info.push(("slaves".to_string(), latest.slaves.join(", ")));
+        }
+        if !latest.holders.is_empty() {
+            info.push(("holders".to_string(), latest.holders.join(", ")));
+        }
+    }
+    Panel {
+        title: format!("Blockdevice: {} extra info", device_name),
+        info,
+        ..Default::default()
+    }
+}
+
+/// The interactive equivalent of the topology plot: the MBPS and latency of the device and the
+/// devices it is built on or that are built on it. Empty if the device is not stacked.
+pub fn blockdevice_topology_panels(
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Vec<Panel> {
+    let topology = topology_devices(device_name);
+    if topology.is_empty() {
+        return Vec::new();
+    }
+    let devices = std::iter::once(device_name.to_string())
+        .chain(topology)
+        .collect::<Vec<String>>();
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let per_device = |value: fn(&BlockDeviceInfo) -> f64| {
+        devices
+            .iter()
+            .map(|device| {
+                let rows = rows_in_range(
+                    historical_data_read
+                        .iter()
+                        .filter(|b| &b.device_name == device),
+                    |b| b.timestamp,
+                    start_time,
+                    end_time,
+                );
+                Series::new(device, &rows, |b| (b.timestamp, value(b)))
+            })
+            .collect::<Vec<Series>>()
+    };
+    vec![
+        Panel {
+            title: format!("Blockdevice: {} and related devices MBPS", device_name),
+            y_desc: "MBPS".to_string(),
+            series: per_device(total_mbps),
+            ..Default::default()
+        },
+        Panel {
+            title: format!("Blockdevice: {} and related devices latency", device_name),
+            y_desc: "Average latency (ms)".to_string(),
+            series: per_device(average_latency),
+            ..Default::default()
+        },
+    ]
+}
+
+pub fn blockdevice_utilization_panel(
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| b.device_name == device_name),
+        |b| b.timestamp,
+        start_time,
+        end_time,
+    );
+    let rotational = rows.last().is_some_and(|b| b.queue_rotational != 0_f64);
+    Panel {
+        title: utilization_caption(device_name, rotational),
+        y_desc: "%".to_string(),
+        y2_desc: Some("service time (ms)".to_string()),
+        series: vec![
+            Series::new("%util", &rows, |b| (b.timestamp, utilization(b))),
+            Series::new("%qsat (aqu-sz/nr_requests)", &rows, |b| {
+                (b.timestamp, queue_saturation(b))
+            }),
+            Series::new("svctm", &rows, |b| (b.timestamp, service_time(b)))
+                .on_secondary_axis()
+                .as_points(),
+        ],
+        ..Default::default()
+    }
+}
+
+/// The interactive equivalent of a latency heatmap: the IOs per second of every latency bucket,
+/// stacked.
+pub fn blockdevice_latency_panel(
+    device_name: &str,
+    io_type: &str,
+    latency: fn(&BlockDeviceInfo) -> (f64, f64),
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.blockdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| b.device_name == device_name),
+        |b| b.timestamp,
+        start_time,
+        end_time,
+    );
+    let buckets = rows
+        .iter()
+        .filter(|b| latency(b).0 > 0_f64)
+        .map(|b| latency_bucket(latency(b).1))
+        .collect::<BTreeSet<usize>>();
+    Panel {
+        title: format!(
+            "Blockdevice: {} {} latency distribution",
+            device_name, io_type
+        ),
+        y_desc: "IOPS per latency bucket".to_string(),
+        series: buckets
+            .into_iter()
+            .map(|bucket| {
+                Series::new(&latency_bucket_name(bucket), &rows, |b| {
+                    let (ios_per_second, latency_ms) = latency(b);
+                    if ios_per_second > 0_f64 && latency_bucket(latency_ms) == bucket {
+                        (b.timestamp, ios_per_second)
+                    } else {
+                        (b.timestamp, 0_f64)
+                    }
+                })
+                .stacked("ios")
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn latency_histogram_counts_ios_per_bucket() {
+        assert_eq!(latency_bucket(0.005), 0);
+        assert_eq!(latency_bucket(0.01), 1);
+        assert_eq!(latency_bucket(1.5), 7);
+        assert_eq!(latency_bucket(10000.), LATENCY_BUCKETS_MS.len());
+        assert_eq!(latency_bucket_name(7), "1ms-2ms");
+        assert_eq!(latency_bucket_name(0), "<10us");
+
+        let start: DateTime<Local> = DateTime::parse_from_rfc3339("2023-12-13T15:20:24+00:00")
+            .unwrap()
+            .into();
+        // 100 reads per second at 1.5ms, with a 5 second gap, and then one interval at 300ms.
+        let rows = [(0, 1.5), (1, 1.5), (6, 1.5), (7, 300.)]
+            .iter()
+            .map(|(seconds, latency_ms)| BlockDeviceInfo {
+                timestamp: start + Duration::seconds(*seconds),
+                reads_completed_success: 100.,
+                reads_time_spent_ms: 100. * latency_ms,
+                ..Default::default()
+            })
+            .collect::<Vec<BlockDeviceInfo>>();
+        let rows = rows.iter().collect::<Vec<&BlockDeviceInfo>>();
+        let histogram = latency_histogram(&rows, read_latency, 2);
+
+        assert_eq!(histogram[0][latency_bucket(1.5)], 200.);
+        assert_eq!(histogram[1][latency_bucket(1.5)], 500.);
+        assert_eq!(histogram[1][latency_bucket(300.)], 100.);
+        assert_eq!(histogram.iter().flatten().sum::<f64>(), 800.);
+        assert!(latency_histogram(&rows, write_latency, 2)
+            .iter()
+            .flatten()
+            .all(|ios| *ios == 0_f64));
+    }
 }
@@ -0,0 +1,300 @@
+use axum::response::Html;
+use axum_session::{Session, SessionNullPool};
+use chrono::{DateTime, Local};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::fmt::Write;
+
+use crate::processor::pressure::PressureInfo;
+use crate::processor::Data;
+use crate::webserver::blockdevice::average_latency;
+use crate::webserver::compare::overlay_panel_plot;
+use crate::webserver::hosts::host_label;
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
+use crate::DATA;
+
+type Values = Vec<(DateTime<Local>, f64)>;
+type TimeRange = (Option<DateTime<Local>>, Option<DateTime<Local>>);
+
+/// A metric that is shown for all hosts of the cluster.
+struct ClusterMetric {
+    title: &'static str,
+    unit: &'static str,
+    values: fn(&Data, TimeRange) -> Values,
+}
+
+static CLUSTER_METRICS: [ClusterMetric; 5] = [
+    ClusterMetric {
+        title: "CPU busy",
+        unit: "%",
+        values: cpu_busy,
+    },
+    ClusterMetric {
+        title: "CPU pressure some avg10",
+        unit: "%",
+        values: |data, time_range| pressure(data, time_range, |p| p.cpu_some_avg10),
+    },
+    ClusterMetric {
+        title: "Memory pressure some avg10",
+        unit: "%",
+        values: |data, time_range| pressure(data, time_range, |p| p.memory_some_avg10),
+    },
+    ClusterMetric {
+        title: "IO pressure some avg10",
+        unit: "%",
+        values: |data, time_range| pressure(data, time_range, |p| p.io_some_avg10),
+    },
+    ClusterMetric {
+        title: "Disk latency TOTAL",
+        unit: "ms",
+        values: disk_latency,
+    },
+];
+
+fn cpu_busy(data: &Data, (start_time, end_time): TimeRange) -> Values {
+    let historical_data_read = data.cpu.read().unwrap();
+    rows_in_range(
+        historical_data_read.iter(),
+        |c| c.timestamp,
+        start_time,
+        end_time,
+    )
+    .iter()
+    .map(|c| {
+        // guest time is included in user time.
+        let total = c.user + c.nice + c.system + c.idle + c.iowait + c.irq + c.softirq + c.steal;
+        let busy = total - c.idle - c.iowait;
+        (
+            c.timestamp,
+            if total == 0_f64 {
+                0_f64
+            } else {
+                busy / total * 100_f64
+            },
+        )
+    })
+    .collect()
+}
+
+fn pressure(
+    data: &Data,
+    (start_time, end_time): TimeRange,
+    value: fn(&PressureInfo) -> f64,
+) -> Values {
+    let historical_data_read = data.pressure.read().unwrap();
+    rows_in_range(
+        historical_data_read.iter(),
+        |p| p.timestamp,
+        start_time,
+        end_time,
+    )
+    .iter()
+    .map(|p| (p.timestamp, value(p)))
+    .collect()
+}
+
+fn disk_latency(data: &Data, (start_time, end_time): TimeRange) -> Values {
+    let historical_data_read = data.blockdevices.read().unwrap();
+    rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|b| b.device_name == "TOTAL"),
+        |b| b.timestamp,
+        start_time,
+        end_time,
+    )
+    .iter()
+    .map(|b| (b.timestamp, average_latency(b)))
+    .collect()
+}
+
+fn average(values: &Values) -> f64 {
+    if values.is_empty() {
+        0_f64
+    } else {
+        values.iter().map(|(_, value)| value).sum::<f64>() / values.len() as f64
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    if sorted.is_empty() {
+        0_f64
+    } else if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2_f64
+    } else {
+        sorted[middle]
+    }
+}
+
+/// Whether the value of every host is an outlier: higher than the median of the hosts by more
+/// than three times the median absolute deviation, and by at least 1 (% or ms), so that small
+/// differences of idle hosts are not shown. This needs at least 3 hosts.
+pub fn outliers(values: &[f64]) -> Vec<bool> {
+    if values.len() < 3 {
+        return vec![false; values.len()];
+    }
+    let median_value = median(values);
+    let deviation = median(
+        &values
+            .iter()
+            .map(|value| (value - median_value).abs())
+            .collect::<Vec<f64>>(),
+    );
+    values
+        .iter()
+        .map(|value| value - median_value > (3_f64 * deviation).max(1_f64))
+        .collect()
+}
+
+/// The values of a metric of every host, with the average and whether the host is an outlier.
+fn metric_by_host(
+    metric: &ClusterMetric,
+    hostnames: &[String],
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Vec<(String, Values, f64, bool)> {
+    let values = hostnames
+        .iter()
+        .map(|hostname| {
+            let values = (metric.values)(DATA.host(hostname), (start_time, end_time));
+            (hostname.to_string(), values)
+        })
+        .collect::<Vec<(String, Values)>>();
+    let averages = values
+        .iter()
+        .map(|(_, values)| average(values))
+        .collect::<Vec<f64>>();
+    values
+        .into_iter()
+        .zip(outliers(&averages))
+        .zip(averages)
+        .map(|(((hostname, values), outlier), average)| (hostname, values, average, outlier))
+        .collect()
+}
+
+/// A panel for every cluster metric, with a series for every host.
+pub fn cluster_panels(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Vec<Panel> {
+    let hostnames = DATA.hostnames();
+    CLUSTER_METRICS
+        .iter()
+        .map(|metric| Panel {
+            title: metric.title.to_string(),
+            y_desc: metric.unit.to_string(),
+            series: metric_by_host(metric, &hostnames, start_time, end_time)
+                .into_iter()
+                .map(|(hostname, values, _, outlier)| Series {
+                    name: format!(
+                        "{}{}",
+                        host_label(&hostname),
+                        if outlier { " (outlier)" } else { "" }
+                    ),
+                    values: values
+                        .iter()
+                        .map(|(timestamp, value)| (timestamp.timestamp_millis(), *value))
+                        .collect(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The cluster panels as plotters charts.
+pub fn cluster_plot<DB: DrawingBackend>(
+    backend: &DrawingArea<DB, Shift>,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) {
+    backend.fill(&WHITE).unwrap();
+    let panels = cluster_panels(start_time, end_time);
+    let multi_backend = backend.split_evenly((panels.len(), 1));
+    for (panel, area) in panels.iter().zip(multi_backend.iter()) {
+        overlay_panel_plot(area, panel, &panel.title, false);
+    }
+}
+
+/// The cluster page: a table with the average of the cluster metrics of every host with the
+/// outliers marked, and the cluster plot.
+pub async fn handler_cluster(session: Session<SessionNullPool>) -> Html<String> {
+    let start_time = session.get::<DateTime<Local>>("start_time");
+    let end_time = session.get::<DateTime<Local>>("end_time");
+    // the host names are read once, as a federated host can be added meanwhile.
+    let hostnames = DATA.hostnames();
+    let metrics = CLUSTER_METRICS
+        .iter()
+        .map(|metric| metric_by_host(metric, &hostnames, start_time, end_time))
+        .collect::<Vec<_>>();
+    let html_for_header = CLUSTER_METRICS
+        .iter()
+        .fold(String::new(), |mut output, metric| {
+            let _ = write!(output, "<th>{} ({})</th>", metric.title, metric.unit);
+            output
+        });
+    let html_for_rows =
+        hostnames
+            .iter()
+            .enumerate()
+            .fold(String::new(), |mut output, (index, hostname)| {
+                let _ = write!(output, "<tr><td>{}</td>", host_label(hostname));
+                for metric in &metrics {
+                    let (_, _, average, outlier) = &metric[index];
+                    let _ = write!(
+                        output,
+                        r#"<td{}>{:.2}</td>"#,
+                        if *outlier { r#" class="outlier""# } else { "" },
+                        average
+                    );
+                }
+                output.push_str("</tr>");
+                output
+            });
+
+    format!(
+        r##"<!doctype html>
+ <html>
+  <head>
+   <style>
+    body {{ font-family: monospace; }}
+    table {{ border-collapse: collapse; }}
+    td, th {{ border: 1px solid grey; padding: 2px 8px; text-align: right; }}
+    td:first-child {{ text-align: left; }}
+    td.outlier {{ background: #ff9999; font-weight: bold; }}
+   </style>
+  </head>
+  <body>
+   <p>The averages of the hosts; outliers are marked.</p>
+   <table>
+    <tr><th>host</th>{html_for_header}</tr>
+    {html_for_rows}
+   </table>
+   <a href="/interactive/cluster/x">interactive view</a><br>
+   <img src="/plotter/cluster/x">
+  </body>
+ </html>
+ "##
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outliers_are_far_from_the_median() {
+        assert_eq!(
+            outliers(&[10_f64, 12_f64, 11_f64, 45_f64]),
+            vec![false, false, false, true]
+        );
+        // small differences of idle hosts are not outliers.
+        assert_eq!(outliers(&[0_f64, 0_f64, 0.5_f64]), vec![false; 3]);
+        assert_eq!(outliers(&[0_f64, 50_f64]), vec![false; 2]);
+    }
+}
@@ -0,0 +1,477 @@
+use axum::extract::Query;
+use axum::response::Html;
+use axum_session::{Session, SessionNullPool};
+use chrono::{DateTime, Local, TimeZone};
+use plotters::chart::{ChartBuilder, LabelAreaPosition, SeriesLabelPosition::UpperLeft};
+use plotters::coord::Shift;
+use plotters::element::{Circle, Rectangle};
+use plotters::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::processor::rollup::Aggregate;
+use crate::report::summary;
+use crate::webserver::interactive::{create_layout, Layout, Panel, Series};
+use crate::{parse_time, DATA};
+use crate::{
+    CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
+    LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, LABEL_AREA_SIZE_RIGHT, MESH_STYLE_FONT,
+    MESH_STYLE_FONT_SIZE,
+};
+
+/// The baseline and candidate time ranges of a comparison.
+#[derive(Debug, Clone, Copy)]
+pub struct Comparison {
+    pub baseline_start: DateTime<Local>,
+    pub baseline_end: DateTime<Local>,
+    pub candidate_start: DateTime<Local>,
+    pub candidate_end: DateTime<Local>,
+}
+
+/// The plot groups that can be compared; the device of the device specific plot groups is
+/// chosen separately.
+pub(crate) static COMPARE_PLOTS: [&str; 17] = [
+    "cpu",
+    "cpu_load",
+    "cpu_load_psi",
+    "memory",
+    "memory_alloc",
+    "memory_commit",
+    "memory_psi",
+    "memory_psi_alloc",
+    "memory_swap",
+    "memory_swap_inout",
+    "memory_act_inact",
+    "memory_dirty",
+    "xfs",
+    "blockdevice",
+    "blockdevice_psi",
+    "blockdevice_extra",
+    "networkdevice",
+];
+
+/// Create the layout of a plot group for the baseline and the candidate range, and overlay them
+/// with the time relative to the start of each range.
+/// The series are ordered in pairs of the baseline and the candidate series of the same metric.
+pub fn comparison_layout(plot_1: &str, plot_2: &str, comparison: &Comparison) -> Layout {
+    let baseline = create_layout(
+        plot_1,
+        plot_2,
+        Some(comparison.baseline_start),
+        Some(comparison.baseline_end),
+        Aggregate::Avg,
+    );
+    let candidate = create_layout(
+        plot_1,
+        plot_2,
+        Some(comparison.candidate_start),
+        Some(comparison.candidate_end),
+        Aggregate::Avg,
+    );
+    let relative = |series: Series, range: &str, start_time: DateTime<Local>| Series {
+        name: format!("{} {}", range, series.name),
+        values: series
+            .values
+            .iter()
+            .map(|(timestamp, value)| (timestamp - start_time.timestamp_millis(), *value))
+            .collect(),
+        // stacked areas of two ranges hide each other, so all series are drawn as lines.
+        stack: None,
+        ..series
+    };
+    let panels = baseline
+        .panels
+        .into_iter()
+        .zip(candidate.panels)
+        .filter(|(baseline, _)| !baseline.series.is_empty())
+        .map(|(baseline, candidate)| Panel {
+            title: baseline.title,
+            y_desc: baseline.y_desc,
+            y2_desc: baseline.y2_desc,
+            series: baseline
+                .series
+                .into_iter()
+                .zip(candidate.series)
+                .flat_map(|(baseline_series, candidate_series)| {
+                    [
+                        relative(baseline_series, "baseline", comparison.baseline_start),
+                        relative(candidate_series, "candidate", comparison.candidate_start),
+                    ]
+                })
+                .collect(),
+            ..Default::default()
+        })
+        .collect();
+    Layout {
+        plot: plot_1.to_string(),
+        device: plot_2.to_string(),
+        panels,
+        ..Default::default()
+    }
+}
+
+/// Draw the comparison of a plot group, with a chart per panel.
+pub fn draw_comparison<DB: DrawingBackend>(
+    backend: DrawingArea<DB, Shift>,
+    plot_1: &str,
+    plot_2: &str,
+    comparison: &Comparison,
+) {
+    backend.fill(&WHITE).unwrap();
+    let layout = comparison_layout(plot_1, plot_2, comparison);
+    if layout.panels.is_empty() {
+        return;
+    }
+    let multi_backend = backend.split_evenly((layout.panels.len(), 1));
+    for (panel, area) in layout.panels.iter().zip(multi_backend.iter()) {
+        overlay_panel_plot(
+            area,
+            panel,
+            &format!("{}: baseline versus candidate", panel.title),
+            true,
+        );
+    }
+}
+
+fn relative_time(milliseconds: i64) -> String {
+    let seconds = milliseconds / 1000;
+    format!(
+        "+{:02}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Draw the series of a panel of an overlay on a chart. With relative, the time is the time since
+/// the start of the ranges, and the series are pairs of the baseline and candidate series of the
+/// same metric; otherwise the time is the time of the day, and every series has its own colour.
+pub(crate) fn overlay_panel_plot<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    panel: &Panel,
+    caption: &str,
+    relative: bool,
+) {
+    let timestamps = || {
+        panel
+            .series
+            .iter()
+            .flat_map(|series| series.values.iter().map(|(timestamp, _)| *timestamp))
+    };
+    let low_time = if relative {
+        0
+    } else {
+        timestamps().min().unwrap_or_default()
+    };
+    let high_time = timestamps().max().unwrap_or_default().max(low_time + 1);
+    let time_of_day = |milliseconds: i64| {
+        Local
+            .timestamp_millis_opt(milliseconds)
+            .single()
+            .map(|timestamp| timestamp.format("%H:%M:%S").to_string())
+            .unwrap_or_default()
+    };
+    let high_value = |secondary_axis: bool| {
+        let high_value = panel
+            .series
+            .iter()
+            .filter(|series| series.secondary_axis == secondary_axis)
+            .flat_map(|series| series.values.iter().map(|(_, value)| *value))
+            .fold(0_f64, f64::max)
+            * 1.1_f64;
+        if high_value == 0_f64 {
+            1_f64
+        } else {
+            high_value
+        }
+    };
+
+    let mut contextarea = ChartBuilder::on(area)
+        .set_label_area_size(LabelAreaPosition::Left, LABEL_AREA_SIZE_LEFT)
+        .set_label_area_size(LabelAreaPosition::Bottom, LABEL_AREA_SIZE_BOTTOM)
+        .set_label_area_size(LabelAreaPosition::Right, LABEL_AREA_SIZE_RIGHT)
+        .caption(caption, (CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE))
+        .build_cartesian_2d(low_time..high_time, 0_f64..high_value(false))
+        .unwrap()
+        .set_secondary_coord(low_time..high_time, 0_f64..high_value(true));
+    contextarea
+        .configure_mesh()
+        .x_labels(6)
+        .x_label_formatter(&|milliseconds| {
+            if relative {
+                relative_time(*milliseconds)
+            } else {
+                time_of_day(*milliseconds)
+            }
+        })
+        .x_desc(if relative { "Time since start" } else { "Time" })
+        .y_desc(&panel.y_desc)
+        .label_style((MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE))
+        .draw()
+        .unwrap();
+    if let Some(y2_desc) = &panel.y2_desc {
+        contextarea
+            .configure_secondary_axes()
+            .y_desc(y2_desc)
+            .label_style((MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE))
+            .draw()
+            .unwrap();
+    }
+    for (index, series) in panel.series.iter().enumerate() {
+        // the baseline and candidate series of a metric share the colour, the baseline is lighter.
+        let style = if !relative {
+            Palette99::pick(index).stroke_width(2)
+        } else if index % 2 == 0 {
+            Palette99::pick(index / 2).mix(0.4).stroke_width(1)
+        } else {
+            Palette99::pick(index / 2).stroke_width(2)
+        };
+        macro_rules! draw_series {
+            ($draw_function:ident) => {
+                if series.points {
+                    contextarea.$draw_function(series.values.iter().map(|(timestamp, value)| {
+                        Circle::new((*timestamp, *value), 2, style.filled())
+                    }))
+                } else {
+                    contextarea
+                        .$draw_function(LineSeries::new(series.values.iter().copied(), style))
+                }
+            };
+        }
+        let series_annotation = if series.secondary_axis {
+            draw_series!(draw_secondary_series)
+        } else {
+            draw_series!(draw_series)
+        };
+        series_annotation
+            .unwrap()
+            .label(&series.name)
+            .legend(move |(x, y)| Rectangle::new([(x - 3, y - 3), (x + 3, y + 3)], style.filled()));
+    }
+    contextarea
+        .configure_series_labels()
+        .border_style(BLACK)
+        .background_style(WHITE.mix(0.7))
+        .label_font((LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE))
+        .position(UpperLeft)
+        .draw()
+        .unwrap();
+}
+
+/// A html table with the summary statistics of the baseline and the candidate range, and the
+/// difference of the averages.
+pub fn comparison_summary_html(comparison: &Comparison) -> String {
+    let baseline = summary(
+        &DATA,
+        Some(comparison.baseline_start),
+        Some(comparison.baseline_end),
+    );
+    let candidate = summary(
+        &DATA,
+        Some(comparison.candidate_start),
+        Some(comparison.candidate_end),
+    );
+    let rows = baseline.iter().zip(candidate.iter()).fold(
+        String::new(),
+        |mut output, ((metric, _, baseline_avg, baseline_max), (_, _, candidate_avg, candidate_max))| {
+            let difference = if *baseline_avg == 0_f64 {
+                "-".to_string()
+            } else {
+                format!("{:+.1}%", (candidate_avg - baseline_avg) / baseline_avg * 100_f64)
+            };
+            let _ = write!(
+                output,
+                "<tr><td>{}</td><td>{:.2}</td><td>{:.2}</td><td>{}</td><td>{:.2}</td><td>{:.2}</td></tr>",
+                metric, baseline_avg, candidate_avg, difference, baseline_max, candidate_max
+            );
+            output
+        },
+    );
+    format!(
+        r##"<p>baseline: {} - {}<br>candidate: {} - {}</p>
+   <table>
+    <tr><th>metric</th><th>baseline avg</th><th>candidate avg</th><th>difference</th><th>baseline max</th><th>candidate max</th></tr>
+    {}
+   </table>"##,
+        comparison.baseline_start.format("%Y-%m-%d %H:%M:%S %:z"),
+        comparison.baseline_end.format("%Y-%m-%d %H:%M:%S %:z"),
+        comparison.candidate_start.format("%Y-%m-%d %H:%M:%S %:z"),
+        comparison.candidate_end.format("%Y-%m-%d %H:%M:%S %:z"),
+        rows
+    )
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CompareQuery {
+    pub plot: Option<String>,
+    pub device: Option<String>,
+    pub baseline_start: Option<String>,
+    pub baseline_end: Option<String>,
+    pub candidate_start: Option<String>,
+    pub candidate_end: Option<String>,
+}
+
+impl CompareQuery {
+    /// The comparison, if all times are set and valid.
+    /// The times are seconds since the epoch, or any format that parse_time accepts.
+    pub fn comparison(&self) -> Option<Comparison> {
+        let time = |time: &Option<String>| {
+            time.as_ref().and_then(|time| {
+                time.parse::<i64>()
+                    .ok()
+                    .and_then(|seconds| Local.timestamp_opt(seconds, 0).single())
+                    .or_else(|| parse_time(time).ok())
+            })
+        };
+        Some(Comparison {
+            baseline_start: time(&self.baseline_start)?,
+            baseline_end: time(&self.baseline_end)?,
+            candidate_start: time(&self.candidate_start)?,
+            candidate_end: time(&self.candidate_end)?,
+        })
+    }
+    /// The comparison as query string, to pass it on to the plotter url.
+    pub fn query_string(&self, comparison: &Comparison) -> String {
+        format!(
+            "plot={}&device={}&baseline_start={}&baseline_end={}&candidate_start={}&candidate_end={}",
+            self.plot.clone().unwrap_or_default(),
+            self.device.clone().unwrap_or_default(),
+            comparison.baseline_start.timestamp(),
+            comparison.baseline_end.timestamp(),
+            comparison.candidate_start.timestamp(),
+            comparison.candidate_end.timestamp(),
+        )
+    }
+}
+
+/// The compare page: a form to select the plot group, device and the baseline and candidate
+/// ranges, and the comparison if these are set.
+pub async fn handler_compare(
+    session: Session<SessionNullPool>,
+    Query(compare_query): Query<CompareQuery>,
+) -> Html<String> {
+    let host = session.get::<String>("host");
+    DATA.with_host(host.as_deref(), || compare_html(&compare_query))
+        .into()
+}
+
+fn compare_html(compare_query: &CompareQuery) -> String {
+    let selected = |value: &str, current: &Option<String>| {
+        if current.as_deref() == Some(value) {
+            " selected"
+        } else {
+            ""
+        }
+    };
+    let html_for_plots = COMPARE_PLOTS
+        .iter()
+        .fold(String::new(), |mut output, plot| {
+            let _ = write!(
+                output,
+                r#"<option value="{plot}"{}>{plot}</option>"#,
+                selected(plot, &compare_query.plot)
+            );
+            output
+        });
+    let mut devices = BTreeSet::from(["x".to_string()]);
+    devices.extend(
+        DATA.blockdevices
+            .read()
+            .unwrap()
+            .iter()
+            .map(|b| b.device_name.clone()),
+    );
+    devices.extend(
+        DATA.networkdevices
+            .read()
+            .unwrap()
+            .iter()
+            .map(|n| n.device_name.clone()),
+    );
+    let html_for_devices = devices.iter().fold(String::new(), |mut output, device| {
+        let _ = write!(
+            output,
+            r#"<option value="{device}"{}>{device}</option>"#,
+            selected(device, &compare_query.device)
+        );
+        output
+    });
+    let html_for_times = |current: &Option<String>| {
+        let mut minute = String::new();
+        DATA.cpu.read().unwrap().iter().map(|c| c.timestamp).fold(
+            String::new(),
+            |mut output, timestamp| {
+                if minute != timestamp.format("%M").to_string() {
+                    let value = timestamp.timestamp().to_string();
+                    let _ = write!(
+                        output,
+                        r#"<option value="{}"{}>{}</option>"#,
+                        value,
+                        selected(&value, current),
+                        timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+                    );
+                    minute = timestamp.format("%M").to_string();
+                }
+                output
+            },
+        )
+    };
+
+    // the plot and device are only used if these are valid, so these can be used in the html.
+    let valid = compare_query
+        .plot
+        .as_ref()
+        .is_some_and(|plot| COMPARE_PLOTS.contains(&plot.as_str()))
+        && compare_query
+            .device
+            .as_ref()
+            .is_some_and(|device| devices.contains(device));
+    let html_for_comparison = match compare_query.comparison() {
+        Some(comparison) if valid => format!(
+            r#"{}<br><img src="/compare_plotter?{}">"#,
+            comparison_summary_html(&comparison),
+            compare_query.query_string(&comparison)
+        ),
+        _ => String::new(),
+    };
+
+    format!(
+        r##"<!doctype html>
+ <html>
+  <head>
+   <style>
+    body {{ font-family: monospace; }}
+    table {{ border-collapse: collapse; }}
+    td, th {{ border: 1px solid grey; padding: 2px 8px; text-align: right; }}
+    td:first-child {{ text-align: left; }}
+   </style>
+  </head>
+  <body>
+   <form action="/compare" method="get">
+    <label for="plot">plot:</label>
+    <select id="plot" name="plot">{html_for_plots}</select>
+    <label for="device">device:</label>
+    <select id="device" name="device">{html_for_devices}</select>
+    <br>
+    <label for="baseline_start">baseline start:</label>
+    <select id="baseline_start" name="baseline_start">{baseline_start}</select>
+    <label for="baseline_end">end:</label>
+    <select id="baseline_end" name="baseline_end">{baseline_end}</select>
+    <br>
+    <label for="candidate_start">candidate start:</label>
+    <select id="candidate_start" name="candidate_start">{candidate_start}</select>
+    <label for="candidate_end">end:</label>
+    <select id="candidate_end" name="candidate_end">{candidate_end}</select>
+    <input type="submit" value="compare">
+   </form>
+   {html_for_comparison}
+  </body>
+ </html>
+ "##,
+        baseline_start = html_for_times(&compare_query.baseline_start),
+        baseline_end = html_for_times(&compare_query.baseline_end),
+        candidate_start = html_for_times(&compare_query.candidate_start),
+        candidate_end = html_for_times(&compare_query.candidate_end),
+    )
+}
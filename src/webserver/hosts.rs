@@ -0,0 +1,224 @@
+use axum::extract::{Form, Query};
+use axum::response::{Html, Redirect};
+use axum_session::{Session, SessionNullPool};
+use chrono::{DateTime, Local};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::processor::rollup::Aggregate;
+use crate::webserver::compare::{overlay_panel_plot, COMPARE_PLOTS};
+use crate::webserver::interactive::{create_layout, Layout, Panel, Series};
+use crate::DATA;
+
+/// The name of a host as shown; the archives without metadata have no host name.
+pub fn host_label(hostname: &str) -> &str {
+    if hostname.is_empty() {
+        "(no metadata)"
+    } else {
+        hostname
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetHost {
+    pub host: String,
+}
+
+/// Select the host of the pages, and reload the root page for the devices of the host.
+pub async fn set_host(
+    session: Session<SessionNullPool>,
+    Form(set_host): Form<SetHost>,
+) -> Redirect {
+    session.set("host", set_host.host);
+    Redirect::to("/")
+}
+
+/// A form to select the host, if the archives of more than one host are read.
+pub fn host_form(selected_host: Option<&str>) -> String {
+    let hostnames = DATA.hostnames();
+    if hostnames.len() < 2 {
+        return String::new();
+    }
+    let html_for_hosts = hostnames
+        .iter()
+        .fold(String::new(), |mut output, hostname| {
+            let _ = write!(
+                output,
+                r#"<option value="{}"{}>{}</option>"#,
+                hostname,
+                if selected_host == Some(hostname.as_str()) {
+                    " selected"
+                } else {
+                    ""
+                },
+                host_label(hostname)
+            );
+            output
+        });
+    format!(
+        r#"
+    <form action="/set_host" method="post" target="_top">
+      <label for="host">host:</label>
+      <select id="host" name="host">{html_for_hosts}</select>
+      <input type="submit" value="select">
+    </form>
+    <li><a href="/hosts" target="right">Hosts overlay</a></li>
+    <li><a href="/cluster" target="right">Cluster</a></li>
+    "#
+    )
+}
+
+/// Create the layout of a plot group for every host, and overlay the series of the same panel
+/// of all hosts, with the host name in the name of the series.
+pub fn hosts_layout(
+    plot_1: &str,
+    plot_2: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Layout {
+    let mut panels: Vec<Panel> = Vec::new();
+    for hostname in DATA.hostnames() {
+        let layout = DATA.with_host(Some(&hostname), || {
+            create_layout(plot_1, plot_2, start_time, end_time, Aggregate::Avg)
+        });
+        for panel in layout.panels {
+            let position = match panels.iter().position(|p| p.title == panel.title) {
+                Some(position) => position,
+                None => {
+                    panels.push(Panel {
+                        title: panel.title,
+                        y_desc: panel.y_desc,
+                        y2_desc: panel.y2_desc,
+                        ..Default::default()
+                    });
+                    panels.len() - 1
+                }
+            };
+            panels[position]
+                .series
+                .extend(panel.series.into_iter().map(|series| Series {
+                    name: format!("{} {}", host_label(&hostname), series.name),
+                    // stacked areas of several hosts hide each other, so these are drawn as lines.
+                    stack: None,
+                    ..series
+                }));
+        }
+    }
+    Layout {
+        plot: plot_1.to_string(),
+        device: plot_2.to_string(),
+        panels: panels
+            .into_iter()
+            .filter(|panel| !panel.series.is_empty())
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Draw the overlay of a plot group of all hosts, with a chart per panel.
+pub fn draw_hosts<DB: DrawingBackend>(
+    backend: DrawingArea<DB, Shift>,
+    plot_1: &str,
+    plot_2: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) {
+    backend.fill(&WHITE).unwrap();
+    let layout = hosts_layout(plot_1, plot_2, start_time, end_time);
+    if layout.panels.is_empty() {
+        return;
+    }
+    let multi_backend = backend.split_evenly((layout.panels.len(), 1));
+    for (panel, area) in layout.panels.iter().zip(multi_backend.iter()) {
+        overlay_panel_plot(area, panel, &format!("{}: hosts", panel.title), false);
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct HostsQuery {
+    pub plot: Option<String>,
+    pub device: Option<String>,
+}
+
+/// The hosts page: a form to select the plot group and device, and the overlay of all hosts if
+/// these are set.
+pub async fn handler_hosts(Query(hosts_query): Query<HostsQuery>) -> Html<String> {
+    let selected = |value: &str, current: &Option<String>| {
+        if current.as_deref() == Some(value) {
+            " selected"
+        } else {
+            ""
+        }
+    };
+    let html_for_plots = COMPARE_PLOTS
+        .iter()
+        .fold(String::new(), |mut output, plot| {
+            let _ = write!(
+                output,
+                r#"<option value="{plot}"{}>{plot}</option>"#,
+                selected(plot, &hosts_query.plot)
+            );
+            output
+        });
+    let mut devices = BTreeSet::from(["x".to_string()]);
+    for hostname in DATA.hostnames() {
+        let data = DATA.host(&hostname);
+        devices.extend(
+            data.blockdevices
+                .read()
+                .unwrap()
+                .iter()
+                .map(|b| b.device_name.clone()),
+        );
+        devices.extend(
+            data.networkdevices
+                .read()
+                .unwrap()
+                .iter()
+                .map(|n| n.device_name.clone()),
+        );
+    }
+    let html_for_devices = devices.iter().fold(String::new(), |mut output, device| {
+        let _ = write!(
+            output,
+            r#"<option value="{device}"{}>{device}</option>"#,
+            selected(device, &hosts_query.device)
+        );
+        output
+    });
+    // the plot and device are only used if these are valid, so these can be used in the html.
+    let html_for_overlay = match (&hosts_query.plot, &hosts_query.device) {
+        (Some(plot), Some(device))
+            if COMPARE_PLOTS.contains(&plot.as_str()) && devices.contains(device) =>
+        {
+            format!(r#"<img src="/hosts_plotter/{}/{}">"#, plot, device)
+        }
+        _ => String::new(),
+    };
+
+    format!(
+        r##"<!doctype html>
+ <html>
+  <head>
+   <style>
+    body {{ font-family: monospace; }}
+   </style>
+  </head>
+  <body>
+   <form action="/hosts" method="get">
+    <label for="plot">plot:</label>
+    <select id="plot" name="plot">{html_for_plots}</select>
+    <label for="device">device:</label>
+    <select id="device" name="device">{html_for_devices}</select>
+    <input type="submit" value="overlay">
+   </form>
+   {html_for_overlay}
+  </body>
+ </html>
+ "##
+    )
+    .into()
+}
@@ -0,0 +1,253 @@
+// client side rendering of a plot group layout as fetched from /json/:plot_1/:plot_2.
+const [, , PLOT_1, PLOT_2] = window.location.pathname.split("/");
+const COLOURS = [
+  "#2e7d32", "#c62828", "#1565c0", "#ef6c00", "#6a1b9a", "#00838f", "#9e9d24", "#4e342e",
+  "#ad1457", "#283593", "#558b2f", "#f9a825", "#37474f", "#d84315", "#00695c", "#7b1fa2",
+];
+const MARGIN = { left: 80, right: 80, top: 10, bottom: 30 };
+const hidden = new Set();
+
+function formatTime(ms, withDate) {
+  const d = new Date(ms);
+  const pad = (n) => String(n).padStart(2, "0");
+  const time = `${pad(d.getHours())}:${pad(d.getMinutes())}:${pad(d.getSeconds())}`;
+  return withDate ? `${d.getFullYear()}-${pad(d.getMonth() + 1)}-${pad(d.getDate())} ${time}` : time;
+}
+
+function formatValue(value) {
+  if (value === 0) return "0";
+  if (Math.abs(value) < 0.1) return value.toFixed(3);
+  if (Math.abs(value) < 10) return value.toFixed(2);
+  return value.toFixed(0);
+}
+
+// set the selected range on the server, so the bitmap views use the same range.
+async function setTime(start, end) {
+  const body = new URLSearchParams();
+  body.append("start_time", start === null ? "-" : new Date(start).toISOString());
+  body.append("end_time", end === null ? "-" : new Date(end).toISOString());
+  await fetch("/set_time", { method: "POST", body });
+  await load();
+}
+
+// stack the values of visible series with the same stack name on top of each other.
+function plottedValues(panel) {
+  const bases = {};
+  return panel.series.map((series) => {
+    if (hidden.has(panel.title + "/" + series.name)) return null;
+    if (series.stack === null) return series.values;
+    const base = bases[series.stack] || new Map();
+    const values = series.values.map(([t, v]) => [t, v + (base.get(t) || 0)]);
+    values.forEach(([t, v]) => base.set(t, v));
+    bases[series.stack] = base;
+    return values;
+  });
+}
+
+function nearest(values, t) {
+  let low = 0;
+  let high = values.length - 1;
+  while (low < high) {
+    const mid = (low + high) >> 1;
+    if (values[mid][0] < t) low = mid + 1;
+    else high = mid;
+  }
+  if (low > 0 && Math.abs(values[low - 1][0] - t) < Math.abs(values[low][0] - t)) low -= 1;
+  return values[low];
+}
+
+function drawPanel(canvas, panel, layout, selection) {
+  const ratio = window.devicePixelRatio || 1;
+  const width = canvas.clientWidth;
+  const height = canvas.clientHeight;
+  canvas.width = width * ratio;
+  canvas.height = height * ratio;
+  const ctx = canvas.getContext("2d");
+  ctx.scale(ratio, ratio);
+  ctx.clearRect(0, 0, width, height);
+
+  // the history can be larger than the maximum number of arguments, so no Math.min(...array).
+  const plotted = plottedValues(panel);
+  const all = plotted.filter((values) => values !== null).flat();
+  const xMin = layout.start_time ?? all.reduce((min, [t]) => Math.min(min, t), Infinity);
+  const xMax = layout.end_time ?? all.reduce((max, [t]) => Math.max(max, t), -Infinity);
+  const yMax = [false, true].map((secondary) => {
+    const max = plotted
+      .filter((values, index) => values !== null && panel.series[index].secondary_axis === secondary)
+      .flat()
+      .reduce((max, [, v]) => Math.max(max, v), 0);
+    return max * 1.1 || 1;
+  });
+  const plotWidth = width - MARGIN.left - MARGIN.right;
+  const plotHeight = height - MARGIN.top - MARGIN.bottom;
+  const x = (t) => MARGIN.left + ((t - xMin) / Math.max(xMax - xMin, 1)) * plotWidth;
+  const y = (v, secondary) => MARGIN.top + plotHeight - (v / yMax[secondary ? 1 : 0]) * plotHeight;
+
+  // mesh and labels
+  ctx.strokeStyle = "#e0e0e0";
+  ctx.fillStyle = "black";
+  ctx.font = "12px monospace";
+  for (let i = 0; i <= 5; i++) {
+    const v = (yMax[0] / 5) * i;
+    ctx.beginPath();
+    ctx.moveTo(MARGIN.left, y(v, false));
+    ctx.lineTo(MARGIN.left + plotWidth, y(v, false));
+    ctx.stroke();
+    ctx.textAlign = "right";
+    ctx.fillText(formatValue(v), MARGIN.left - 4, y(v, false) + 4);
+    if (panel.y2_desc !== null) {
+      ctx.textAlign = "left";
+      ctx.fillText(formatValue((yMax[1] / 5) * i), MARGIN.left + plotWidth + 4, y(v, false) + 4);
+    }
+  }
+  ctx.textAlign = "center";
+  for (let i = 0; i <= 6; i++) {
+    const t = xMin + ((xMax - xMin) / 6) * i;
+    ctx.fillText(formatTime(t, false), x(t), height - MARGIN.bottom + 16);
+  }
+  ctx.save();
+  ctx.translate(14, MARGIN.top + plotHeight / 2);
+  ctx.rotate(-Math.PI / 2);
+  ctx.fillText(panel.y_desc, 0, 0);
+  ctx.restore();
+  if (panel.y2_desc !== null) {
+    ctx.save();
+    ctx.translate(width - 6, MARGIN.top + plotHeight / 2);
+    ctx.rotate(-Math.PI / 2);
+    ctx.fillText(panel.y2_desc, 0, 0);
+    ctx.restore();
+  }
+
+  // series; stacked series are drawn first from the top of the stack down, so every layer stays
+  // visible, lines and points are drawn over them.
+  const indexes = panel.series.map((_, index) => index);
+  const order = [
+    ...indexes.filter((index) => panel.series[index].stack !== null).reverse(),
+    ...indexes.filter((index) => panel.series[index].stack === null),
+  ];
+  for (const index of order) {
+    const values = plotted[index];
+    const series = panel.series[index];
+    if (values === null || values.length === 0) continue;
+    const colour = COLOURS[index % COLOURS.length];
+    ctx.strokeStyle = colour;
+    ctx.fillStyle = colour;
+    if (series.points) {
+      values.forEach(([t, v]) => ctx.fillRect(x(t) - 1.5, y(v, series.secondary_axis) - 1.5, 3, 3));
+      continue;
+    }
+    ctx.beginPath();
+    values.forEach(([t, v], i) =>
+      i === 0 ? ctx.moveTo(x(t), y(v, series.secondary_axis)) : ctx.lineTo(x(t), y(v, series.secondary_axis))
+    );
+    if (series.stack !== null) {
+      ctx.lineTo(x(values[values.length - 1][0]), y(0, series.secondary_axis));
+      ctx.lineTo(x(values[0][0]), y(0, series.secondary_axis));
+      ctx.closePath();
+      ctx.globalAlpha = 0.6;
+      ctx.fill();
+      ctx.globalAlpha = 1;
+    } else {
+      ctx.lineWidth = 1.5;
+      ctx.stroke();
+    }
+  }
+
+  if (selection !== null) {
+    ctx.fillStyle = "rgba(0, 0, 255, 0.15)";
+    ctx.fillRect(Math.min(...selection), MARGIN.top, Math.abs(selection[1] - selection[0]), plotHeight);
+  }
+  return { xMin, xMax, plotWidth };
+}
+
+function createPanel(panel, layout) {
+  const div = document.createElement("div");
+  div.className = "panel";
+  const title = document.createElement("h3");
+  title.textContent = panel.title;
+  div.appendChild(title);
+
+  if (panel.series.length === 0) {
+    const table = document.createElement("table");
+    table.className = "info";
+    panel.info.forEach(([name, value]) => {
+      const row = table.insertRow();
+      row.insertCell().textContent = name;
+      row.insertCell().textContent = value;
+    });
+    div.appendChild(table);
+    return div;
+  }
+
+  const canvas = document.createElement("canvas");
+  const legend = document.createElement("div");
+  legend.className = "legend";
+  const tooltip = document.createElement("div");
+  tooltip.className = "tooltip";
+  div.append(canvas, legend, tooltip);
+
+  let geometry = null;
+  let dragStart = null;
+  const redraw = (selection) => (geometry = drawPanel(canvas, panel, layout, selection));
+  const timeAt = (offsetX) =>
+    geometry.xMin + ((offsetX - MARGIN.left) / geometry.plotWidth) * (geometry.xMax - geometry.xMin);
+
+  panel.series.forEach((series, index) => {
+    const key = panel.title + "/" + series.name;
+    const entry = document.createElement("span");
+    entry.innerHTML = `<b style="color: ${COLOURS[index % COLOURS.length]}">&#9632;</b> `;
+    entry.append(series.name);
+    entry.classList.toggle("hidden", hidden.has(key));
+    entry.onclick = () => {
+      hidden.has(key) ? hidden.delete(key) : hidden.add(key);
+      entry.classList.toggle("hidden", hidden.has(key));
+      redraw(null);
+    };
+    legend.appendChild(entry);
+  });
+
+  canvas.onmousedown = (event) => (dragStart = event.offsetX);
+  canvas.onmouseleave = () => {
+    tooltip.style.display = "none";
+    dragStart = null;
+    redraw(null);
+  };
+  canvas.onmouseup = (event) => {
+    if (dragStart !== null && Math.abs(event.offsetX - dragStart) > 5) {
+      const times = [timeAt(dragStart), timeAt(event.offsetX)].sort((a, b) => a - b);
+      setTime(times[0], times[1]);
+    }
+    dragStart = null;
+  };
+  canvas.onmousemove = (event) => {
+    redraw(dragStart === null ? null : [dragStart, event.offsetX]);
+    const t = timeAt(event.offsetX);
+    const lines = [formatTime(t, true)];
+    panel.series.forEach((series) => {
+      if (hidden.has(panel.title + "/" + series.name) || series.values.length === 0) return;
+      lines.push(`${series.name.padEnd(25)} ${formatValue(nearest(series.values, t)[1]).padStart(10)}`);
+    });
+    tooltip.textContent = lines.join("\n");
+    tooltip.style.display = "block";
+    tooltip.style.left = `${event.offsetX + 16}px`;
+    tooltip.style.top = `${event.offsetY + 30}px`;
+  };
+  requestAnimationFrame(() => redraw(null));
+  return div;
+}
+
+async function load() {
+  const aggregate = document.getElementById("aggregate");
+  const response = await fetch(`/json/${PLOT_1}/${PLOT_2}?aggregate=${aggregate.value}`);
+  const layout = await response.json();
+  // the aggregate can be selected when the rows are a roll-up of a longer range.
+  document.getElementById("resolution").textContent = `resolution: ${layout.resolution}`;
+  aggregate.hidden = layout.resolution === "raw";
+  const container = document.getElementById("panels");
+  container.replaceChildren(...layout.panels.map((panel) => createPanel(panel, layout)));
+}
+
+document.getElementById("bitmap").href = `/handler/${PLOT_1}/${PLOT_2}`;
+document.getElementById("reset").onclick = () => setTime(null, null);
+document.getElementById("aggregate").onchange = () => load();
+load();
@@ -0,0 +1,237 @@
+use crate::processor::rollup::Aggregate;
+use crate::webserver::plot_group::plot_group;
+use crate::DATA;
+use axum::extract::{Path, Query};
+use axum::{response::Html, Json};
+use axum_session::{Session, SessionNullPool};
+use chrono::{DateTime, Local};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// A single series of a panel, with the timestamps as milliseconds since the epoch.
+#[derive(Debug, Serialize, Default)]
+pub struct Series {
+    pub name: String,
+    pub values: Vec<(i64, f64)>,
+    /// series with the same stack name are added on top of each other in the order of the panel.
+    pub stack: Option<String>,
+    pub secondary_axis: bool,
+    /// draw the values as points instead of a line.
+    pub points: bool,
+}
+
+impl Series {
+    pub fn new<T>(name: &str, rows: &[&T], value: impl Fn(&T) -> (DateTime<Local>, f64)) -> Series {
+        Series {
+            name: name.to_string(),
+            values: rows
+                .iter()
+                .map(|row| value(row))
+                .map(|(timestamp, value)| (timestamp.timestamp_millis(), value))
+                .collect(),
+            ..Default::default()
+        }
+    }
+    pub fn stacked(mut self, stack: &str) -> Series {
+        self.stack = Some(stack.to_string());
+        self
+    }
+    pub fn on_secondary_axis(mut self) -> Series {
+        self.secondary_axis = true;
+        self
+    }
+    pub fn as_points(mut self) -> Series {
+        self.points = true;
+        self
+    }
+}
+
+/// A panel is the interactive equivalent of a single plotters chart.
+#[derive(Debug, Serialize, Default)]
+pub struct Panel {
+    pub title: String,
+    pub y_desc: String,
+    pub y2_desc: Option<String>,
+    pub series: Vec<Series>,
+    /// name and value pairs that are shown as a table instead of a chart.
+    pub info: Vec<(String, String)>,
+}
+
+/// A layout is the interactive equivalent of a plot group.
+#[derive(Debug, Serialize, Default)]
+pub struct Layout {
+    pub plot: String,
+    pub device: String,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// the roll-up of the rows, or "raw" for the rows themselves.
+    pub resolution: String,
+    pub panels: Vec<Panel>,
+}
+
+/// The aggregate of the rows of a roll-up that is shown.
+#[derive(Debug, Default, Deserialize)]
+pub struct LayoutOptions {
+    #[serde(default)]
+    pub aggregate: Aggregate,
+}
+
+/// Select the rows between start_time and end_time; if a time is not set, the selection is not
+/// limited on that side.
+pub fn rows_in_range<'a, T>(
+    rows: impl Iterator<Item = &'a T>,
+    timestamp: impl Fn(&T) -> DateTime<Local>,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Vec<&'a T> {
+    rows.filter(|row| start_time.is_none_or(|start_time| timestamp(row) >= start_time))
+        .filter(|row| end_time.is_none_or(|end_time| timestamp(row) <= end_time))
+        .collect()
+}
+
+pub fn create_layout(
+    plot_1: &str,
+    plot_2: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+    aggregate: Aggregate,
+) -> Layout {
+    let device_name = plot_2.to_string();
+    // the cluster panels have the time range and the history of all hosts; the other panels use
+    // the roll-up that fits the time range.
+    let level = if plot_1 == "cluster" {
+        None
+    } else {
+        DATA.rollup_level(start_time, end_time)
+    };
+    let resolution = level.map_or("raw", |level| DATA.rollups[level].level.name);
+    DATA.with_rollup(level.map(|level| (level, aggregate)), || {
+        let (start_time, end_time) = if plot_1 == "cluster" {
+            (start_time, end_time)
+        } else {
+            DATA.time_range(start_time, end_time)
+        };
+        let panels = plot_group(plot_1, &device_name)
+            .iter()
+            .flat_map(|chart| chart.panels(&device_name, start_time, end_time))
+            .collect();
+        Layout {
+            plot: plot_1.to_string(),
+            device: device_name,
+            start_time: start_time.map(|start_time| start_time.timestamp_millis()),
+            end_time: end_time.map(|end_time| end_time.timestamp_millis()),
+            resolution: resolution.to_string(),
+            panels,
+        }
+    })
+}
+
+pub async fn handler_json(
+    session: Session<SessionNullPool>,
+    Path((plot_1, plot_2)): Path<(String, String)>,
+    Query(layout_options): Query<LayoutOptions>,
+) -> Json<Layout> {
+    debug!("handler_json: session: {:?}", session);
+    let start_time = session.get::<DateTime<Local>>("start_time");
+    let end_time = session.get::<DateTime<Local>>("end_time");
+    let host = session.get::<String>("host");
+    Json(DATA.with_host(host.as_deref(), || {
+        create_layout(
+            &plot_1,
+            &plot_2,
+            start_time,
+            end_time,
+            layout_options.aggregate,
+        )
+    }))
+}
+
+/// The page takes the plot group and device from its own url, and fetches the data from
+/// /json/:plot_1/:plot_2, with the aggregate that is selected when the rows are a roll-up.
+pub async fn handler_interactive() -> Html<String> {
+    format!(
+        r##"<!doctype html>
+ <html>
+  <head>
+   <style>
+    body {{ font-family: monospace; margin: 0; }}
+    .toolbar {{ padding: 4px 8px; }}
+    .panel {{ position: relative; margin: 4px 8px 12px 8px; }}
+    .panel h3 {{ margin: 4px 0; }}
+    .legend span {{ cursor: pointer; margin-right: 12px; white-space: nowrap; }}
+    .legend span.hidden {{ opacity: 0.3; text-decoration: line-through; }}
+    .tooltip {{ position: absolute; pointer-events: none; background: rgba(255,255,255,0.9);
+               border: 1px solid black; padding: 4px; display: none; white-space: pre; }}
+    canvas {{ width: 100%; height: 320px; cursor: crosshair; }}
+    table.info td {{ padding-right: 24px; }}
+   </style>
+  </head>
+  <body>
+   <div class="toolbar">
+    <a id="bitmap" href="/">bitmap view</a>
+    <button id="reset">reset zoom</button>
+    <span id="resolution"></span>
+    <select id="aggregate" hidden>
+     <option value="avg">avg</option>
+     <option value="min">min</option>
+     <option value="max">max</option>
+    </select>
+    <span>drag on a chart to zoom, click a legend entry to hide or show a series.</span>
+   </div>
+   <div id="panels"></div>
+   <script>
+    {script}
+   </script>
+  </body>
+ </html>
+ "##,
+        script = include_str!("interactive.js"),
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone};
+
+    #[test]
+    fn series_of_the_rows_in_range() {
+        let start = Local.with_ymd_and_hms(2024, 10, 20, 12, 0, 0).unwrap();
+        let rows = (0..5)
+            .map(|seconds| (start + TimeDelta::seconds(seconds), seconds as f64))
+            .collect::<Vec<_>>();
+        let timestamp = |row: &(DateTime<Local>, f64)| row.0;
+        let second = |seconds: i64| Some(start + TimeDelta::seconds(seconds));
+
+        // the start and end time are included, and a time that is not set doesn't limit.
+        let in_range = rows_in_range(rows.iter(), timestamp, second(1), second(3));
+        assert_eq!(
+            in_range.iter().map(|row| row.1).collect::<Vec<_>>(),
+            vec![1_f64, 2_f64, 3_f64]
+        );
+        assert_eq!(rows_in_range(rows.iter(), timestamp, None, None).len(), 5);
+        assert_eq!(
+            rows_in_range(rows.iter(), timestamp, second(3), None).len(),
+            2
+        );
+
+        let series = Series::new("load", &in_range, |row| (row.0, row.1 * 2_f64))
+            .stacked("total")
+            .on_secondary_axis();
+        assert_eq!(
+            serde_json::to_value(&series).unwrap(),
+            serde_json::json!({
+                "name": "load",
+                "values": [
+                    [start.timestamp_millis() + 1000, 2.0],
+                    [start.timestamp_millis() + 2000, 4.0],
+                    [start.timestamp_millis() + 3000, 6.0]
+                ],
+                "stack": "total",
+                "secondary_axis": true,
+                "points": false
+            })
+        );
+    }
+}
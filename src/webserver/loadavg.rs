@@ -1,5 +1,4 @@
 use chrono::{DateTime, Local};
-use plotters::backend::{BitMapBackend, RGBPixel};
 use plotters::chart::SeriesLabelPosition::UpperLeft;
 use plotters::chart::{ChartBuilder, LabelAreaPosition};
 use plotters::coord::Shift;
@@ -8,6 +7,7 @@ use plotters::element::Rectangle;
 use plotters::prelude::*;
 use plotters::style::full_palette::{GREY_500, GREY_A100};
 
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
 use crate::{
     CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, DATA, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
     LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, LABEL_AREA_SIZE_RIGHT, MESH_STYLE_FONT,
@@ -27,8 +27,8 @@ struct HighValue {
     pub load_5: f64,
     pub load_15: f64,
 }
-pub fn load_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub fn load_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -145,3 +145,23 @@ pub fn load_plot(
         .draw()
         .unwrap();
 }
+
+pub fn load_panel(start_time: Option<DateTime<Local>>, end_time: Option<DateTime<Local>>) -> Panel {
+    let historical_data_read = DATA.loadavg.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |l| l.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "Load".to_string(),
+        y_desc: "Load".to_string(),
+        series: vec![
+            Series::new("load_1", &rows, |l| (l.timestamp, l.load_1)),
+            Series::new("load_5", &rows, |l| (l.timestamp, l.load_5)),
+            Series::new("load_15", &rows, |l| (l.timestamp, l.load_15)),
+        ],
+        ..Default::default()
+    }
+}
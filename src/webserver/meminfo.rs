@@ -1,4 +1,3 @@
-use plotters::backend::{BitMapBackend, RGBPixel};
 use plotters::chart::SeriesLabelPosition::UpperLeft;
 use plotters::coord::Shift;
 use plotters::prelude::full_palette::LIGHTGREEN;
@@ -11,10 +10,7 @@ use plotters::style::full_palette::{
     LIGHTGREEN_400, ORANGE, PURPLE_100, PURPLE_800, RED_100,
 };
 
-use crate::webserver::pressure::pressure_memory_plot;
-use crate::webserver::vmstat::swap_inout_plot;
-use crate::webserver::vmstat::pages_dirty;
-use crate::ARGS;
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
 use crate::{
     CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, DATA, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
     LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, LABEL_AREA_SIZE_RIGHT, MESH_STYLE_FONT,
@@ -23,90 +19,8 @@ use crate::{
 use chrono::{DateTime, Local};
 use sysctl::{Ctl, Sysctl};
 
-pub fn create_memory_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((1, 1));
-    memory_plot(&mut multi_backend, 0, start_time, end_time);
-}
-
-pub fn create_memory_psi_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((2, 1));
-    memory_plot(&mut multi_backend, 0, start_time, end_time);
-    pressure_memory_plot(&mut multi_backend, 1, start_time, end_time);
-}
-
-pub fn create_memory_commit(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((2, 1));
-    memory_plot(&mut multi_backend, 0, start_time, end_time);
-    committed_mem_plot(&mut multi_backend, 1, start_time, end_time);
-}
-
-pub fn create_memory_active_inactive_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((2, 1));
-    memory_plot(&mut multi_backend, 0, start_time, end_time);
-    active_inactive_mem_plot(&mut multi_backend, 1, start_time, end_time);
-}
-
-pub fn create_memory_swap_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((2, 1));
-    memory_plot(&mut multi_backend, 0, start_time, end_time);
-    swap_space_plot(&mut multi_backend, 1, start_time, end_time);
-}
-
-pub fn create_memory_swap_inout_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((3, 1));
-    memory_plot(&mut multi_backend, 0, start_time, end_time);
-    swap_inout_plot(&mut multi_backend, 1, start_time, end_time);
-    swap_space_plot(&mut multi_backend, 2, start_time, end_time);
-}
-pub fn create_memory_dirty_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((2, 1));
-    memory_plot(&mut multi_backend, 0, start_time, end_time);
-    pages_dirty(&mut multi_backend, 1, start_time, end_time);
-}
-pub fn memory_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub fn memory_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -934,8 +848,8 @@ pub fn memory_plot(
         .unwrap();
 }
 
-fn swap_space_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub(crate) fn swap_space_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -1086,8 +1000,8 @@ fn swap_space_plot(
         .unwrap();
 }
 
-fn active_inactive_mem_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub(crate) fn active_inactive_mem_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -1220,13 +1134,13 @@ fn active_inactive_mem_plot(
     let min_hugepages = historical_data_read
         .iter()
         .filter(|m| m.timestamp >= final_start_time && m.timestamp <= final_end_time)
-        .map(|m| (m.hugepagesize * m.hugepages_total))
+        .map(|m| m.hugepagesize * m.hugepages_total)
         .min_by(|a, b| a.partial_cmp(b).unwrap())
         .unwrap();
     let max_hugepages = historical_data_read
         .iter()
         .filter(|m| m.timestamp >= final_start_time && m.timestamp <= final_end_time)
-        .map(|m| (m.hugepagesize * m.hugepages_total))
+        .map(|m| m.hugepagesize * m.hugepages_total)
         .max_by(|a, b| a.partial_cmp(b).unwrap())
         .unwrap();
     contextarea
@@ -1572,8 +1486,8 @@ fn active_inactive_mem_plot(
         .unwrap();
 }
 
-fn committed_mem_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub(crate) fn committed_mem_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -1737,3 +1651,166 @@ fn committed_mem_plot(
         .draw()
         .unwrap();
 }
+
+// meminfo is in kilobytes, the panels show megabytes.
+pub fn memory_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.memory.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |m| m.timestamp,
+        start_time,
+        end_time,
+    );
+    let mut series = Vec::new();
+    macro_rules! add_stacked_memory_series {
+        ($($struct_field_name:ident),*) => {
+            $(
+                series.push(
+                    Series::new(stringify!($struct_field_name), &rows, |m| {
+                        (m.timestamp, m.$struct_field_name / 1024_f64)
+                    })
+                    .stacked("memory"),
+                );
+            )*
+        };
+    }
+    add_stacked_memory_series!(
+        anonpages,
+        cached,
+        buffers,
+        slab,
+        pagetables,
+        kernelstack,
+        swapcached,
+        hugetlb,
+        memfree
+    );
+    macro_rules! add_memory_series {
+        ($($struct_field_name:ident),*) => {
+            $(
+                series.push(Series::new(stringify!($struct_field_name), &rows, |m| {
+                    (m.timestamp, m.$struct_field_name / 1024_f64)
+                }));
+            )*
+        };
+    }
+    add_memory_series!(memtotal, memavailable, dirty, mapped, shmem);
+    Panel {
+        title: "Memory".to_string(),
+        y_desc: "MB".to_string(),
+        series,
+        ..Default::default()
+    }
+}
+
+pub fn swap_space_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.memory.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |m| m.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "Swap usage".to_string(),
+        y_desc: "MB".to_string(),
+        series: vec![
+            Series::new("swap used", &rows, |m| {
+                (m.timestamp, (m.swaptotal - m.swapfree) / 1024_f64)
+            })
+            .stacked("swap"),
+            Series::new("swap total", &rows, |m| {
+                (m.timestamp, m.swaptotal / 1024_f64)
+            }),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn active_inactive_mem_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.memory.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |m| m.timestamp,
+        start_time,
+        end_time,
+    );
+    let mut series = Vec::new();
+    macro_rules! add_stacked_memory_series {
+        ($([$struct_field_name:ident, $name:expr]),*) => {
+            $(
+                series.push(
+                    Series::new($name, &rows, |m| {
+                        (m.timestamp, m.$struct_field_name / 1024_f64)
+                    })
+                    .stacked("memory"),
+                );
+            )*
+        };
+    }
+    add_stacked_memory_series!(
+        [active_anon, "active anonymous"],
+        [inactive_anon, "inactive anonymous"],
+        [sunreclaim, "slab unreclaimable"],
+        [sreclaimable, "slab reclaimable"],
+        [active_file, "active file"],
+        [inactive_file, "inactive file"]
+    );
+    series.push(
+        Series::new("hugepages", &rows, |m| {
+            (m.timestamp, (m.hugepagesize * m.hugepages_total) / 1024_f64)
+        })
+        .stacked("memory"),
+    );
+    series.push(
+        Series::new("memfree", &rows, |m| (m.timestamp, m.memfree / 1024_f64)).stacked("memory"),
+    );
+    series.push(Series::new("memory total", &rows, |m| {
+        (m.timestamp, m.memtotal / 1024_f64)
+    }));
+    series.push(Series::new("memavailable", &rows, |m| {
+        (m.timestamp, m.memavailable / 1024_f64)
+    }));
+    Panel {
+        title: "active/inactive memory".to_string(),
+        y_desc: "MB".to_string(),
+        series,
+        ..Default::default()
+    }
+}
+
+pub fn committed_mem_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.memory.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |m| m.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "Committed memory overview".to_string(),
+        y_desc: "MB".to_string(),
+        series: vec![
+            Series::new("committed_as", &rows, |m| {
+                (m.timestamp, m.committed_as / 1024_f64)
+            })
+            .stacked("committed"),
+            Series::new("commitlimit", &rows, |m| {
+                (m.timestamp, m.commitlimit / 1024_f64)
+            }),
+        ],
+        ..Default::default()
+    }
+}
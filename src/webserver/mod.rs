@@ -1,29 +1,34 @@
 pub mod blockdevice;
+pub mod cluster;
+pub mod compare;
+pub mod hosts;
+pub mod interactive;
 pub mod loadavg;
 pub mod meminfo;
 pub mod net_dev;
+pub mod plot_group;
 pub mod pressure;
+pub mod scaled;
+pub mod schedstat;
 pub mod stat;
 pub mod vmstat;
 pub mod xfs;
 
-use crate::webserver::meminfo::{
-    create_memory_plot, create_memory_psi_plot, create_memory_swap_inout_plot,
-    create_memory_swap_plot, create_memory_dirty_plot,
-};
-use crate::webserver::net_dev::create_networkdevice_plot;
-use crate::webserver::stat::create_cpu_plot;
-use crate::webserver::stat::{create_cpu_load_plot, create_cpu_load_pressure_plot};
-use crate::webserver::vmstat::{create_memory_alloc_plot, create_memory_alloc_psi_plot};
-use crate::webserver::{
-    blockdevice::{
-        create_blockdevice_plot, create_blockdevice_plot_extra, create_blockdevice_psi_plot,
-    },
-    meminfo::create_memory_commit,
-};
+use crate::federation::handler_history;
+use crate::metadata::ArchiveMetadata;
+use crate::processor::rollup::Aggregate;
+use crate::processor::Collector;
+use crate::webserver::cluster::handler_cluster;
+use crate::webserver::compare::{draw_comparison, handler_compare, CompareQuery, Comparison};
+use crate::webserver::hosts::{draw_hosts, handler_hosts, host_form, set_host};
+use crate::webserver::interactive::{handler_interactive, handler_json};
+use crate::webserver::plot_group::{draw_plot_group, plot_group};
+use crate::webserver::scaled::ScaledBackend;
+use crate::webserver::stat::CPU_HEATMAP_METRICS;
 use crate::{ARGS, DATA};
 use axum::{
-    extract::{Form, Path},
+    extract::{Form, Path, Query},
+    http::{header, StatusCode},
     response::Html,
     response::IntoResponse,
     routing::{get, post},
@@ -31,14 +36,14 @@ use axum::{
 };
 use axum_session::{Session, SessionConfig, SessionLayer, SessionNullPool, SessionStore};
 use chrono::{DateTime, Local};
+use clap::ValueEnum;
 use image::{DynamicImage, ImageFormat};
 use log::{debug, info};
+use plotters::coord::Shift;
+use plotters::prelude::{BitMapBackend, DrawingArea, DrawingBackend, IntoDrawingArea, SVGBackend};
 use serde::Deserialize;
 use std::fmt::Write;
 use std::{collections::BTreeSet, io::Cursor, thread::sleep, time::Duration};
-use xfs::create_xfs_plot;
-
-use self::meminfo::create_memory_active_inactive_plot;
 
 pub async fn webserver() {
     let session_config = SessionConfig::default().with_table_name("session");
@@ -49,12 +54,22 @@ pub async fn webserver() {
     let app = Router::new()
         .route("/handler/:plot_1/:plot_2", get(handler_html))
         .route("/plotter/:plot_1/:plot_2", get(handler_plotter))
+        .route("/json/:plot_1/:plot_2", get(handler_json))
+        .route("/interactive/:plot_1/:plot_2", get(handler_interactive))
+        .route("/compare", get(handler_compare))
+        .route("/compare_plotter", get(handler_compare_plotter))
+        .route("/hosts", get(handler_hosts))
+        .route("/cluster", get(handler_cluster))
+        .route("/history", get(handler_history))
+        .route("/hosts_plotter/:plot_1/:plot_2", get(handler_hosts_plotter))
         .route("/set_time", post(set_time))
+        .route("/set_host", post(set_host))
         .route("/", get(root_handler))
         .layer(SessionLayer::new(session_store));
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", ARGS.webserver_port))
-        .await
-        .unwrap();
+    let listener =
+        tokio::net::TcpListener::bind(format!("{}:{}", ARGS.webserver_bind, ARGS.webserver_port))
+            .await
+            .unwrap();
     axum::serve(listener, app.into_make_service())
         .await
         .unwrap();
@@ -68,21 +83,20 @@ pub struct SetTime {
 
 pub async fn set_time(session: Session<SessionNullPool>, Form(set_time): Form<SetTime>) {
     debug! {"set_time: {:#?}", set_time};
-    let start_time =
-        match DateTime::parse_from_str(&set_time.start_time, "%Y-%m-%d %H:%M:%S.%9f %:z") {
-            Ok(start_time) => Some(start_time),
-            _ => None,
-        };
-    let end_time = match DateTime::parse_from_str(&set_time.end_time, "%Y-%m-%d %H:%M:%S.%9f %:z") {
-        Ok(start_time) => Some(start_time),
-        _ => None,
+    // the form sends the timestamp as displayed by chrono, the interactive page sends rfc3339.
+    let parse_time = |time: &str| {
+        DateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f %:z")
+            .or_else(|_| DateTime::parse_from_rfc3339(time))
+            .ok()
     };
+    let start_time = parse_time(&set_time.start_time);
+    let end_time = parse_time(&set_time.end_time);
     session.set("start_time", start_time);
     session.set("end_time", end_time);
     debug!("set_time: {:#?}", session);
 }
 
-pub async fn time_form() -> String {
+pub fn time_form() -> String {
     let mut form = r#"
     <iframe name="dummyframe" id="dummyframe" style="display: none;"></iframe>
     <form action="/set_time" method="post" target="dummyframe">
@@ -92,9 +106,20 @@ pub async fn time_form() -> String {
     "#
     .to_string();
 
+    // the times of the roll-ups are listed too, so a range before the history can be selected.
+    let mut timestamps = DATA
+        .rollups
+        .iter()
+        .map(|rollup| &rollup.avg)
+        .chain([&**DATA])
+        .flat_map(|data| {
+            let cpu = data.cpu.read().unwrap();
+            cpu.iter().map(|r| r.timestamp).collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    timestamps.sort();
     let mut minute = String::from("");
-    let cpu = DATA.cpu.read().unwrap();
-    for timestamp in cpu.iter().map(|r| r.timestamp) {
+    for timestamp in timestamps.iter() {
         if minute != format!("{}", timestamp.format("%M")) {
             form += format!(
                 r#"<option value="{}">{}</option>"#,
@@ -113,7 +138,7 @@ pub async fn time_form() -> String {
         <option value="-">-</option>"
     "#;
 
-    for timestamp in cpu.iter().map(|r| r.timestamp) {
+    for timestamp in timestamps.iter() {
         if minute != format!("{}", timestamp.format("%M")) {
             form += format!(
                 r#"<option value="{}">{}</option>"#,
@@ -132,17 +157,93 @@ pub async fn time_form() -> String {
     form
 }
 
-pub async fn root_handler() -> Html<String> {
-    // await blockdevices to appear to be able to make a list of them
-    loop {
-        if DATA.blockdevices.read().unwrap().iter().count() > 0 {
-            break;
-        } else {
-            info!("Waiting for blockdevices to become available...");
+pub async fn root_handler(session: Session<SessionNullPool>) -> Html<String> {
+    let host = session.get::<String>("host");
+    DATA.with_host(host.as_deref(), || {
+        // await the first statistics to be able to list the blockdevices; the archives that are
+        // read have their statistics already.
+        while ARGS.read.is_none()
+            && !Collector::all()
+                .into_iter()
+                .any(|collector| DATA.has_rows(collector))
+        {
+            info!("Waiting for statistics to become available...");
             sleep(Duration::from_secs(1));
         }
-    }
+        root_html(host.as_deref())
+    })
+    .into()
+}
+
+/// The views of the navigation, with the collectors of which they show the statistics; the views
+/// of which the history has no statistics are hidden.
+static VIEWS: &[(&str, &str, &[Collector])] = &[
+    ("/handler/cpu/x", "CPU total", &[Collector::Cpu]),
+    (
+        "/handler/cpu_load/x",
+        "CPU total-load",
+        &[Collector::Cpu, Collector::Loadavg],
+    ),
+    (
+        "/handler/cpu_load_psi/x",
+        "CPU total-load-psi",
+        &[Collector::Cpu, Collector::Loadavg, Collector::Pressure],
+    ),
+    (
+        "/handler/cpu_heatmap/busy",
+        "CPU per-cpu heatmap",
+        &[Collector::Cpu],
+    ),
+    (
+        "/handler/schedstat/x",
+        "CPU scheduler-psi",
+        &[Collector::Schedstat, Collector::Pressure],
+    ),
+    ("/handler/memory/x", "Memory", &[Collector::Memory]),
+    (
+        "/handler/memory_alloc/x",
+        "Memory-alloc",
+        &[Collector::Memory, Collector::Vmstat],
+    ),
+    (
+        "/handler/memory_commit/x",
+        "Memory-committed",
+        &[Collector::Memory],
+    ),
+    (
+        "/handler/memory_psi/x",
+        "Memory-psi",
+        &[Collector::Memory, Collector::Pressure],
+    ),
+    (
+        "/handler/memory_psi_alloc/x",
+        "Memory-psi-alloc",
+        &[Collector::Memory, Collector::Vmstat, Collector::Pressure],
+    ),
+    (
+        "/handler/memory_swap/x",
+        "Memory-swapspace",
+        &[Collector::Memory],
+    ),
+    (
+        "/handler/memory_swap_inout/x",
+        "Memory-swapspace-swapio",
+        &[Collector::Memory, Collector::Vmstat],
+    ),
+    (
+        "/handler/memory_act_inact/x",
+        "Memory-active-inactive",
+        &[Collector::Memory],
+    ),
+    (
+        "/handler/memory_dirty/x",
+        "Memory-dirty",
+        &[Collector::Memory, Collector::Vmstat],
+    ),
+    ("/handler/xfs/x", "Filesystem-XFS", &[Collector::Xfs]),
+];
 
+fn root_html(host: Option<&str>) -> String {
     let html_for_blockdevices = DATA
         .blockdevices
         .read()
@@ -162,7 +263,28 @@ pub async fn root_handler() -> Html<String> {
             output
         });
 
-    let html_for_blockdevices_psi = DATA.blockdevices
+    let html_for_blockdevices_psi = if !DATA.has_rows(Collector::Pressure) {
+        String::new()
+    } else {
+        DATA.blockdevices
+            .read()
+            .unwrap()
+            .iter()
+            .map(|d| d.device_name.clone())
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+            .iter()
+            .fold(String::new(), |mut output, d| {
+                let _ = write!(
+                    output,
+                    r##"<li><a href="/handler/blockdevice_psi/{}" target="right">Blockdevice-psi {}</a>"##, d, d
+                );
+                output
+            })
+    };
+
+    let html_for_blockdevices_extra = DATA.blockdevices
         .read()
         .unwrap()
         .iter()
@@ -172,14 +294,13 @@ pub async fn root_handler() -> Html<String> {
         .collect::<Vec<_>>()
         .iter()
         .fold(String::new(), |mut output, d| {
-            let _ = write!(
-                output,
-                r##"<li><a href="/handler/blockdevice_psi/{}" target="right">Blockdevice-psi {}</a>"##, d, d
+            let _ = write!(output,
+               r##"<li><a href="/handler/blockdevice_extra/{}" target="right">Blockdevice-extra {}</a>"##, d, d
             );
             output
         });
 
-    let html_for_blockdevices_extra = DATA.blockdevices
+    let html_for_blockdevices_latency = DATA.blockdevices
         .read()
         .unwrap()
         .iter()
@@ -190,7 +311,7 @@ pub async fn root_handler() -> Html<String> {
         .iter()
         .fold(String::new(), |mut output, d| {
             let _ = write!(output,
-               r##"<li><a href="/handler/blockdevice_extra/{}" target="right">Blockdevice-extra {}</a>"##, d, d
+               r##"<li><a href="/handler/blockdevice_latency/{}" target="right">Blockdevice-latency {}</a>"##, d, d
             );
             output
         });
@@ -213,7 +334,50 @@ pub async fn root_handler() -> Html<String> {
             );
             output
         });
-    let form = time_form().await;
+    let html_for_views = VIEWS
+        .iter()
+        .filter(|(_, _, collectors)| collectors.iter().all(|collector| DATA.has_rows(*collector)))
+        .fold(String::new(), |mut output, (href, label, _)| {
+            let _ = write!(
+                output,
+                r##"<li><a href="{}" target="right">{}</a></li>"##,
+                href, label
+            );
+            output
+        });
+
+    let form = time_form();
+    let host_form = host_form(host);
+
+    // the archives that are read have their metadata, otherwise it's the metadata of this host.
+    let metadatas = if ARGS.read.is_some() {
+        DATA.hostnames()
+            .iter()
+            .filter_map(|hostname| DATA.host(hostname).metadata.read().unwrap().clone())
+            .collect()
+    } else {
+        vec![ArchiveMetadata::current(
+            DATA.blockdevices
+                .read()
+                .unwrap()
+                .iter()
+                .map(|d| &d.device_name),
+            DATA.networkdevices
+                .read()
+                .unwrap()
+                .iter()
+                .map(|d| &d.device_name),
+        )]
+    };
+    let html_for_metadata = metadatas.iter().fold(String::new(), |mut output, m| {
+        let _ = write!(
+            output,
+            r##"<div title="{}">{}</div>"##,
+            m.kernel_version,
+            m.summary()
+        );
+        output
+    });
 
     format!(
         r##"<!doctype html>
@@ -226,27 +390,19 @@ pub async fn root_handler() -> Html<String> {
    </style>
   </head>
   <body>
+  <header>{html_for_metadata}</header>
   <div class = "container">
    <div class = "column_left">
     <nav>
      <li><a href="/" target="right">Home</a></li>
-     <li><a href="/handler/cpu/x" target="right">CPU total</a></li>
-     <li><a href="/handler/cpu_load/x" target="right">CPU total-load</a></li>
-     <li><a href="/handler/cpu_load_psi/x" target="right">CPU total-load-psi</a></li>
-     <li><a href="/handler/memory/x" target="right">Memory</a></li>
-     <li><a href="/handler/memory_alloc/x" target="right">Memory-alloc</a></li>
-     <li><a href="/handler/memory_commit/x" target="right">Memory-committed</a></li>
-     <li><a href="/handler/memory_psi/x" target="right">Memory-psi</a></li>
-     <li><a href="/handler/memory_psi_alloc/x" target="right">Memory-psi-alloc</a></li>
-     <li><a href="/handler/memory_swap/x" target="right">Memory-swapspace</a></li>
-     <li><a href="/handler/memory_swap_inout/x" target="right">Memory-swapspace-swapio</a></li>
-     <li><a href="/handler/memory_act_inact/x" target="right">Memory-active-inactive</a></li>
-     <li><a href="/handler/memory_dirty/x" target="right">Memory-dirty</a></li>
-     <li><a href="/handler/xfs/x" target="right">Filesystem-XFS</a></li>
+     {html_for_views}
+     <li><a href="/compare" target="right">Compare</a></li>
      {html_for_blockdevices}
      {html_for_blockdevices_psi}
      {html_for_blockdevices_extra}
+     {html_for_blockdevices_latency}
      {html_for_networkdevices}
+     {host_form}
      <p>{form}</p>
     </nav>
    </div>
@@ -258,52 +414,339 @@ pub async fn root_handler() -> Html<String> {
  </html>
  "##
     )
+}
+
+/// The image options of a plot url: `?format=svg|png&width=&height=&scale=`.
+/// The width and height are the logical size of the plot, and default to --graph-width and
+/// --graph-height; the scale multiplies the resolution, so the layout doesn't change.
+#[derive(Debug, Default, Deserialize)]
+pub struct PlotOptions {
+    #[serde(default)]
+    pub format: PlotFormat,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub scale: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PlotFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+impl PlotOptions {
+    /// Returns the logical width, height and the scale, or an error if the size is unreasonable.
+    pub fn size(&self) -> Result<(u32, u32, f64), String> {
+        let width = self.width.unwrap_or(ARGS.graph_width);
+        let height = self.height.unwrap_or(ARGS.graph_height);
+        let scale = self.scale.unwrap_or(1_f64);
+        if !(0.1..=8_f64).contains(&scale) {
+            return Err(format!("scale {} must be between 0.1 and 8", scale));
+        }
+        if !(100..=MAX_PLOT_SIDE).contains(&width) || !(100..=MAX_PLOT_SIDE).contains(&height) {
+            return Err(format!(
+                "width {} and height {} must be between 100 and {}",
+                width, height, MAX_PLOT_SIDE
+            ));
+        }
+        // the image is drawn in a buffer of the size of the number of pixels.
+        if width as f64 * height as f64 * scale * scale > MAX_PLOT_PIXELS as f64 {
+            return Err(format!(
+                "width times height times scale squared must not exceed {} pixels",
+                MAX_PLOT_PIXELS
+            ));
+        }
+        Ok((width, height, scale))
+    }
+    /// The options as query string, to pass them on to another url.
+    pub fn query_string(&self) -> String {
+        let mut query = vec![format!(
+            "format={}",
+            match self.format {
+                PlotFormat::Png => "png",
+                PlotFormat::Svg => "svg",
+            }
+        )];
+        if let Some(width) = self.width {
+            query.push(format!("width={}", width));
+        }
+        if let Some(height) = self.height {
+            query.push(format!("height={}", height));
+        }
+        if let Some(scale) = self.scale {
+            query.push(format!("scale={}", scale));
+        }
+        query.join("&")
+    }
+}
+
+/// The largest width or height of a plot in pixels.
+static MAX_PLOT_SIDE: u32 = 16384;
+/// The largest number of pixels of a plot, which is a buffer of about 100 megabytes.
+static MAX_PLOT_PIXELS: u64 = 32 * 1024 * 1024;
+
+pub async fn handler_html(
+    Path((plot_1, plot_2)): Path<(String, String)>,
+    Query(plot_options): Query<PlotOptions>,
+) -> Html<String> {
+    // the per-cpu heatmap has links to switch between the metrics.
+    let metric_links = match plot_1.as_str() {
+        "cpu_heatmap" => CPU_HEATMAP_METRICS
+            .iter()
+            .fold(String::new(), |mut output, metric| {
+                let _ = write!(
+                    output,
+                    r#" | <a href="/handler/{}/{}?{}">%{}</a>"#,
+                    plot_1,
+                    metric,
+                    plot_options.query_string(),
+                    metric
+                );
+                output
+            }),
+        _ => String::new(),
+    };
+    format!(
+        r#"<a href="/interactive/{}/{}">interactive view</a>{}<br><img src="/plotter/{}/{}?{}">"#,
+        plot_1,
+        plot_2,
+        metric_links,
+        plot_1,
+        plot_2,
+        plot_options.query_string()
+    )
     .into()
 }
 
-pub async fn handler_html(Path((plot_1, plot_2)): Path<(String, String)>) -> Html<String> {
-    format!(r#"<img src="/plotter/{}/{}">"#, plot_1, plot_2).into()
+/// Draw the plot group plot_1 (for device plot_2 if applicable) on a drawing area.
+pub fn draw_plot<DB: DrawingBackend>(
+    backend: DrawingArea<DB, Shift>,
+    plot_1: &str,
+    plot_2: String,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) {
+    // the cluster plot has the time range and the history of all hosts; the other plots use the
+    // roll-up that fits the time range.
+    let rollup = if plot_1 == "cluster" {
+        None
+    } else {
+        DATA.rollup_level(start_time, end_time)
+            .map(|level| (level, Aggregate::Avg))
+    };
+    DATA.with_rollup(rollup, || {
+        let (start_time, end_time) = if plot_1 == "cluster" {
+            (start_time, end_time)
+        } else {
+            DATA.time_range(start_time, end_time)
+        };
+        draw_plot_group(
+            &backend,
+            &plot_group(plot_1, &plot_2),
+            &plot_2,
+            start_time,
+            end_time,
+        );
+    })
+}
+
+/// Render a plot group as png or svg image, and return the image with its content type.
+pub fn render_plot(
+    plot_1: &str,
+    plot_2: String,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+    plot_options: &PlotOptions,
+) -> Result<(&'static str, Vec<u8>), String> {
+    render(
+        plot_1,
+        plot_2,
+        start_time,
+        end_time,
+        View::Plot,
+        plot_options,
+    )
+}
+
+/// Render the overlay of a plot group of all hosts as png or svg image, and return the image with
+/// its content type.
+pub fn render_hosts(
+    plot_1: &str,
+    plot_2: String,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+    plot_options: &PlotOptions,
+) -> Result<(&'static str, Vec<u8>), String> {
+    render(
+        plot_1,
+        plot_2,
+        start_time,
+        end_time,
+        View::Hosts,
+        plot_options,
+    )
+}
+
+/// Render the comparison of a plot group as png or svg image, and return the image with its
+/// content type.
+pub fn render_comparison(
+    plot_1: &str,
+    plot_2: String,
+    comparison: &Comparison,
+    plot_options: &PlotOptions,
+) -> Result<(&'static str, Vec<u8>), String> {
+    render(
+        plot_1,
+        plot_2,
+        None,
+        None,
+        View::Comparison(comparison),
+        plot_options,
+    )
+}
+
+/// What is drawn of a plot group.
+enum View<'a> {
+    Plot,
+    Comparison(&'a Comparison),
+    Hosts,
+}
+
+fn render(
+    plot_1: &str,
+    plot_2: String,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+    view: View,
+    plot_options: &PlotOptions,
+) -> Result<(&'static str, Vec<u8>), String> {
+    let (width, height, scale) = plot_options.size()?;
+    let pixel_width = (width as f64 * scale).round() as u32;
+    let pixel_height = (height as f64 * scale).round() as u32;
+    macro_rules! draw {
+        ($backend:expr) => {
+            match view {
+                View::Plot => draw_plot($backend, plot_1, plot_2, start_time, end_time),
+                View::Comparison(comparison) => {
+                    draw_comparison($backend, plot_1, &plot_2, comparison)
+                }
+                View::Hosts => draw_hosts($backend, plot_1, &plot_2, start_time, end_time),
+            }
+        };
+    }
+    match plot_options.format {
+        PlotFormat::Png => {
+            let mut buffer = vec![0; (pixel_width * pixel_height * 3).try_into().unwrap()];
+            draw!(ScaledBackend::new(
+                BitMapBackend::with_buffer(&mut buffer, (pixel_width, pixel_height)),
+                scale,
+            )
+            .into_drawing_area());
+            let rgb_image = DynamicImage::ImageRgb8(
+                image::RgbImage::from_raw(pixel_width, pixel_height, buffer).unwrap(),
+            );
+            let mut cursor = Cursor::new(Vec::new());
+            rgb_image.write_to(&mut cursor, ImageFormat::Png).unwrap();
+            Ok(("image/png", cursor.into_inner()))
+        }
+        PlotFormat::Svg => {
+            let mut svg = String::new();
+            draw!(ScaledBackend::new(
+                SVGBackend::with_string(&mut svg, (pixel_width, pixel_height)),
+                scale,
+            )
+            .into_drawing_area());
+            Ok(("image/svg+xml", svg.into_bytes()))
+        }
+    }
 }
 
 pub async fn handler_plotter(
     session: Session<SessionNullPool>,
     Path((plot_1, plot_2)): Path<(String, String)>,
+    Query(plot_options): Query<PlotOptions>,
 ) -> impl IntoResponse {
     debug!("handler_plotter: session: {:?}", session);
     let start_time = session.get::<DateTime<Local>>("start_time");
     let end_time = session.get::<DateTime<Local>>("end_time");
-    let mut buffer = vec![
-        0;
-        (ARGS.graph_width * ARGS.graph_height * 3)
-            .try_into()
-            .unwrap()
-    ];
-    match plot_1.as_str() {
-        "networkdevice" => create_networkdevice_plot(&mut buffer, plot_2, start_time, end_time),
-        "blockdevice" => create_blockdevice_plot(&mut buffer, plot_2, start_time, end_time),
-        "blockdevice_psi" => create_blockdevice_psi_plot(&mut buffer, plot_2, start_time, end_time),
-        "blockdevice_extra" => {
-            create_blockdevice_plot_extra(&mut buffer, plot_2, start_time, end_time)
+    let host = session.get::<String>("host");
+    match DATA.with_host(host.as_deref(), || {
+        render_plot(&plot_1, plot_2, start_time, end_time, &plot_options)
+    }) {
+        Ok((content_type, image)) => {
+            ([(header::CONTENT_TYPE, content_type)], image).into_response()
+        }
+        Err(error) => (StatusCode::BAD_REQUEST, error).into_response(),
+    }
+}
+
+pub async fn handler_hosts_plotter(
+    session: Session<SessionNullPool>,
+    Path((plot_1, plot_2)): Path<(String, String)>,
+    Query(plot_options): Query<PlotOptions>,
+) -> impl IntoResponse {
+    let start_time = session.get::<DateTime<Local>>("start_time");
+    let end_time = session.get::<DateTime<Local>>("end_time");
+    match render_hosts(&plot_1, plot_2, start_time, end_time, &plot_options) {
+        Ok((content_type, image)) => {
+            ([(header::CONTENT_TYPE, content_type)], image).into_response()
         }
-        "cpu" => create_cpu_plot(&mut buffer, start_time, end_time),
-        "cpu_load" => create_cpu_load_plot(&mut buffer, start_time, end_time),
-        "cpu_load_psi" => create_cpu_load_pressure_plot(&mut buffer, start_time, end_time),
-        "memory" => create_memory_plot(&mut buffer, start_time, end_time),
-        "memory_alloc" => create_memory_alloc_plot(&mut buffer, start_time, end_time),
-        "memory_commit" => create_memory_commit(&mut buffer, start_time, end_time),
-        "memory_psi" => create_memory_psi_plot(&mut buffer, start_time, end_time),
-        "memory_psi_alloc" => create_memory_alloc_psi_plot(&mut buffer, start_time, end_time),
-        "memory_swap" => create_memory_swap_plot(&mut buffer, start_time, end_time),
-        "memory_swap_inout" => create_memory_swap_inout_plot(&mut buffer, start_time, end_time),
-        "memory_act_inact" => create_memory_active_inactive_plot(&mut buffer, start_time, end_time),
-        "memory_dirty" => create_memory_dirty_plot(&mut buffer, start_time, end_time),
-        "xfs" => create_xfs_plot(&mut buffer, start_time, end_time),
-        &_ => todo!(),
+        Err(error) => (StatusCode::BAD_REQUEST, error).into_response(),
+    }
+}
+
+pub async fn handler_compare_plotter(
+    session: Session<SessionNullPool>,
+    Query(compare_query): Query<CompareQuery>,
+    Query(plot_options): Query<PlotOptions>,
+) -> impl IntoResponse {
+    let Some(comparison) = compare_query.comparison() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "invalid or missing comparison times",
+        )
+            .into_response();
+    };
+    let host = session.get::<String>("host");
+    match DATA.with_host(host.as_deref(), || {
+        render_comparison(
+            &compare_query.plot.unwrap_or_default(),
+            compare_query.device.unwrap_or_default(),
+            &comparison,
+            &plot_options,
+        )
+    }) {
+        Ok((content_type, image)) => {
+            ([(header::CONTENT_TYPE, content_type)], image).into_response()
+        }
+        Err(error) => (StatusCode::BAD_REQUEST, error).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plot_size_is_limited_by_side_and_number_of_pixels() {
+        let size = |width: u32, height: u32, scale: f64| {
+            PlotOptions {
+                width: Some(width),
+                height: Some(height),
+                scale: Some(scale),
+                ..Default::default()
+            }
+            .size()
+        };
+        assert_eq!(size(1600, 1200, 2_f64), Ok((1600, 1200, 2_f64)));
+        assert_eq!(size(16384, 2048, 1_f64), Ok((16384, 2048, 1_f64)));
+        assert!(size(99, 1200, 1_f64).is_err());
+        assert!(size(16385, 1200, 1_f64).is_err());
+        assert!(size(1600, 1200, 9_f64).is_err());
+        // every side is allowed, but the number of pixels is not.
+        assert!(size(16384, 16384, 1_f64).is_err());
+        assert!(size(4096, 4096, 2_f64).is_err());
     }
-    let rgb_image = DynamicImage::ImageRgb8(
-        image::RgbImage::from_raw(ARGS.graph_width, ARGS.graph_height, buffer).unwrap(),
-    );
-    let mut cursor = Cursor::new(Vec::new());
-    rgb_image.write_to(&mut cursor, ImageFormat::Png).unwrap();
-    cursor.into_inner()
 }
@@ -1,6 +1,6 @@
 #![allow(unused_assignments)]
 
-use crate::ARGS;
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
 use crate::{
     CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, DATA, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
     LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE,
@@ -8,48 +8,14 @@ use crate::{
 use chrono::{DateTime, Local};
 use log::debug;
 use ordered_float::OrderedFloat;
-use plotters::backend::{BitMapBackend, RGBPixel};
 use plotters::chart::{ChartBuilder, LabelAreaPosition, SeriesLabelPosition::UpperLeft};
 use plotters::coord::Shift;
 use plotters::element::Rectangle;
 use plotters::prelude::*;
 use std::collections::BTreeSet;
 
-pub fn create_networkdevice_plot(
-    buffer: &mut [u8],
-    device_name: String,
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((3, 1));
-    let mut mbit_graph = multi_backend[0].split_horizontally((60).percent_width());
-    networkdevice_mbit_plot(&mut mbit_graph.0, device_name.clone(), start_time, end_time);
-    networkdevice_mbit_percentile_plot(
-        &mut mbit_graph.1,
-        device_name.clone(),
-        start_time,
-        end_time,
-    );
-    let mut packet_graph = multi_backend[1].split_horizontally((60).percent_width());
-    networkdevice_packet_plot(
-        &mut packet_graph.0,
-        device_name.clone(),
-        start_time,
-        end_time,
-    );
-    networkdevice_packet_percentile_plot(
-        &mut packet_graph.1,
-        device_name.clone(),
-        start_time,
-        end_time,
-    );
-    networkdevice_error_plot(&mut multi_backend, 2, device_name, start_time, end_time);
-}
-
-fn networkdevice_mbit_percentile_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn networkdevice_mbit_percentile_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -316,8 +282,8 @@ fn networkdevice_mbit_percentile_plot(
         .draw()
         .unwrap();
 }
-fn networkdevice_mbit_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn networkdevice_mbit_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -352,8 +318,7 @@ fn networkdevice_mbit_plot(
         .unwrap_or_default();
     let latest = historical_data_read
         .iter()
-        .filter(|networkdevice| networkdevice.device_name == device_name)
-        .last();
+        .rfind(|networkdevice| networkdevice.device_name == device_name);
     debug!(
         "mbit plot. start_time: {:?}, final_start_time {:?}",
         start_time, final_start_time
@@ -562,8 +527,8 @@ fn networkdevice_mbit_plot(
         .unwrap();
 }
 
-fn networkdevice_packet_percentile_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn networkdevice_packet_percentile_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -823,8 +788,8 @@ fn networkdevice_packet_percentile_plot(
         .draw()
         .unwrap();
 }
-fn networkdevice_packet_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn networkdevice_packet_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     device_name: String,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -859,8 +824,7 @@ fn networkdevice_packet_plot(
         .unwrap_or_default();
     let latest = historical_data_read
         .iter()
-        .filter(|networkdevice| networkdevice.device_name == device_name)
-        .last();
+        .rfind(|networkdevice| networkdevice.device_name == device_name);
 
     // create the plot
     multi_backend.fill(&WHITE).unwrap();
@@ -1019,8 +983,8 @@ fn networkdevice_packet_plot(
         .unwrap();
 }
 
-fn networkdevice_error_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub(crate) fn networkdevice_error_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     device_name: String,
     start_time: Option<DateTime<Local>>,
@@ -1118,8 +1082,7 @@ fn networkdevice_error_plot(
     .unwrap();
     let latest = historical_data_read
         .iter()
-        .filter(|n| n.device_name == device_name)
-        .last();
+        .rfind(|n| n.device_name == device_name);
 
     // create the plot
     multi_backend[backend_number].fill(&WHITE).unwrap();
@@ -1204,3 +1167,119 @@ fn networkdevice_error_plot(
         .draw()
         .unwrap();
 }
+
+pub fn networkdevice_mbit_panel(
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.networkdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|n| n.device_name == device_name),
+        |n| n.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: format!("Networkdevice: {} Megabit per second", device_name),
+        y_desc: "Megabit per second".to_string(),
+        series: vec![
+            Series::new("total", &rows, |n| {
+                (
+                    n.timestamp,
+                    ((n.receive_bytes + n.transmit_bytes) / (1024_f64 * 1024_f64)) * 8_f64,
+                )
+            }),
+            Series::new("transmit", &rows, |n| {
+                (
+                    n.timestamp,
+                    (n.transmit_bytes / (1024_f64 * 1024_f64)) * 8_f64,
+                )
+            })
+            .as_points(),
+            Series::new("receive", &rows, |n| {
+                (
+                    n.timestamp,
+                    (n.receive_bytes / (1024_f64 * 1024_f64)) * 8_f64,
+                )
+            })
+            .as_points(),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn networkdevice_packet_panel(
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.networkdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|n| n.device_name == device_name),
+        |n| n.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: format!("Networkdevice: {} packets per second", device_name),
+        y_desc: "Packets per second".to_string(),
+        series: vec![
+            Series::new("total", &rows, |n| {
+                (n.timestamp, n.receive_packets + n.transmit_packets)
+            }),
+            Series::new("transmit", &rows, |n| (n.timestamp, n.transmit_packets)).as_points(),
+            Series::new("receive", &rows, |n| (n.timestamp, n.receive_packets)).as_points(),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn networkdevice_error_panel(
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.networkdevices.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read
+            .iter()
+            .filter(|n| n.device_name == device_name),
+        |n| n.timestamp,
+        start_time,
+        end_time,
+    );
+    let mut series = Vec::new();
+    macro_rules! add_error_series {
+        ($($struct_field_name:ident),*) => {
+            $(
+                series.push(
+                    Series::new(stringify!($struct_field_name), &rows, |n| {
+                        (n.timestamp, n.$struct_field_name)
+                    })
+                    .as_points(),
+                );
+            )*
+        };
+    }
+    add_error_series!(
+        receive_errors,
+        transmit_errors,
+        transmit_collisions,
+        receive_drop,
+        transmit_drop,
+        transmit_carrier,
+        receive_fifo,
+        transmit_fifo
+    );
+    Panel {
+        title: format!("Networkdevice: {} errors", device_name),
+        y_desc: "Errors per second".to_string(),
+        series,
+        ..Default::default()
+    }
+}
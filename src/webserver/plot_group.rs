@@ -0,0 +1,380 @@
+//! The plot groups. A plot group is a number of charts from top to bottom, which are drawn as
+//! plotters charts for the bitmap view, and as panels for the interactive view.
+use chrono::{DateTime, Local};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::processor::blockdevice::is_total;
+use crate::webserver::blockdevice::{
+    blockdevice_extra, blockdevice_extra_panel, blockdevice_iops_panel,
+    blockdevice_iops_percentile_plot, blockdevice_iops_plot, blockdevice_iosize_panel,
+    blockdevice_iosize_plot, blockdevice_latency_heatmap_plot, blockdevice_latency_panel,
+    blockdevice_latency_percentile_plot, blockdevice_latency_queuedepth_panel,
+    blockdevice_latency_queuedepth_plot, blockdevice_mbps_panel, blockdevice_mbps_percentile_plot,
+    blockdevice_mbps_plot, blockdevice_topology_panels, blockdevice_topology_plot,
+    blockdevice_utilization_panel, blockdevice_utilization_plot, read_latency, topology_devices,
+    write_latency,
+};
+use crate::webserver::cluster::{cluster_panels, cluster_plot};
+use crate::webserver::interactive::Panel;
+use crate::webserver::loadavg::{load_panel, load_plot};
+use crate::webserver::meminfo::{
+    active_inactive_mem_panel, active_inactive_mem_plot, committed_mem_panel, committed_mem_plot,
+    memory_panel, memory_plot, swap_space_panel, swap_space_plot,
+};
+use crate::webserver::net_dev::{
+    networkdevice_error_panel, networkdevice_error_plot, networkdevice_mbit_panel,
+    networkdevice_mbit_percentile_plot, networkdevice_mbit_plot, networkdevice_packet_panel,
+    networkdevice_packet_percentile_plot, networkdevice_packet_plot,
+};
+use crate::webserver::pressure::{
+    pressure_cpu_some_panel, pressure_cpu_some_plot, pressure_io_panel, pressure_io_plot,
+    pressure_memory_panel, pressure_memory_plot,
+};
+use crate::webserver::schedstat::{
+    schedstat_wait_panel, schedstat_wait_per_timeslice_panel, schedstat_wait_per_timeslice_plot,
+    schedstat_wait_plot,
+};
+use crate::webserver::stat::{
+    cpu_heatmap_panel, cpu_heatmap_plot, cpu_total_panel, cpu_total_plot,
+};
+use crate::webserver::vmstat::{
+    pages_allocated_and_free, pages_allocated_and_free_panel, pages_dirty, pages_dirty_panel,
+    swap_inout_panel, swap_inout_plot,
+};
+use crate::webserver::xfs::{
+    xfs_iops_panel, xfs_iops_percentile_plot, xfs_iops_plot, xfs_mbps_panel,
+    xfs_mbps_percentile_plot, xfs_mbps_plot,
+};
+
+/// A chart of a plot group. The chart is a single row of the bitmap plot, and one or more
+/// panels of the interactive page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Chart {
+    CpuTotal,
+    CpuHeatmap,
+    Load,
+    PressureCpuSome,
+    PressureMemory,
+    PressureIo,
+    SchedstatWait,
+    SchedstatWaitPerTimeslice,
+    Memory,
+    PagesAllocatedAndFree,
+    CommittedMemory,
+    SwapSpace,
+    SwapInout,
+    ActiveInactiveMemory,
+    PagesDirty,
+    XfsMbps,
+    XfsIops,
+    BlockdeviceMbps,
+    BlockdeviceIops,
+    BlockdeviceLatencyQueuedepth,
+    BlockdeviceIosize,
+    BlockdeviceUtilization,
+    BlockdeviceExtra,
+    BlockdeviceTopology,
+    BlockdeviceReadLatency,
+    BlockdeviceWriteLatency,
+    NetworkdeviceMbit,
+    NetworkdevicePacket,
+    NetworkdeviceError,
+    Cluster,
+}
+
+/// The charts of a plot group, from top to bottom. The device name is the device of the device
+/// specific plot groups, or the metric of the cpu heatmap. An unknown plot group has no charts.
+pub fn plot_group(plot_1: &str, device_name: &str) -> Vec<Chart> {
+    match plot_1 {
+        "networkdevice" => vec![
+            Chart::NetworkdeviceMbit,
+            Chart::NetworkdevicePacket,
+            Chart::NetworkdeviceError,
+        ],
+        "blockdevice" | "blockdevice_extra" => {
+            let mut charts = vec![
+                Chart::BlockdeviceMbps,
+                Chart::BlockdeviceIops,
+                Chart::BlockdeviceLatencyQueuedepth,
+            ];
+            if !is_total(device_name) {
+                charts.push(Chart::BlockdeviceIosize);
+                charts.push(Chart::BlockdeviceUtilization);
+                if plot_1 == "blockdevice_extra" {
+                    charts.push(Chart::BlockdeviceExtra);
+                }
+            }
+            if !topology_devices(device_name).is_empty() {
+                charts.push(Chart::BlockdeviceTopology);
+            }
+            charts
+        }
+        "blockdevice_psi" => vec![
+            Chart::BlockdeviceMbps,
+            Chart::BlockdeviceIops,
+            Chart::BlockdeviceLatencyQueuedepth,
+            Chart::PressureIo,
+        ],
+        "blockdevice_latency" => vec![
+            Chart::BlockdeviceReadLatency,
+            Chart::BlockdeviceWriteLatency,
+        ],
+        "cpu" => vec![Chart::CpuTotal],
+        "cpu_load" => vec![Chart::CpuTotal, Chart::Load],
+        "cpu_load_psi" => vec![Chart::CpuTotal, Chart::Load, Chart::PressureCpuSome],
+        "cpu_heatmap" => vec![Chart::CpuTotal, Chart::CpuHeatmap],
+        "schedstat" => vec![
+            Chart::SchedstatWait,
+            Chart::SchedstatWaitPerTimeslice,
+            Chart::PressureCpuSome,
+        ],
+        "memory" => vec![Chart::Memory],
+        "memory_alloc" => vec![Chart::Memory, Chart::PagesAllocatedAndFree],
+        "memory_commit" => vec![Chart::Memory, Chart::CommittedMemory],
+        "memory_psi" => vec![Chart::Memory, Chart::PressureMemory],
+        "memory_psi_alloc" => vec![
+            Chart::Memory,
+            Chart::PagesAllocatedAndFree,
+            Chart::PressureMemory,
+        ],
+        "memory_swap" => vec![Chart::Memory, Chart::SwapSpace],
+        "memory_swap_inout" => vec![Chart::Memory, Chart::SwapInout, Chart::SwapSpace],
+        "memory_act_inact" => vec![Chart::Memory, Chart::ActiveInactiveMemory],
+        "memory_dirty" => vec![Chart::Memory, Chart::PagesDirty],
+        "xfs" => vec![Chart::XfsMbps, Chart::XfsIops],
+        "cluster" => vec![Chart::Cluster],
+        &_ => Vec::new(),
+    }
+}
+
+impl Chart {
+    /// The relative height of the chart in the bitmap plot.
+    fn height(&self) -> u32 {
+        match self {
+            Chart::CpuHeatmap => 3,
+            _ => 2,
+        }
+    }
+    /// The panels of the chart; a chart that is not applicable to the device has no panels.
+    pub fn panels(
+        &self,
+        device_name: &str,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+    ) -> Vec<Panel> {
+        let panel = match self {
+            Chart::CpuTotal => cpu_total_panel(start_time, end_time),
+            Chart::CpuHeatmap => cpu_heatmap_panel(device_name, start_time, end_time),
+            Chart::Load => load_panel(start_time, end_time),
+            Chart::PressureCpuSome => pressure_cpu_some_panel(start_time, end_time),
+            Chart::PressureMemory => pressure_memory_panel(start_time, end_time),
+            Chart::PressureIo => pressure_io_panel(start_time, end_time),
+            Chart::SchedstatWait => schedstat_wait_panel(start_time, end_time),
+            Chart::SchedstatWaitPerTimeslice => {
+                schedstat_wait_per_timeslice_panel(start_time, end_time)
+            }
+            Chart::Memory => memory_panel(start_time, end_time),
+            Chart::PagesAllocatedAndFree => pages_allocated_and_free_panel(start_time, end_time),
+            Chart::CommittedMemory => committed_mem_panel(start_time, end_time),
+            Chart::SwapSpace => swap_space_panel(start_time, end_time),
+            Chart::SwapInout => swap_inout_panel(start_time, end_time),
+            Chart::ActiveInactiveMemory => active_inactive_mem_panel(start_time, end_time),
+            Chart::PagesDirty => pages_dirty_panel(start_time, end_time),
+            Chart::XfsMbps => xfs_mbps_panel(start_time, end_time),
+            Chart::XfsIops => xfs_iops_panel(start_time, end_time),
+            Chart::BlockdeviceMbps => blockdevice_mbps_panel(device_name, start_time, end_time),
+            Chart::BlockdeviceIops => blockdevice_iops_panel(device_name, start_time, end_time),
+            Chart::BlockdeviceLatencyQueuedepth => {
+                blockdevice_latency_queuedepth_panel(device_name, start_time, end_time)
+            }
+            Chart::BlockdeviceIosize => blockdevice_iosize_panel(device_name, start_time, end_time),
+            Chart::BlockdeviceUtilization => {
+                blockdevice_utilization_panel(device_name, start_time, end_time)
+            }
+            Chart::BlockdeviceExtra => blockdevice_extra_panel(device_name),
+            Chart::BlockdeviceTopology => {
+                return blockdevice_topology_panels(device_name, start_time, end_time)
+            }
+            Chart::BlockdeviceReadLatency => {
+                blockdevice_latency_panel(device_name, "read", read_latency, start_time, end_time)
+            }
+            Chart::BlockdeviceWriteLatency => {
+                blockdevice_latency_panel(device_name, "write", write_latency, start_time, end_time)
+            }
+            Chart::NetworkdeviceMbit => networkdevice_mbit_panel(device_name, start_time, end_time),
+            Chart::NetworkdevicePacket => {
+                networkdevice_packet_panel(device_name, start_time, end_time)
+            }
+            Chart::NetworkdeviceError => {
+                networkdevice_error_panel(device_name, start_time, end_time)
+            }
+            Chart::Cluster => return cluster_panels(start_time, end_time),
+        };
+        vec![panel]
+    }
+    /// Draw the chart on the area of its row. A chart with percentiles has the percentiles drawn
+    /// next to it.
+    pub fn draw<DB: DrawingBackend>(
+        &self,
+        area: &DrawingArea<DB, Shift>,
+        device_name: &str,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+    ) {
+        let device = device_name.to_string();
+        let mut area = area.clone();
+        let percentile_areas = || area.split_horizontally((60).percent_width());
+        match self {
+            Chart::CpuTotal => cpu_total_plot(&mut [area], 0, start_time, end_time),
+            Chart::CpuHeatmap => cpu_heatmap_plot(&mut area, device_name, start_time, end_time),
+            Chart::Load => load_plot(&mut [area], 0, start_time, end_time),
+            Chart::PressureCpuSome => pressure_cpu_some_plot(&mut [area], 0, start_time, end_time),
+            Chart::PressureMemory => pressure_memory_plot(&mut [area], 0, start_time, end_time),
+            Chart::PressureIo => pressure_io_plot(&mut [area], 0, start_time, end_time),
+            Chart::SchedstatWait => schedstat_wait_plot(&mut area, start_time, end_time),
+            Chart::SchedstatWaitPerTimeslice => {
+                schedstat_wait_per_timeslice_plot(&mut area, start_time, end_time)
+            }
+            Chart::Memory => memory_plot(&mut [area], 0, start_time, end_time),
+            Chart::PagesAllocatedAndFree => {
+                pages_allocated_and_free(&mut [area], 0, start_time, end_time)
+            }
+            Chart::CommittedMemory => committed_mem_plot(&mut [area], 0, start_time, end_time),
+            Chart::SwapSpace => swap_space_plot(&mut [area], 0, start_time, end_time),
+            Chart::SwapInout => swap_inout_plot(&mut [area], 0, start_time, end_time),
+            Chart::ActiveInactiveMemory => {
+                active_inactive_mem_plot(&mut [area], 0, start_time, end_time)
+            }
+            Chart::PagesDirty => pages_dirty(&mut [area], 0, start_time, end_time),
+            Chart::XfsMbps => {
+                let (mut chart, mut percentiles) = percentile_areas();
+                xfs_mbps_plot(&mut chart, start_time, end_time);
+                xfs_mbps_percentile_plot(&mut percentiles, start_time, end_time);
+            }
+            Chart::XfsIops => {
+                let (mut chart, mut percentiles) = percentile_areas();
+                xfs_iops_plot(&mut chart, start_time, end_time);
+                xfs_iops_percentile_plot(&mut percentiles, start_time, end_time);
+            }
+            Chart::BlockdeviceMbps => {
+                let (mut chart, mut percentiles) = percentile_areas();
+                blockdevice_mbps_plot(&mut chart, device.clone(), start_time, end_time);
+                blockdevice_mbps_percentile_plot(&mut percentiles, device, start_time, end_time);
+            }
+            Chart::BlockdeviceIops => {
+                let (mut chart, mut percentiles) = percentile_areas();
+                blockdevice_iops_plot(&mut chart, device.clone(), start_time, end_time);
+                blockdevice_iops_percentile_plot(&mut percentiles, device, start_time, end_time);
+            }
+            Chart::BlockdeviceLatencyQueuedepth => {
+                let (mut chart, mut percentiles) = percentile_areas();
+                blockdevice_latency_queuedepth_plot(
+                    &mut chart,
+                    device.clone(),
+                    start_time,
+                    end_time,
+                );
+                blockdevice_latency_percentile_plot(&mut percentiles, device, start_time, end_time);
+            }
+            Chart::BlockdeviceIosize => {
+                blockdevice_iosize_plot(&mut [area], 0, device, start_time, end_time)
+            }
+            Chart::BlockdeviceUtilization => {
+                blockdevice_utilization_plot(&mut area, device_name, start_time, end_time)
+            }
+            Chart::BlockdeviceExtra => blockdevice_extra(&mut [area], 0, device),
+            Chart::BlockdeviceTopology => blockdevice_topology_plot(
+                &mut area,
+                device_name,
+                &topology_devices(device_name),
+                start_time,
+                end_time,
+            ),
+            Chart::BlockdeviceReadLatency => blockdevice_latency_heatmap_plot(
+                &mut area,
+                device_name,
+                "read",
+                read_latency,
+                start_time,
+                end_time,
+            ),
+            Chart::BlockdeviceWriteLatency => blockdevice_latency_heatmap_plot(
+                &mut area,
+                device_name,
+                "write",
+                write_latency,
+                start_time,
+                end_time,
+            ),
+            Chart::NetworkdeviceMbit => {
+                let (mut chart, mut percentiles) = percentile_areas();
+                networkdevice_mbit_plot(&mut chart, device.clone(), start_time, end_time);
+                networkdevice_mbit_percentile_plot(&mut percentiles, device, start_time, end_time);
+            }
+            Chart::NetworkdevicePacket => {
+                let (mut chart, mut percentiles) = percentile_areas();
+                networkdevice_packet_plot(&mut chart, device.clone(), start_time, end_time);
+                networkdevice_packet_percentile_plot(
+                    &mut percentiles,
+                    device,
+                    start_time,
+                    end_time,
+                );
+            }
+            Chart::NetworkdeviceError => {
+                networkdevice_error_plot(&mut [area], 0, device, start_time, end_time)
+            }
+            Chart::Cluster => cluster_plot(&area, start_time, end_time),
+        }
+    }
+}
+
+/// Draw the charts of a plot group, every chart in a row with the relative height of the chart.
+pub fn draw_plot_group<DB: DrawingBackend>(
+    backend: &DrawingArea<DB, Shift>,
+    charts: &[Chart],
+    device_name: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) {
+    let total_height = charts.iter().map(Chart::height).sum::<u32>();
+    let (_, pixels) = backend.dim_in_pixel();
+    let mut height = 0;
+    let breakpoints = charts
+        .iter()
+        .take(charts.len().saturating_sub(1))
+        .map(|chart| {
+            height += chart.height();
+            pixels * height / total_height
+        })
+        .collect::<Vec<u32>>();
+    let areas = backend.split_by_breakpoints(Vec::<u32>::new(), breakpoints);
+    for (chart, area) in charts.iter().zip(areas.iter()) {
+        chart.draw(area, device_name, start_time, end_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plot_groups_have_their_charts() {
+        assert_eq!(
+            plot_group("cpu_load_psi", "all"),
+            vec![Chart::CpuTotal, Chart::Load, Chart::PressureCpuSome]
+        );
+        // the total has no io size, utilization and extra info.
+        assert_eq!(
+            plot_group("blockdevice_extra", "TOTAL"),
+            vec![
+                Chart::BlockdeviceMbps,
+                Chart::BlockdeviceIops,
+                Chart::BlockdeviceLatencyQueuedepth,
+            ]
+        );
+        assert_eq!(plot_group("blockdevice_extra", "sda").len(), 6);
+        assert!(plot_group("nonexistent", "").is_empty());
+    }
+}
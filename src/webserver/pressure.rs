@@ -1,10 +1,10 @@
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
 use crate::{
     CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, DATA, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
     LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, LABEL_AREA_SIZE_RIGHT, MESH_STYLE_FONT,
     MESH_STYLE_FONT_SIZE,
 };
 use chrono::{DateTime, Local};
-use plotters::backend::{BitMapBackend, RGBPixel};
 use plotters::chart::SeriesLabelPosition::UpperLeft;
 use plotters::chart::{ChartBuilder, LabelAreaPosition};
 use plotters::coord::Shift;
@@ -70,8 +70,8 @@ struct HighValue {
     pub io_full_total: f64,
 }
 
-pub fn pressure_cpu_some_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub fn pressure_cpu_some_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -267,8 +267,8 @@ pub fn pressure_cpu_some_plot(
         .draw()
         .unwrap();
 }
-pub fn pressure_memory_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub fn pressure_memory_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -497,8 +497,8 @@ pub fn pressure_memory_plot(
         .unwrap();
 }
 
-pub fn pressure_io_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub fn pressure_io_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -725,3 +725,131 @@ pub fn pressure_io_plot(
         .draw()
         .unwrap();
 }
+
+// the totals are in microseconds, the plots show them in seconds per second.
+pub fn pressure_cpu_some_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.pressure.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |p| p.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "Pressure stall CPU".to_string(),
+        y_desc: "Time per second".to_string(),
+        y2_desc: Some("Percent".to_string()),
+        series: vec![
+            Series::new("cpu_some_total", &rows, |p| {
+                (p.timestamp, p.cpu_some_total / 1_000_000_f64)
+            })
+            .stacked("some"),
+            Series::new("cpu_some_avg10", &rows, |p| (p.timestamp, p.cpu_some_avg10))
+                .on_secondary_axis(),
+            Series::new("cpu_some_avg60", &rows, |p| (p.timestamp, p.cpu_some_avg60))
+                .on_secondary_axis(),
+            Series::new("cpu_some_avg300", &rows, |p| {
+                (p.timestamp, p.cpu_some_avg300)
+            })
+            .on_secondary_axis(),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn pressure_memory_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.pressure.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |p| p.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "Pressure stall memory".to_string(),
+        y_desc: "Time per second".to_string(),
+        y2_desc: Some("Percent".to_string()),
+        series: vec![
+            Series::new("memory_some_total", &rows, |p| {
+                (p.timestamp, p.memory_some_total / 1_000_000_f64)
+            })
+            .stacked("some"),
+            Series::new("memory_some_avg10", &rows, |p| {
+                (p.timestamp, p.memory_some_avg10)
+            })
+            .on_secondary_axis(),
+            Series::new("memory_some_avg60", &rows, |p| {
+                (p.timestamp, p.memory_some_avg60)
+            })
+            .on_secondary_axis(),
+            Series::new("memory_some_avg300", &rows, |p| {
+                (p.timestamp, p.memory_some_avg300)
+            })
+            .on_secondary_axis(),
+            Series::new("memory_full_total", &rows, |p| {
+                (p.timestamp, p.memory_full_total / 1_000_000_f64)
+            })
+            .stacked("full"),
+            Series::new("memory_full_avg10", &rows, |p| {
+                (p.timestamp, p.memory_full_avg10)
+            })
+            .on_secondary_axis(),
+            Series::new("memory_full_avg60", &rows, |p| {
+                (p.timestamp, p.memory_full_avg60)
+            })
+            .on_secondary_axis(),
+            Series::new("memory_full_avg300", &rows, |p| {
+                (p.timestamp, p.memory_full_avg300)
+            })
+            .on_secondary_axis(),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn pressure_io_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.pressure.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |p| p.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "Pressure stall io".to_string(),
+        y_desc: "Time per second".to_string(),
+        y2_desc: Some("Percent".to_string()),
+        series: vec![
+            Series::new("io_some_total", &rows, |p| {
+                (p.timestamp, p.io_some_total / 1_000_000_f64)
+            })
+            .stacked("some"),
+            Series::new("io_some_avg10", &rows, |p| (p.timestamp, p.io_some_avg10))
+                .on_secondary_axis(),
+            Series::new("io_some_avg60", &rows, |p| (p.timestamp, p.io_some_avg60))
+                .on_secondary_axis(),
+            Series::new("io_some_avg300", &rows, |p| (p.timestamp, p.io_some_avg300))
+                .on_secondary_axis(),
+            Series::new("io_full_total", &rows, |p| {
+                (p.timestamp, p.io_full_total / 1_000_000_f64)
+            })
+            .stacked("full"),
+            Series::new("io_full_avg10", &rows, |p| (p.timestamp, p.io_full_avg10))
+                .on_secondary_axis(),
+            Series::new("io_full_avg60", &rows, |p| (p.timestamp, p.io_full_avg60))
+                .on_secondary_axis(),
+            Series::new("io_full_avg300", &rows, |p| (p.timestamp, p.io_full_avg300))
+                .on_secondary_axis(),
+        ],
+        ..Default::default()
+    }
+}
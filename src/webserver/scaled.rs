@@ -0,0 +1,167 @@
+use plotters::style::{FontDesc, TextStyle};
+use plotters_backend::{
+    BackendColor, BackendCoord, BackendStyle, BackendTextStyle, DrawingBackend, DrawingErrorKind,
+};
+
+/// A drawing backend that draws on a backend that is `scale` times larger than its own size.
+/// The plot functions lay out the plots for the logical size, and all coordinates, line widths
+/// and font sizes are multiplied by the scale, so a plot rendered with scale 2 looks identical to
+/// the unscaled plot, but with twice the resolution.
+pub struct ScaledBackend<DB: DrawingBackend> {
+    backend: DB,
+    scale: f64,
+}
+
+struct ScaledStyle {
+    color: BackendColor,
+    stroke_width: u32,
+}
+
+impl BackendStyle for ScaledStyle {
+    fn color(&self) -> BackendColor {
+        self.color
+    }
+    fn stroke_width(&self) -> u32 {
+        self.stroke_width
+    }
+}
+
+impl<DB: DrawingBackend> ScaledBackend<DB> {
+    pub fn new(backend: DB, scale: f64) -> Self {
+        ScaledBackend { backend, scale }
+    }
+    fn coord(&self, (x, y): BackendCoord) -> BackendCoord {
+        (
+            (x as f64 * self.scale).round() as i32,
+            (y as f64 * self.scale).round() as i32,
+        )
+    }
+    fn style<S: BackendStyle>(&self, style: &S) -> ScaledStyle {
+        ScaledStyle {
+            color: style.color(),
+            stroke_width: (style.stroke_width() as f64 * self.scale)
+                .round()
+                .max(1_f64) as u32,
+        }
+    }
+    fn text_style<'a, TStyle: BackendTextStyle>(&self, style: &'a TStyle) -> TextStyle<'a> {
+        TextStyle {
+            font: FontDesc::new(style.family(), style.size() * self.scale, style.style())
+                .transform(style.transform()),
+            color: style.color(),
+            pos: style.anchor(),
+        }
+    }
+}
+
+impl<DB: DrawingBackend> DrawingBackend for ScaledBackend<DB> {
+    type ErrorType = DB::ErrorType;
+
+    fn get_size(&self) -> (u32, u32) {
+        let (width, height) = self.backend.get_size();
+        (
+            (width as f64 / self.scale).round() as u32,
+            (height as f64 / self.scale).round() as u32,
+        )
+    }
+    fn ensure_prepared(&mut self) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.backend.ensure_prepared()
+    }
+    fn present(&mut self) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.backend.present()
+    }
+    fn draw_pixel(
+        &mut self,
+        point: BackendCoord,
+        color: BackendColor,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        let upper_left = self.coord(point);
+        let bottom_right = self.coord((point.0 + 1, point.1 + 1));
+        self.backend.draw_rect(
+            upper_left,
+            (bottom_right.0 - 1, bottom_right.1 - 1),
+            &color,
+            true,
+        )
+    }
+    fn draw_line<S: BackendStyle>(
+        &mut self,
+        from: BackendCoord,
+        to: BackendCoord,
+        style: &S,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        let style = self.style(style);
+        self.backend
+            .draw_line(self.coord(from), self.coord(to), &style)
+    }
+    fn draw_rect<S: BackendStyle>(
+        &mut self,
+        upper_left: BackendCoord,
+        bottom_right: BackendCoord,
+        style: &S,
+        fill: bool,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        let style = self.style(style);
+        self.backend.draw_rect(
+            self.coord(upper_left),
+            self.coord(bottom_right),
+            &style,
+            fill,
+        )
+    }
+    fn draw_path<S: BackendStyle, I: IntoIterator<Item = BackendCoord>>(
+        &mut self,
+        path: I,
+        style: &S,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        let style = self.style(style);
+        let path: Vec<_> = path.into_iter().map(|point| self.coord(point)).collect();
+        self.backend.draw_path(path, &style)
+    }
+    fn draw_circle<S: BackendStyle>(
+        &mut self,
+        center: BackendCoord,
+        radius: u32,
+        style: &S,
+        fill: bool,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        let style = self.style(style);
+        self.backend.draw_circle(
+            self.coord(center),
+            (radius as f64 * self.scale).round() as u32,
+            &style,
+            fill,
+        )
+    }
+    fn fill_polygon<S: BackendStyle, I: IntoIterator<Item = BackendCoord>>(
+        &mut self,
+        vert: I,
+        style: &S,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        let style = self.style(style);
+        let vert: Vec<_> = vert.into_iter().map(|point| self.coord(point)).collect();
+        self.backend.fill_polygon(vert, &style)
+    }
+    fn draw_text<TStyle: BackendTextStyle>(
+        &mut self,
+        text: &str,
+        style: &TStyle,
+        pos: BackendCoord,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        let text_style = self.text_style(style);
+        self.backend.draw_text(text, &text_style, self.coord(pos))
+    }
+    fn estimate_text_size<TStyle: BackendTextStyle>(
+        &self,
+        text: &str,
+        style: &TStyle,
+    ) -> Result<(u32, u32), DrawingErrorKind<Self::ErrorType>> {
+        let (width, height) = self
+            .backend
+            .estimate_text_size(text, &self.text_style(style))?;
+        Ok((
+            (width as f64 / self.scale).round() as u32,
+            (height as f64 / self.scale).round() as u32,
+        ))
+    }
+}
@@ -0,0 +1,227 @@
+use plotters::chart::SeriesLabelPosition::UpperLeft;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::processor::schedstat::SchedStatInfo;
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
+use crate::DATA;
+use crate::{
+    CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
+    LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE,
+};
+use chrono::{DateTime, Local};
+use std::collections::BTreeSet;
+
+/// The cpu names of the rows, ordered by cpu number.
+fn cpu_names(rows: &[&SchedStatInfo]) -> Vec<String> {
+    rows.iter()
+        .map(|s| {
+            (
+                s.cpu_name[3..].parse::<usize>().unwrap_or_default(),
+                s.cpu_name.clone(),
+            )
+        })
+        .collect::<BTreeSet<(usize, String)>>()
+        .into_iter()
+        .map(|(_, cpu_name)| cpu_name)
+        .collect()
+}
+
+fn schedstat_per_cpu_plot<DB: DrawingBackend>(
+    backend: &mut DrawingArea<DB, Shift>,
+    caption: &str,
+    y_desc: &str,
+    value: fn(&SchedStatInfo) -> f64,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) {
+    backend.fill(&WHITE).unwrap();
+    let historical_data_read = DATA.schedstat.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |s| s.timestamp,
+        start_time,
+        end_time,
+    );
+    let final_start_time = rows.iter().map(|s| s.timestamp).min().unwrap_or_default();
+    let final_end_time = rows.iter().map(|s| s.timestamp).max().unwrap_or_default();
+    let high_value = rows
+        .iter()
+        .map(|s| value(s) * 1.1_f64)
+        .max_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or_default();
+
+    // create the plot
+    let mut contextarea = ChartBuilder::on(backend)
+        .set_label_area_size(LabelAreaPosition::Left, LABEL_AREA_SIZE_LEFT)
+        .set_label_area_size(LabelAreaPosition::Bottom, LABEL_AREA_SIZE_BOTTOM)
+        .caption(caption, (CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE))
+        .build_cartesian_2d(final_start_time..final_end_time, 0_f64..high_value)
+        .unwrap();
+    contextarea
+        .configure_mesh()
+        .x_label_formatter(&|timestamp| timestamp.format("%H:%M:%S").to_string())
+        .x_desc(format!(
+            "Time: {} to {} ({} minutes)",
+            final_start_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            final_end_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            (final_end_time - final_start_time).num_minutes(),
+        ))
+        .y_desc(y_desc)
+        .y_label_formatter(&|value| {
+            if value == &0_f64 {
+                format!("{:5.0}", value)
+            } else if value < &1_f64 {
+                format!("{:5.3}", value)
+            } else {
+                format!("{:5.1}", value)
+            }
+        })
+        .label_style((MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE))
+        .draw()
+        .unwrap();
+    //
+    // This is a dummy plot for the sole intention to write a header in the legend.
+    contextarea
+        .draw_series(LineSeries::new(
+            std::iter::once((final_start_time, 0_f64)),
+            ShapeStyle {
+                color: TRANSPARENT,
+                filled: false,
+                stroke_width: 1,
+            },
+        ))
+        .unwrap()
+        .label(format!(
+            "{:25} {:>10} {:>10} {:>10}",
+            "", "min", "max", "last"
+        ));
+    for (index, cpu_name) in cpu_names(&rows).iter().enumerate() {
+        let cpu_values = rows
+            .iter()
+            .filter(|s| &s.cpu_name == cpu_name)
+            .map(|s| (s.timestamp, value(s)))
+            .collect::<Vec<(DateTime<Local>, f64)>>();
+        let min = cpu_values
+            .iter()
+            .map(|(_, value)| *value)
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap_or_default();
+        let max = cpu_values
+            .iter()
+            .map(|(_, value)| *value)
+            .max_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap_or_default();
+        let last = cpu_values.last().map_or(0_f64, |(_, value)| *value);
+        let colour = Palette99::pick(index);
+        contextarea
+            .draw_series(LineSeries::new(cpu_values, &colour))
+            .unwrap()
+            .label(format!(
+                "{:25} {:10.3} {:10.3} {:10.3}",
+                cpu_name, min, max, last
+            ))
+            .legend(move |(x, y)| {
+                Rectangle::new([(x - 3, y - 3), (x + 3, y + 3)], colour.filled())
+            });
+    }
+    // legend
+    contextarea
+        .configure_series_labels()
+        .border_style(BLACK)
+        .background_style(WHITE.mix(0.7))
+        .label_font((LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE))
+        .position(UpperLeft)
+        .draw()
+        .unwrap();
+}
+
+/// The run queue wait time per CPU.
+pub fn schedstat_wait_plot<DB: DrawingBackend>(
+    backend: &mut DrawingArea<DB, Shift>,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) {
+    schedstat_per_cpu_plot(
+        backend,
+        "Scheduler: run queue wait time per CPU",
+        "Wait time per second",
+        |s| s.time_waiting,
+        start_time,
+        end_time,
+    );
+}
+
+/// The average wait per timeslice per CPU.
+pub fn schedstat_wait_per_timeslice_plot<DB: DrawingBackend>(
+    backend: &mut DrawingArea<DB, Shift>,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) {
+    schedstat_per_cpu_plot(
+        backend,
+        "Scheduler: average wait per timeslice per CPU",
+        "Wait per timeslice (ms)",
+        SchedStatInfo::wait_per_timeslice_ms,
+        start_time,
+        end_time,
+    );
+}
+
+fn schedstat_per_cpu_panel(
+    title: &str,
+    y_desc: &str,
+    value: fn(&SchedStatInfo) -> f64,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.schedstat.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |s| s.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: title.to_string(),
+        y_desc: y_desc.to_string(),
+        series: cpu_names(&rows)
+            .iter()
+            .map(|cpu_name| {
+                let cpu_rows = rows
+                    .iter()
+                    .filter(|s| &s.cpu_name == cpu_name)
+                    .copied()
+                    .collect::<Vec<&SchedStatInfo>>();
+                Series::new(cpu_name, &cpu_rows, |s| (s.timestamp, value(s)))
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+pub fn schedstat_wait_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    schedstat_per_cpu_panel(
+        "Scheduler: run queue wait time per CPU",
+        "Wait time per second",
+        |s| s.time_waiting,
+        start_time,
+        end_time,
+    )
+}
+
+pub fn schedstat_wait_per_timeslice_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    schedstat_per_cpu_panel(
+        "Scheduler: average wait per timeslice per CPU",
+        "Wait per timeslice (ms)",
+        SchedStatInfo::wait_per_timeslice_ms,
+        start_time,
+        end_time,
+    )
+}
@@ -1,54 +1,21 @@
-use plotters::backend::RGBPixel;
 use plotters::chart::SeriesLabelPosition::UpperLeft;
 use plotters::coord::Shift;
 use plotters::prelude::*;
 use plotters::style::full_palette::{GREEN_A400, GREY, LIGHTBLUE, PURPLE, YELLOW_600};
 
-use crate::webserver::loadavg::load_plot;
-use crate::webserver::pressure::pressure_cpu_some_plot;
-use crate::{ARGS, DATA};
+use crate::processor::stat::PerCpuStat;
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
+use crate::DATA;
 use crate::{
     CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
     LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, LABEL_AREA_SIZE_RIGHT, MESH_STYLE_FONT,
     MESH_STYLE_FONT_SIZE,
 };
 use chrono::{DateTime, Local};
+use std::collections::BTreeSet;
 
-pub fn create_cpu_load_pressure_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((3, 1));
-    cpu_total_plot(&mut multi_backend, 0, start_time, end_time);
-    load_plot(&mut multi_backend, 1, start_time, end_time);
-    pressure_cpu_some_plot(&mut multi_backend, 2, start_time, end_time);
-}
-pub fn create_cpu_load_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((2, 1));
-    cpu_total_plot(&mut multi_backend, 0, start_time, end_time);
-    load_plot(&mut multi_backend, 1, start_time, end_time);
-}
-pub fn create_cpu_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((1, 1));
-    cpu_total_plot(&mut multi_backend, 0, start_time, end_time);
-}
-fn cpu_total_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub(crate) fn cpu_total_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -572,3 +539,290 @@ fn cpu_total_plot(
         .draw()
         .unwrap();
 }
+
+pub fn cpu_total_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.cpu.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |c| c.timestamp,
+        start_time,
+        end_time,
+    );
+    let mut series = vec![
+        Series::new("scheduler run", &rows, |c| {
+            (c.timestamp, c.scheduler_running)
+        })
+        .stacked("scheduler"),
+        Series::new("scheduler wait", &rows, |c| {
+            (c.timestamp, c.scheduler_waiting)
+        })
+        .stacked("scheduler"),
+    ];
+    macro_rules! add_stacked_cpu_series {
+        ($([$struct_field_name:ident, $name:expr]),*) => {
+            $(
+                series.push(
+                    Series::new($name, &rows, |c| (c.timestamp, c.$struct_field_name)).stacked("cpu"),
+                );
+            )*
+        };
+    }
+    add_stacked_cpu_series!(
+        [user, "user"],
+        [nice, "nice"],
+        [system, "system"],
+        [iowait, "iowait"],
+        [steal, "steal"],
+        [irq, "irq"],
+        [softirq, "softirq"],
+        [guest, "guest_user"],
+        [guest_nice, "guest_nice"]
+    );
+    series.push(Series::new("total (v)cpu", &rows, |c| {
+        (
+            c.timestamp,
+            (c.guest_nice
+                + c.guest
+                + c.idle
+                + c.softirq
+                + c.irq
+                + c.steal
+                + c.iowait
+                + c.system
+                + c.nice
+                + c.user)
+                .round(),
+        )
+    }));
+    Panel {
+        title: "Total CPU usage".to_string(),
+        y_desc: "CPU per second".to_string(),
+        series,
+        ..Default::default()
+    }
+}
+
+/// The metrics of the per-CPU heatmap, as percentage of the time of the CPU.
+pub static CPU_HEATMAP_METRICS: [&str; 6] =
+    ["busy", "user", "system", "iowait", "steal", "softirq"];
+/// The maximal number of time columns of the per-CPU heatmap; samples are averaged to fit.
+static CPU_HEATMAP_COLUMNS: usize = 300;
+
+fn cpu_heatmap_value(metric: &str, c: &PerCpuStat) -> f64 {
+    match metric {
+        "user" => c.percentage(c.user + c.nice),
+        "system" => c.percentage(c.system),
+        "iowait" => c.percentage(c.iowait),
+        "steal" => c.percentage(c.steal),
+        "softirq" => c.percentage(c.softirq),
+        _ => 100_f64 - c.percentage(c.idle),
+    }
+}
+
+fn cpu_number(cpu_name: &str) -> usize {
+    cpu_name
+        .trim_start_matches("cpu")
+        .parse()
+        .unwrap_or_default()
+}
+
+pub(crate) fn cpu_heatmap_plot<DB: DrawingBackend>(
+    backend: &mut DrawingArea<DB, Shift>,
+    metric: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) {
+    backend.fill(&WHITE).unwrap();
+    let historical_data_read = DATA.per_cpu.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |c| c.timestamp,
+        start_time,
+        end_time,
+    );
+    let final_start_time = rows.iter().map(|c| c.timestamp).min().unwrap_or_default();
+    let final_end_time = rows.iter().map(|c| c.timestamp).max().unwrap_or_default();
+    let cpus = rows
+        .iter()
+        .map(|c| cpu_number(&c.cpu_name))
+        .max()
+        .map_or(0, |cpu| cpu + 1);
+    let samples = rows
+        .iter()
+        .map(|c| c.timestamp)
+        .collect::<BTreeSet<DateTime<Local>>>()
+        .len();
+    let columns = samples.clamp(1, CPU_HEATMAP_COLUMNS);
+    // the sum and the number of the values per column and cpu, for the average.
+    let mut heatmap = vec![vec![(0_f64, 0_usize); cpus]; columns];
+    let range = (final_end_time - final_start_time).num_milliseconds();
+    for c in &rows {
+        let column = if range == 0 {
+            0
+        } else {
+            (((c.timestamp - final_start_time).num_milliseconds() * columns as i64 / range)
+                as usize)
+                .min(columns - 1)
+        };
+        let cell = &mut heatmap[column][cpu_number(&c.cpu_name)];
+        cell.0 += cpu_heatmap_value(metric, c);
+        cell.1 += 1;
+    }
+    let column_time = |column: usize| {
+        final_start_time + (final_end_time - final_start_time) * column as i32 / columns as i32
+    };
+    let color = |percentage: f64| ViridisRGB.get_color_normalized(percentage, 0_f64, 100_f64);
+
+    // create the plot
+    let mut contextarea = ChartBuilder::on(backend)
+        .set_label_area_size(LabelAreaPosition::Left, LABEL_AREA_SIZE_LEFT)
+        .set_label_area_size(LabelAreaPosition::Bottom, LABEL_AREA_SIZE_BOTTOM)
+        .caption(
+            format!("Per CPU usage: %{}", metric),
+            (CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE),
+        )
+        .build_cartesian_2d(
+            final_start_time..final_end_time,
+            (0..cpus.max(1) - 1).into_segmented(),
+        )
+        .unwrap();
+    contextarea
+        .configure_mesh()
+        .disable_mesh()
+        .x_label_formatter(&|timestamp| timestamp.format("%H:%M:%S").to_string())
+        .x_desc(format!(
+            "Time: {} to {} ({} minutes)",
+            final_start_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            final_end_time.format("%Y-%m-%d %H:%M:%S%:z"),
+            (final_end_time - final_start_time).num_minutes(),
+        ))
+        .y_desc("CPU")
+        .y_labels(cpus.clamp(1, 64))
+        .y_label_formatter(&|cpu| match cpu {
+            SegmentValue::CenterOf(cpu) => format!("cpu{}", cpu),
+            _ => String::new(),
+        })
+        .label_style((MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE))
+        .draw()
+        .unwrap();
+    contextarea
+        .draw_series(
+            heatmap
+                .iter()
+                .enumerate()
+                .flat_map(|(column, cpus)| {
+                    cpus.iter()
+                        .enumerate()
+                        .filter(|(_, (_, count))| *count > 0)
+                        .map(move |(cpu, (sum, count))| (column, cpu, sum / *count as f64))
+                })
+                .map(|(column, cpu, percentage)| {
+                    Rectangle::new(
+                        [
+                            (column_time(column), SegmentValue::Exact(cpu)),
+                            (column_time(column + 1), SegmentValue::Exact(cpu + 1)),
+                        ],
+                        color(percentage).filled(),
+                    )
+                }),
+        )
+        .unwrap();
+    //
+    // This is a dummy plot for the sole intention to write a header in the legend.
+    contextarea
+        .draw_series(LineSeries::new(
+            std::iter::once((final_start_time, SegmentValue::Exact(0))),
+            ShapeStyle {
+                color: TRANSPARENT,
+                filled: false,
+                stroke_width: 1,
+            },
+        ))
+        .unwrap()
+        .label(format!("{:>10}", format!("%{}", metric)));
+    for percentage in [100_f64, 75_f64, 50_f64, 25_f64, 0_f64] {
+        contextarea
+            .draw_series(LineSeries::new(
+                std::iter::once((final_start_time, SegmentValue::Exact(0))),
+                TRANSPARENT,
+            ))
+            .unwrap()
+            .label(format!("{:10.0}", percentage))
+            .legend(move |(x, y)| {
+                Rectangle::new([(x - 3, y - 3), (x + 3, y + 3)], color(percentage).filled())
+            });
+    }
+    // legend
+    contextarea
+        .configure_series_labels()
+        .border_style(BLACK)
+        .background_style(WHITE.mix(0.7))
+        .label_font((LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE))
+        .position(SeriesLabelPosition::UpperRight)
+        .draw()
+        .unwrap();
+}
+
+/// The interactive equivalent of the per-CPU heatmap: a line per CPU.
+pub fn cpu_heatmap_panel(
+    metric: &str,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.per_cpu.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |c| c.timestamp,
+        start_time,
+        end_time,
+    );
+    let cpu_names = rows
+        .iter()
+        .map(|c| (cpu_number(&c.cpu_name), c.cpu_name.clone()))
+        .collect::<BTreeSet<(usize, String)>>();
+    Panel {
+        title: format!("Per CPU usage: %{}", metric),
+        y_desc: "%".to_string(),
+        series: cpu_names
+            .iter()
+            .map(|(_, cpu_name)| {
+                let cpu_rows = rows
+                    .iter()
+                    .filter(|c| &c.cpu_name == cpu_name)
+                    .copied()
+                    .collect::<Vec<&PerCpuStat>>();
+                Series::new(cpu_name, &cpu_rows, |c| {
+                    (c.timestamp, cpu_heatmap_value(metric, c))
+                })
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_heatmap_values_are_percentages_of_the_time_of_the_cpu() {
+        let c = PerCpuStat {
+            cpu_name: "cpu12".to_string(),
+            user: 0.2,
+            nice: 0.05,
+            system: 0.15,
+            idle: 0.5,
+            iowait: 0.1,
+            ..Default::default()
+        };
+        assert_eq!(cpu_number(&c.cpu_name), 12);
+        assert!((cpu_heatmap_value("busy", &c) - 50_f64).abs() < 1e-9);
+        assert!((cpu_heatmap_value("user", &c) - 25_f64).abs() < 1e-9);
+        assert!((cpu_heatmap_value("iowait", &c) - 10_f64).abs() < 1e-9);
+        // the time of a CPU without time is not divided by zero.
+        assert_eq!(cpu_heatmap_value("system", &PerCpuStat::default()), 0_f64);
+    }
+}
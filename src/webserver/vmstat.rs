@@ -1,13 +1,11 @@
-use crate::webserver::meminfo::memory_plot;
-use crate::webserver::pressure::pressure_memory_plot;
-use crate::{ARGS, DATA};
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
+use crate::DATA;
 use crate::{
     CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
     LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, LABEL_AREA_SIZE_RIGHT, MESH_STYLE_FONT,
     MESH_STYLE_FONT_SIZE,
 };
 use chrono::{DateTime, Local};
-use plotters::backend::{BitMapBackend, RGBPixel};
 use plotters::chart::{ChartBuilder, LabelAreaPosition, SeriesLabelPosition::UpperLeft};
 use plotters::coord::Shift;
 use plotters::drawing::DrawingArea;
@@ -17,33 +15,8 @@ use plotters::style::full_palette::{
     BLUE_300, BLUE_900, LIGHTGREEN_300, LIGHTGREEN_900, ORANGE_300, ORANGE_900, PURPLE,
 };
 
-pub fn create_memory_alloc_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((2, 1));
-    memory_plot(&mut multi_backend, 0, start_time, end_time);
-    pages_allocated_and_free(&mut multi_backend, 1, start_time, end_time)
-}
-
-pub fn create_memory_alloc_psi_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let mut multi_backend = backend.split_evenly((3, 1));
-    memory_plot(&mut multi_backend, 0, start_time, end_time);
-    pages_allocated_and_free(&mut multi_backend, 1, start_time, end_time);
-    pressure_memory_plot(&mut multi_backend, 2, start_time, end_time);
-}
-
-pub fn swap_inout_plot(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub fn swap_inout_plot<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -213,8 +186,8 @@ pub fn swap_inout_plot(
         .unwrap();
 }
 
-pub fn pages_allocated_and_free(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub fn pages_allocated_and_free<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -719,8 +692,8 @@ pub fn pages_allocated_and_free(
         .unwrap();
 }
 
-pub fn pages_dirty(
-    multi_backend: &mut [DrawingArea<BitMapBackend<RGBPixel>, Shift>],
+pub fn pages_dirty<DB: DrawingBackend>(
+    multi_backend: &mut [DrawingArea<DB, Shift>],
     backend_number: usize,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
@@ -745,7 +718,7 @@ pub fn pages_dirty(
             .unwrap_or_default()
     };
     let latest = historical_data_read.back();
-    let high_value= historical_data_read
+    let high_value = historical_data_read
         .iter()
         .filter(|v| v.timestamp >= final_start_time && v.timestamp <= final_end_time)
         .map(|v| v.nr_dirty_threshold * 1.1_f64)
@@ -758,10 +731,7 @@ pub fn pages_dirty(
         .set_label_area_size(LabelAreaPosition::Left, LABEL_AREA_SIZE_LEFT)
         .set_label_area_size(LabelAreaPosition::Bottom, LABEL_AREA_SIZE_BOTTOM)
         .set_label_area_size(LabelAreaPosition::Right, LABEL_AREA_SIZE_RIGHT)
-        .caption(
-            "Pages dirty",
-            (CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE),
-        )
+        .caption("Pages dirty", (CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE))
         .build_cartesian_2d(final_start_time..final_end_time, 0_f64..high_value)
         .unwrap();
     contextarea
@@ -924,3 +894,104 @@ pub fn pages_dirty(
         .draw()
         .unwrap();
 }
+
+pub fn swap_inout_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.vmstat.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |v| v.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "Swap IO".to_string(),
+        y_desc: "Swap IO (pages)".to_string(),
+        series: vec![
+            Series::new("total", &rows, |v| (v.timestamp, v.pswpin + v.pswpout)),
+            Series::new("pages swap out", &rows, |v| (v.timestamp, v.pswpout)).as_points(),
+            Series::new("pages swap in", &rows, |v| (v.timestamp, v.pswpin)).as_points(),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn pages_allocated_and_free_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.vmstat.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |v| v.timestamp,
+        start_time,
+        end_time,
+    );
+    let mut series = vec![
+        Series::new("pgfree", &rows, |v| (v.timestamp, v.pgfree)),
+        Series::new("pgfault", &rows, |v| (v.timestamp, v.pgfault_delta)),
+        Series::new("pgalloc", &rows, |v| {
+            (
+                v.timestamp,
+                v.pgalloc_dma
+                    + v.pgalloc_dma32
+                    + v.pgalloc_normal
+                    + v.pgalloc_device
+                    + v.pgalloc_movable,
+            )
+        }),
+    ];
+    macro_rules! add_vmstat_series {
+        ($($struct_field_name:ident),*) => {
+            $(
+                series.push(Series::new(stringify!($struct_field_name), &rows, |v| {
+                    (v.timestamp, v.$struct_field_name)
+                }));
+            )*
+        };
+    }
+    add_vmstat_series!(
+        pgsteal_kswapd,
+        pgscan_kswapd,
+        pgsteal_direct,
+        pgscan_direct,
+        pgsteal_khugepaged,
+        pgscan_khugepaged,
+        oom_kill
+    );
+    Panel {
+        title: "Pages allocated and freed".to_string(),
+        y_desc: "Pages".to_string(),
+        series,
+        ..Default::default()
+    }
+}
+
+pub fn pages_dirty_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.vmstat.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |v| v.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "Pages dirty".to_string(),
+        y_desc: "Pages".to_string(),
+        series: vec![
+            Series::new("nr_dirty", &rows, |v| (v.timestamp, v.nr_dirty)).stacked("dirty"),
+            Series::new("nr_dirty_threshold", &rows, |v| {
+                (v.timestamp, v.nr_dirty_threshold)
+            }),
+            Series::new("nr_dirty_background_threshold", &rows, |v| {
+                (v.timestamp, v.nr_dirty_background_threshold)
+            }),
+        ],
+        ..Default::default()
+    }
+}
@@ -1,7 +1,5 @@
-use crate::ARGS;
 use chrono::{DateTime, Local};
 use ordered_float::OrderedFloat;
-use plotters::backend::{BitMapBackend, RGBPixel};
 use plotters::chart::SeriesLabelPosition::UpperLeft;
 use plotters::chart::{ChartBuilder, LabelAreaPosition};
 use plotters::coord::Shift;
@@ -9,29 +7,14 @@ use plotters::drawing::DrawingArea;
 use plotters::prelude::*;
 use std::collections::BTreeSet;
 
+use crate::webserver::interactive::{rows_in_range, Panel, Series};
 use crate::{
     CAPTION_STYLE_FONT, CAPTION_STYLE_FONT_SIZE, DATA, LABELS_STYLE_FONT, LABELS_STYLE_FONT_SIZE,
     LABEL_AREA_SIZE_BOTTOM, LABEL_AREA_SIZE_LEFT, MESH_STYLE_FONT, MESH_STYLE_FONT_SIZE,
 };
 
-pub fn create_xfs_plot(
-    buffer: &mut [u8],
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-) {
-    let backend = BitMapBackend::with_buffer(buffer, (ARGS.graph_width, ARGS.graph_height))
-        .into_drawing_area();
-    let multi_backend = backend.split_evenly((2, 1));
-    let mut mbps_graph = multi_backend[0].split_horizontally((60).percent_width());
-    xfs_mbps_plot(&mut mbps_graph.0, start_time, end_time);
-    xfs_mbps_percentile_plot(&mut mbps_graph.1, start_time, end_time);
-    let mut iops_graph = multi_backend[1].split_horizontally((60).percent_width());
-    xfs_iops_plot(&mut iops_graph.0, start_time, end_time);
-    xfs_iops_percentile_plot(&mut iops_graph.1, start_time, end_time);
-}
-
-fn xfs_iops_percentile_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn xfs_iops_percentile_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
 ) {
@@ -283,8 +266,8 @@ fn xfs_iops_percentile_plot(
         .draw()
         .unwrap();
 }
-pub fn xfs_iops_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub fn xfs_iops_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
 ) {
@@ -451,8 +434,8 @@ pub fn xfs_iops_plot(
         .draw()
         .unwrap();
 }
-fn xfs_mbps_percentile_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub(crate) fn xfs_mbps_percentile_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
 ) {
@@ -695,8 +678,8 @@ fn xfs_mbps_percentile_plot(
         .draw()
         .unwrap();
 }
-pub fn xfs_mbps_plot(
-    multi_backend: &mut DrawingArea<BitMapBackend<RGBPixel>, Shift>,
+pub fn xfs_mbps_plot<DB: DrawingBackend>(
+    multi_backend: &mut DrawingArea<DB, Shift>,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
 ) {
@@ -888,3 +871,62 @@ pub fn xfs_mbps_plot(
         .draw()
         .unwrap();
 }
+
+pub fn xfs_iops_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.xfs.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |x| x.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "XFS IOPS".to_string(),
+        y_desc: "IOPS".to_string(),
+        series: vec![
+            Series::new("total", &rows, |x| {
+                (x.timestamp, x.xs_read_calls + x.xs_write_calls)
+            }),
+            Series::new("write", &rows, |x| (x.timestamp, x.xs_write_calls)).as_points(),
+            Series::new("read", &rows, |x| (x.timestamp, x.xs_read_calls)).as_points(),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn xfs_mbps_panel(
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+) -> Panel {
+    let historical_data_read = DATA.xfs.read().unwrap();
+    let rows = rows_in_range(
+        historical_data_read.iter(),
+        |x| x.timestamp,
+        start_time,
+        end_time,
+    );
+    Panel {
+        title: "XFS MBPS".to_string(),
+        y_desc: "MBPS".to_string(),
+        series: vec![
+            Series::new("total", &rows, |x| {
+                (
+                    x.timestamp,
+                    (x.xs_read_bytes + x.xs_write_bytes) / (1024_f64 * 1024_f64),
+                )
+            }),
+            Series::new("write", &rows, |x| {
+                (x.timestamp, x.xs_write_bytes / (1024_f64 * 1024_f64))
+            })
+            .as_points(),
+            Series::new("read", &rows, |x| {
+                (x.timestamp, x.xs_read_bytes / (1024_f64 * 1024_f64))
+            })
+            .as_points(),
+        ],
+        ..Default::default()
+    }
+}